@@ -0,0 +1,53 @@
+use std::time::Instant;
+
+use my_xml_reader::MyXmlReader;
+
+const DOCUMENT_SIZE: usize = 100 * 1024 * 1024;
+const DEPTH: usize = 1000;
+
+fn build_deeply_nested_document() -> Vec<u8> {
+    let mut xml = Vec::with_capacity(DOCUMENT_SIZE + 64 * 1024);
+    xml.extend_from_slice(b"<?xml version=\"1.0\" encoding=\"utf-8\"?><Root>");
+
+    while xml.len() < DOCUMENT_SIZE {
+        for level in 0..DEPTH {
+            xml.extend_from_slice(format!("<Level{} id=\"{}\">", level % 16, level).as_bytes());
+        }
+
+        xml.extend_from_slice(b"<Leaf>Value</Leaf><Empty/>");
+
+        for level in (0..DEPTH).rev() {
+            xml.extend_from_slice(format!("</Level{}>", level % 16).as_bytes());
+        }
+    }
+
+    xml.extend_from_slice(b"</Root>");
+    xml
+}
+
+fn main() {
+    let xml = build_deeply_nested_document();
+
+    for _ in 0..3 {
+        let started = Instant::now();
+
+        let mut reader = MyXmlReader::from_slice(&xml).unwrap();
+        let mut tags = 0;
+
+        while reader.read_next_tag().unwrap().is_some() {
+            tags += 1;
+        }
+
+        let elapsed = started.elapsed();
+        let mb = xml.len() as f64 / (1024.0 * 1024.0);
+
+        println!(
+            "read_next_tag: {:.1} MB, depth {}, {} tags in {:?} ({:.1} MB/s)",
+            mb,
+            DEPTH,
+            tags,
+            elapsed,
+            mb / elapsed.as_secs_f64()
+        );
+    }
+}
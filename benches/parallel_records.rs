@@ -0,0 +1,65 @@
+use std::time::Instant;
+
+use my_xml_reader::my_xml_reader::{read_top_level_elements_in_parallel, ReaderOptions};
+
+const DOCUMENT_SIZE: usize = 64 * 1024 * 1024;
+
+fn build_export_document() -> Vec<u8> {
+    let mut xml = Vec::with_capacity(DOCUMENT_SIZE + 1024);
+    xml.extend_from_slice(b"<?xml version=\"1.0\" encoding=\"utf-8\"?><Export>");
+
+    let mut id = 0;
+    while xml.len() < DOCUMENT_SIZE {
+        xml.extend_from_slice(
+            format!(
+                "<Record id=\"{}\"><Amount currency=\"EUR\">{}.50</Amount><Comment>Payment &amp; fee {}</Comment></Record>",
+                id, id, id
+            )
+            .as_bytes(),
+        );
+        id += 1;
+    }
+
+    xml.extend_from_slice(b"</Export>");
+    xml
+}
+
+fn main() {
+    let xml = build_export_document();
+    let mb = xml.len() as f64 / (1024.0 * 1024.0);
+
+    let max_threads = std::thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1);
+
+    let options = ReaderOptions::default();
+    let mut threads_amount = 1;
+
+    loop {
+        let started = Instant::now();
+
+        let amounts =
+            read_top_level_elements_in_parallel(&xml, &options, threads_amount, |mut reader| {
+                let amount = reader.find_the_open_node("Record/Amount")?.unwrap();
+                let value = reader.read_the_whole_node(amount)?.get_value()?.unwrap();
+                Ok(value.len())
+            })
+            .unwrap();
+
+        let elapsed = started.elapsed();
+
+        println!(
+            "{:>3} threads: {} records in {:?} ({:.1} MB/s)",
+            threads_amount,
+            amounts.len(),
+            elapsed,
+            mb / elapsed.as_secs_f64()
+        );
+
+        if threads_amount >= max_threads {
+            break;
+        }
+
+        threads_amount = (threads_amount * 2).min(max_threads);
+    }
+}
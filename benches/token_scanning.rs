@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use my_xml_reader::my_xml_reader::xml_utils;
+use my_xml_reader::MyXmlReader;
+
+const DOCUMENT_SIZE: usize = 32 * 1024 * 1024;
+
+fn build_records_document() -> Vec<u8> {
+    let mut xml = Vec::with_capacity(DOCUMENT_SIZE + 1024);
+    xml.extend_from_slice(b"<?xml version=\"1.0\" encoding=\"utf-8\"?><Records>");
+
+    let mut id = 0;
+    while xml.len() < DOCUMENT_SIZE {
+        xml.extend_from_slice(
+            format!(
+                "<Record id=\"{}\" type=\"payment\"><Amount currency=\"EUR\">{}.50</Amount><Comment>Payment number {}</Comment></Record>",
+                id, id, id
+            )
+            .as_bytes(),
+        );
+        id += 1;
+    }
+
+    xml.extend_from_slice(b"</Records>");
+    xml
+}
+
+fn build_text_document() -> Vec<u8> {
+    let mut xml = Vec::with_capacity(DOCUMENT_SIZE + 1024);
+    xml.extend_from_slice(b"<Articles>");
+
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. ".repeat(32);
+
+    while xml.len() < DOCUMENT_SIZE {
+        xml.extend_from_slice(b"<Article title=\"Some title\"><Body>");
+        xml.extend_from_slice(paragraph.as_bytes());
+        xml.extend_from_slice(b"</Body></Article>");
+    }
+
+    xml.extend_from_slice(b"</Articles>");
+    xml
+}
+
+fn build_long_header_document() -> Vec<u8> {
+    let mut xml = Vec::with_capacity(DOCUMENT_SIZE + 1024);
+    xml.extend_from_slice(b"<?xml version=\"1.0\" ");
+    xml.resize(DOCUMENT_SIZE, b' ');
+    xml.extend_from_slice(b"?><Root/>");
+    xml
+}
+
+fn naive_find_next_token(xml: &[u8], token_to_find: u8, start_pos: usize) -> Option<usize> {
+    (start_pos..xml.len()).find(|pos| xml[*pos] == token_to_find)
+}
+
+fn naive_find_next_token_ext(xml: &[u8], token_to_find: &[u8], start_pos: usize) -> Option<usize> {
+    (start_pos..xml.len() - token_to_find.len() + 1)
+        .find(|pos| &xml[*pos..*pos + token_to_find.len()] == token_to_find)
+}
+
+fn measure(name: &str, size: usize, mut action: impl FnMut() -> usize) {
+    let mut best = Duration::MAX;
+    let mut result = 0;
+
+    for _ in 0..5 {
+        let started = Instant::now();
+        result = std::hint::black_box(action());
+        best = best.min(started.elapsed());
+    }
+
+    let mb = size as f64 / (1024.0 * 1024.0);
+
+    println!(
+        "{:<40} {:>10} {:>10.2?} {:>10.1} MB/s",
+        name,
+        result,
+        best,
+        mb / best.as_secs_f64()
+    );
+}
+
+fn count_tokens(xml: &[u8], find: impl Fn(&[u8], u8, usize) -> Option<usize>) -> usize {
+    let mut pos = 0;
+    let mut count = 0;
+
+    while let Some(found) = find(xml, b'<', pos) {
+        count += 1;
+        pos = found + 1;
+    }
+
+    count
+}
+
+fn read_all_tags(xml: &[u8]) -> usize {
+    let mut reader = MyXmlReader::from_slice(xml).unwrap();
+    let mut tags = 0;
+
+    while reader.read_next_tag().unwrap().is_some() {
+        tags += 1;
+    }
+
+    tags
+}
+
+fn main() {
+    println!(
+        "memchr feature: {}",
+        if cfg!(feature = "memchr") {
+            "on"
+        } else {
+            "off"
+        }
+    );
+
+    let documents = [
+        ("records", build_records_document()),
+        ("text", build_text_document()),
+    ];
+
+    for (name, xml) in documents.iter() {
+        measure(&format!("{}: naive '<' scan", name), xml.len(), || {
+            count_tokens(xml, naive_find_next_token)
+        });
+
+        measure(
+            &format!("{}: find_next_token '<' scan", name),
+            xml.len(),
+            || count_tokens(xml, xml_utils::find_next_token),
+        );
+
+        measure(&format!("{}: read_next_tag", name), xml.len(), || {
+            read_all_tags(xml)
+        });
+    }
+
+    let xml = build_long_header_document();
+
+    measure("header: naive '?>' search", xml.len(), || {
+        naive_find_next_token_ext(&xml, b"?>", 0).unwrap()
+    });
+
+    measure("header: find_next_token_ext '?>' search", xml.len(), || {
+        xml_utils::find_next_token_ext(&xml, b"?>", 0).unwrap()
+    });
+}
@@ -0,0 +1,83 @@
+use my_xml_reader::my_xml_reader::xml_utils::{find_markup_end, OPEN_CDATA_TOKEN};
+
+#[derive(Debug, PartialEq)]
+pub enum ContentPart<'t> {
+    Text(&'t [u8]),
+    CData(&'t [u8]),
+    // Comments, processing instructions and the DOCTYPE
+    Markup(&'t [u8]),
+}
+
+// Splits the content between two tags. Tags themselves never get here, so
+// any '<' starts a comment, CDATA section, processing instruction or DOCTYPE.
+// Broken markup read in lenient mode is kept as text.
+pub fn split_content(content: &[u8]) -> Vec<ContentPart<'_>> {
+    let mut result = Vec::new();
+    let mut pos = 0;
+
+    while pos < content.len() {
+        let Some(markup_start) = content[pos..].iter().position(|b| *b == b'<') else {
+            result.push(ContentPart::Text(&content[pos..]));
+            break;
+        };
+
+        let markup_start = pos + markup_start;
+
+        let Some(markup_end) = find_markup_end(content, markup_start) else {
+            result.push(ContentPart::Text(&content[pos..]));
+            break;
+        };
+
+        if markup_start > pos {
+            result.push(ContentPart::Text(&content[pos..markup_start]));
+        }
+
+        let markup = &content[markup_start..=markup_end];
+
+        result.push(if markup.starts_with(OPEN_CDATA_TOKEN) {
+            ContentPart::CData(markup)
+        } else {
+            ContentPart::Markup(markup)
+        });
+
+        pos = markup_end + 1;
+    }
+
+    result
+}
+
+pub fn is_whitespace_only(text: &[u8]) -> bool {
+    text.iter()
+        .all(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_content() {
+        let content =
+            b"\n  text <!-- a > b --><![CDATA[<x>]]><?pi ?>\n<!DOCTYPE R [<!ENTITY e \"v\">]> tail";
+
+        assert_eq!(
+            vec![
+                ContentPart::Text(b"\n  text "),
+                ContentPart::Markup(b"<!-- a > b -->"),
+                ContentPart::CData(b"<![CDATA[<x>]]>"),
+                ContentPart::Markup(b"<?pi ?>"),
+                ContentPart::Text(b"\n"),
+                ContentPart::Markup(b"<!DOCTYPE R [<!ENTITY e \"v\">]>"),
+                ContentPart::Text(b" tail"),
+            ],
+            split_content(content)
+        );
+
+        assert_eq!(
+            vec![ContentPart::Text(b"a <!-- broken")],
+            split_content(b"a <!-- broken")
+        );
+
+        assert!(split_content(b"").is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+mod content;
+mod query;
+mod reformat;
+mod stats;
+mod validate;
+
+use std::io::{Read, Write};
+use std::ops::Deref;
+use std::process::ExitCode;
+
+use my_xml_reader::my_xml_reader::XmlReaderMode;
+use my_xml_reader::MyXmlReader;
+
+const USAGE: &str = "Usage: my-xml [query] [OPTIONS] <PATH> [FILE]
+       my-xml validate [FILE]
+       my-xml fmt [--indent <N>] [FILE]
+       my-xml minify [FILE]
+       my-xml stats [FILE]
+
+Commands:
+  query     print nodes matching PATH, such as Root/Items/Item. Segments are
+            matched from the root element, '*' matches any name. This is the
+            default command
+  validate  check that the document is well-formed and print errors with
+            their line and column
+  fmt       pretty-print the document
+  minify    remove whitespace between tags
+  stats     print element and attribute counts by name, the max depth and
+            the size taken by tags, text and other markup
+
+FILE is read from stdin when it is omitted or '-'. Files are memory mapped,
+so documents larger than the available memory can be processed.
+
+Options:
+  -a, --all          print all matches instead of the first one
+  -v, --value        print values instead of the XML of nodes
+  -t, --attr <NAME>  print the value of the attribute NAME
+      --indent <N>   indent fmt output with N spaces, 2 by default
+      --strict       fail on any well-formedness error
+      --lenient      recover from common breakage
+  -h, --help         print this help
+
+Exit codes: 0 success, 1 not found, 2 XML error, 3 usage or I/O error";
+
+const DEFAULT_INDENT: usize = 2;
+
+#[derive(Debug)]
+enum CliError {
+    NotFound,
+    Xml(String),
+    Usage(String),
+    Io(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Self::NotFound => 1,
+            Self::Xml(_) => 2,
+            Self::Usage(_) | Self::Io(_) => 3,
+        }
+    }
+}
+
+// The reader stops at the end of a truncated document without an error unless
+// the mode is strict, so the commands check it after the last tag.
+fn check_document_complete(reader: &MyXmlReader) -> Result<(), CliError> {
+    match reader.get_unclosed_nodes().last() {
+        Some(node) => Err(CliError::Xml(format!(
+            "Document ends before element <{}> at pos {} is closed",
+            node.name, node.start_pos
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+enum Command {
+    #[default]
+    Query,
+    Validate,
+    Fmt,
+    Minify,
+    Stats,
+}
+
+impl Command {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "query" => Some(Self::Query),
+            "validate" => Some(Self::Validate),
+            "fmt" => Some(Self::Fmt),
+            "minify" => Some(Self::Minify),
+            "stats" => Some(Self::Stats),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+enum Output {
+    #[default]
+    Xml,
+    Value,
+    Attribute(String),
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    command: Command,
+    path: String,
+    file: Option<String>,
+    all: bool,
+    output: Output,
+    indent: Option<usize>,
+    mode: XmlReaderMode,
+    help: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, CliError> {
+    let mut result = Args::default();
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" | "--all" => result.all = true,
+            "-v" | "--value" => result.output = Output::Value,
+            "-t" | "--attr" => {
+                let Some(name) = args.next() else {
+                    return Err(CliError::Usage(format!("{} requires a name", arg)));
+                };
+                result.output = Output::Attribute(name);
+            }
+            "--indent" => {
+                let indent = args.next().and_then(|indent| indent.parse().ok());
+
+                if indent.is_none() {
+                    return Err(CliError::Usage(format!("{} requires a number", arg)));
+                }
+
+                result.indent = indent;
+            }
+            "--strict" => result.mode = XmlReaderMode::Strict,
+            "--lenient" => result.mode = XmlReaderMode::Lenient,
+            "-h" | "--help" => result.help = true,
+            "-" => positional.push(arg),
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Usage(format!("Unknown option {}", arg)));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    if result.help {
+        return Ok(result);
+    }
+
+    let mut positional = positional.into_iter().peekable();
+
+    if let Some(command) = positional.peek().and_then(|arg| Command::parse(arg)) {
+        result.command = command;
+        positional.next();
+    }
+
+    if result.command == Command::Query {
+        let Some(path) = positional.next() else {
+            return Err(CliError::Usage("PATH is missing".to_string()));
+        };
+
+        result.path = path;
+    }
+
+    result.file = positional.next().filter(|file| file != "-");
+
+    if let Some(arg) = positional.next() {
+        return Err(CliError::Usage(format!("Unexpected argument {}", arg)));
+    }
+
+    Ok(result)
+}
+
+enum Input {
+    Mapped(memmap2::Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Mapped(mmap) => mmap,
+            Self::Read(data) => data,
+        }
+    }
+}
+
+fn read_input(file: Option<&str>) -> Result<Input, CliError> {
+    let Some(file) = file else {
+        let mut result = Vec::new();
+
+        std::io::stdin()
+            .read_to_end(&mut result)
+            .map_err(|err| CliError::Io(format!("Can not read stdin: {}", err)))?;
+
+        return Ok(Input::Read(result));
+    };
+
+    let to_io_error = |err: std::io::Error| CliError::Io(format!("Can not read {}: {}", file, err));
+
+    let f = std::fs::File::open(file).map_err(to_io_error)?;
+
+    // Empty files can not be mapped
+    if f.metadata().map_err(to_io_error)?.len() == 0 {
+        return Ok(Input::Read(Vec::new()));
+    }
+
+    // The file may be changed by another process while it is mapped. The tool
+    // only reads it once, so the worst outcome is a garbled result.
+    let mmap = unsafe { memmap2::Mmap::map(&f) }.map_err(to_io_error)?;
+
+    Ok(Input::Mapped(mmap))
+}
+
+#[cfg(feature = "encoding")]
+fn to_utf8(xml: &[u8]) -> Result<std::borrow::Cow<'_, [u8]>, CliError> {
+    my_xml_reader::my_xml_reader::transcode_to_utf8(xml).map_err(CliError::Xml)
+}
+
+#[cfg(not(feature = "encoding"))]
+fn to_utf8(xml: &[u8]) -> Result<std::borrow::Cow<'_, [u8]>, CliError> {
+    Ok(std::borrow::Cow::Borrowed(xml))
+}
+
+fn run() -> Result<(), CliError> {
+    let args = parse_args(std::env::args().skip(1))?;
+
+    if args.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let input = read_input(args.file.as_deref())?;
+    let xml = to_utf8(&input)?;
+
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+
+    match args.command {
+        Command::Query => query::query(&xml, &args, &mut out)?,
+        Command::Validate => {
+            let file_name = args.file.as_deref().unwrap_or("<stdin>");
+            validate::validate_document(&xml, file_name, &mut out)?
+        }
+        Command::Fmt => {
+            let indent = " ".repeat(args.indent.unwrap_or(DEFAULT_INDENT));
+            reformat::reformat(&xml, args.mode, Some(&indent), &mut out)?
+        }
+        Command::Minify => reformat::reformat(&xml, args.mode, None, &mut out)?,
+        Command::Stats => stats::stats(&xml, args.mode, &mut out)?,
+    }
+
+    out.flush().map_err(|err| CliError::Io(err.to_string()))
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            match &err {
+                CliError::NotFound => {}
+                CliError::Xml(message) | CliError::Io(message) => {
+                    eprintln!("my-xml: {}", message)
+                }
+                CliError::Usage(message) => eprintln!("my-xml: {}\n\n{}", message, USAGE),
+            }
+
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(src: &[&str]) -> Args {
+        parse_args(src.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            Args {
+                command: Command::Query,
+                path: "R/A".to_string(),
+                file: Some("in.xml".to_string()),
+                all: true,
+                output: Output::Attribute("id".to_string()),
+                indent: None,
+                mode: XmlReaderMode::Strict,
+                help: false,
+            },
+            args(&["--all", "R/A", "--attr", "id", "--strict", "in.xml"])
+        );
+
+        assert_eq!(None, args(&["R", "-"]).file);
+        assert!(args(&["-h"]).help);
+
+        let fmt = args(&["fmt", "--indent", "4", "in.xml"]);
+        assert_eq!(Command::Fmt, fmt.command);
+        assert_eq!(Some(4), fmt.indent);
+        assert_eq!(Some("in.xml".to_string()), fmt.file);
+
+        assert_eq!(Command::Stats, args(&["stats"]).command);
+        assert_eq!(Command::Validate, args(&["validate", "-"]).command);
+
+        let query = args(&["query", "stats"]);
+        assert_eq!(Command::Query, query.command);
+        assert_eq!("stats", query.path);
+
+        for src in [
+            &[][..],
+            &["--attr"],
+            &["--bogus", "R"],
+            &["R", "a", "b"],
+            &["query"],
+            &["fmt", "--indent", "x"],
+            &["minify", "a.xml", "b.xml"],
+        ] {
+            let err = parse_args(src.iter().map(|arg| arg.to_string())).unwrap_err();
+            assert_eq!(3, err.exit_code(), "{:?}", src);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::Write;
+
+use my_xml_reader::my_xml_reader::{ReaderOptions, XmlTagType};
+use my_xml_reader::{MyXmlNode, MyXmlReader};
+
+use super::{check_document_complete, Args, CliError, Output};
+
+fn is_path_matched(segments: &[&str], reader: &MyXmlReader, name: &str) -> bool {
+    let Some((last, parents)) = segments.split_last() else {
+        return false;
+    };
+
+    let ancestors = &reader.opened_nodes[..parents.len().min(reader.opened_nodes.len())];
+
+    ancestors.len() == parents.len()
+        && parents
+            .iter()
+            .zip(ancestors)
+            .all(|(segment, node)| *segment == "*" || *segment == node.name)
+        && (*last == "*" || *last == name)
+}
+
+pub fn query(xml: &[u8], args: &Args, out: &mut impl Write) -> Result<(), CliError> {
+    let segments: Vec<&str> = args.path.split('/').filter(|s| !s.is_empty()).collect();
+
+    if segments.is_empty() {
+        return Err(CliError::Usage("PATH is empty".to_string()));
+    }
+
+    let mut reader = MyXmlReader::from_slice_with_options(xml, ReaderOptions::with_mode(args.mode))
+        .map_err(CliError::Xml)?;
+
+    let mut found = false;
+
+    while let Some(tag) = reader.read_next_tag().map_err(CliError::Xml)? {
+        let level = match tag.tag_type {
+            XmlTagType::Open | XmlTagType::OpenClose => tag.level,
+            XmlTagType::Close => continue,
+        };
+
+        if level + 1 != segments.len() || !is_path_matched(&segments, &reader, tag.name) {
+            continue;
+        }
+
+        let printed = match &args.output {
+            Output::Attribute(attr_name) => reader
+                .get_attribute_value(&tag, attr_name)
+                .map_err(CliError::Xml)?,
+            Output::Xml | Output::Value => {
+                let node = match tag.tag_type {
+                    XmlTagType::Open => reader.read_the_whole_node(tag).map_err(CliError::Xml)?,
+                    _ => MyXmlNode {
+                        xml,
+                        open_node: tag,
+                        close_node: None,
+                    },
+                };
+
+                if args.output == Output::Xml {
+                    Some(node.get_xml().map_err(CliError::Xml)?.to_string())
+                } else {
+                    let value = reader.get_node_value(&node).map_err(CliError::Xml)?;
+                    Some(value.unwrap_or_default())
+                }
+            }
+        };
+
+        if let Some(printed) = printed {
+            writeln!(out, "{}", printed).map_err(|err| CliError::Io(err.to_string()))?;
+            found = true;
+
+            if !args.all {
+                return Ok(());
+            }
+        }
+    }
+
+    check_document_complete(&reader)?;
+
+    if found {
+        Ok(())
+    } else {
+        Err(CliError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_args;
+
+    const XML: &str = r#"<?xml version="1.0"?>
+<Feed>
+  <Item id="1"><Name>First</Name></Item>
+  <Other><Item id="x"><Name>Nested</Name></Item></Other>
+  <Item id="2"><Name>Second &amp; last</Name></Item>
+  <Item/>
+</Feed>"#;
+
+    fn run_query(xml: &str, src: &[&str]) -> Result<String, CliError> {
+        let args = parse_args(src.iter().map(|arg| arg.to_string())).unwrap();
+
+        let mut out = Vec::new();
+        query(xml.as_bytes(), &args, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_print_first_and_all_matches() {
+        assert_eq!(
+            "<Name>First</Name>\n",
+            run_query(XML, &["Feed/Item/Name"]).unwrap()
+        );
+
+        assert_eq!(
+            "First\nSecond & last\n",
+            run_query(XML, &["-a", "-v", "Feed/Item/Name"]).unwrap()
+        );
+
+        assert_eq!(
+            "x\n",
+            run_query(XML, &["--all", "--attr", "id", "Feed/*/Item"]).unwrap()
+        );
+
+        assert_eq!(
+            "1\n2\n",
+            run_query(XML, &["query", "--all", "--attr", "id", "Feed/Item"]).unwrap()
+        );
+
+        assert_eq!(
+            "<Item/>\n",
+            run_query("<Feed><Item/></Feed>", &["Feed/Item"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let err = run_query(XML, &["Feed/Missing"]).unwrap_err();
+        assert_eq!(1, err.exit_code());
+
+        let err = run_query(XML, &["--attr", "missing", "Feed/Item"]).unwrap_err();
+        assert_eq!(1, err.exit_code());
+
+        let err = run_query("<Feed><Item></Feed>", &["--strict", "Feed/Other"]).unwrap_err();
+        assert_eq!(2, err.exit_code());
+
+        let err = run_query("<Feed><Item attr=\"1></Feed>", &["Feed/Item"]).unwrap_err();
+        assert_eq!(2, err.exit_code());
+
+        let err = run_query("<R><A>1</A>", &["R/B"]).unwrap_err();
+        assert_eq!(2, err.exit_code());
+
+        let err = run_query("<R><A>1</A>", &["--all", "R/A"]).unwrap_err();
+        assert_eq!(2, err.exit_code());
+    }
+}
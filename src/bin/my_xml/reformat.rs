@@ -0,0 +1,254 @@
+use std::io::Write;
+
+use my_xml_reader::my_xml_reader::{
+    AttributesIterator, MyXmlWriter, ReaderOptions, XmlReaderMode, XmlTagType,
+};
+use my_xml_reader::MyXmlReader;
+
+use super::content::{is_whitespace_only, split_content, ContentPart};
+use super::{check_document_complete, CliError};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+fn to_str(data: &[u8]) -> Result<&str, CliError> {
+    std::str::from_utf8(data)
+        .map_err(|err| CliError::Xml(format!("Content is not valid UTF-8: {}", err)))
+}
+
+// Comments, processing instructions and CDATA sections are copied as is, and so
+// is text. Whitespace-only text is dropped unless it is inside
+// xml:space="preserve".
+fn write_content<W: Write>(
+    writer: &mut MyXmlWriter<W>,
+    content: &[u8],
+    preserve_space: bool,
+) -> Result<(), CliError> {
+    for part in split_content(content) {
+        match part {
+            ContentPart::Text(text) => {
+                if is_whitespace_only(text) && (!preserve_space || writer.get_level() == 0) {
+                    continue;
+                }
+
+                let text = to_str(text)?;
+
+                if writer.get_level() == 0 {
+                    writer.write_raw_markup(text.trim()).map_err(CliError::Io)?;
+                } else {
+                    writer.write_raw_text(text).map_err(CliError::Io)?;
+                }
+            }
+            ContentPart::CData(cdata) if writer.get_level() > 0 => {
+                writer
+                    .write_raw_text(to_str(cdata)?)
+                    .map_err(CliError::Io)?;
+            }
+            ContentPart::CData(markup) | ContentPart::Markup(markup) => {
+                writer
+                    .write_raw_markup(to_str(markup)?)
+                    .map_err(CliError::Io)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// In lenient mode one close tag or the end of the document can close several
+// elements, so the writer ends elements until it is at the level of the reader.
+fn end_elements<W: Write>(
+    writer: &mut MyXmlWriter<W>,
+    preserve_space: &mut Vec<bool>,
+    level: usize,
+) -> Result<(), CliError> {
+    while writer.get_level() > level {
+        preserve_space.pop();
+        writer.end_element().map_err(CliError::Io)?;
+    }
+
+    Ok(())
+}
+
+// Rewrites the document tag by tag: with indent it is pretty-printed,
+// without it is minified.
+pub fn reformat(
+    xml: &[u8],
+    mode: XmlReaderMode,
+    indent: Option<&str>,
+    out: impl Write,
+) -> Result<(), CliError> {
+    let mut reader = MyXmlReader::from_slice_with_options(xml, ReaderOptions::with_mode(mode))
+        .map_err(CliError::Xml)?;
+
+    let mut writer = match indent {
+        Some(indent) => MyXmlWriter::with_indent(out, indent),
+        None => MyXmlWriter::new(out),
+    };
+
+    let mut content_start = match reader.get_declaration() {
+        Some(declaration) => {
+            writer
+                .write_declaration(declaration)
+                .map_err(CliError::Io)?;
+            declaration.end_pos + 1
+        }
+        None if xml.starts_with(UTF8_BOM) => UTF8_BOM.len(),
+        None => 0,
+    };
+
+    let mut preserve_space = Vec::new();
+
+    while let Some(tag) = reader.read_next_tag().map_err(CliError::Xml)? {
+        write_content(
+            &mut writer,
+            &xml[content_start..tag.start_pos],
+            preserve_space.last() == Some(&true),
+        )?;
+
+        content_start = tag.end_pos + 1;
+
+        if let XmlTagType::Close = tag.tag_type {
+            end_elements(&mut writer, &mut preserve_space, reader.get_level())?;
+            continue;
+        }
+
+        writer.start_element(tag.name).map_err(CliError::Io)?;
+
+        let mut attributes = AttributesIterator::new(tag.raw);
+
+        while let Some((name, value)) = attributes.get_next().map_err(CliError::Xml)? {
+            writer
+                .write_raw_attribute(name, value)
+                .map_err(CliError::Io)?;
+        }
+
+        if let XmlTagType::Open = tag.tag_type {
+            preserve_space.push(reader.is_space_preserved(&tag).map_err(CliError::Xml)?);
+        } else {
+            writer.end_element().map_err(CliError::Io)?;
+        }
+    }
+
+    check_document_complete(&reader)?;
+    end_elements(&mut writer, &mut preserve_space, 0)?;
+
+    write_content(&mut writer, &xml[content_start..], false)?;
+
+    writer.finish().map_err(CliError::Io)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE Feed>
+<Feed   title='Say "hi"'
+        id="1">
+
+  <!-- items -->
+  <Item><Name>A &amp; B</Name><Empty></Empty></Item>
+  <Item   />
+  <Code xml:space="preserve">  keep
+    this  </Code>
+  <P>Hello <b>world</b>!</P>
+</Feed>
+<!-- end -->
+"#;
+
+    fn run(indent: Option<&str>) -> String {
+        let mut out = Vec::new();
+        reformat(XML.as_bytes(), XmlReaderMode::Default, indent, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE Feed>
+<Feed title='Say "hi"' id="1">
+  <!-- items -->
+  <Item>
+    <Name>A &amp; B</Name>
+    <Empty/>
+  </Item>
+  <Item/>
+  <Code xml:space="preserve">  keep
+    this  </Code>
+  <P>Hello <b>world</b>!</P>
+</Feed>
+<!-- end -->
+"#,
+            run(Some("  "))
+        );
+    }
+
+    #[test]
+    fn test_minify() {
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><!DOCTYPE Feed><Feed title='Say "hi"' id="1"><!-- items --><Item><Name>A &amp; B</Name><Empty/></Item><Item/><Code xml:space="preserve">  keep
+    this  </Code><P>Hello <b>world</b>!</P></Feed><!-- end -->"#,
+            run(None)
+        );
+    }
+
+    #[test]
+    fn test_reformat_round_trip() {
+        let pretty = run(Some("\t"));
+
+        let mut minified = Vec::new();
+        reformat(
+            pretty.as_bytes(),
+            XmlReaderMode::Strict,
+            None,
+            &mut minified,
+        )
+        .unwrap();
+
+        let mut minified_again = Vec::new();
+        reformat(&minified, XmlReaderMode::Strict, None, &mut minified_again).unwrap();
+
+        assert_eq!(minified, minified_again);
+    }
+
+    #[test]
+    fn test_lenient_mode_ends_implicitly_closed_elements() {
+        let reformat_lenient = |xml: &str, indent: Option<&str>| {
+            let mut out = Vec::new();
+            reformat(xml.as_bytes(), XmlReaderMode::Lenient, indent, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            "<R><br><p>x</p></br></R>",
+            reformat_lenient("<R><br><p>x</R>", None)
+        );
+
+        assert_eq!(
+            "<R>\n  <br>\n    <p>x</p>\n  </br>\n</R>\n",
+            reformat_lenient("<R><br><p>x</R>", Some("  "))
+        );
+
+        assert_eq!("<R><A><B/></A></R>", reformat_lenient("<R><A><B/>", None));
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn test_transcoded_document_declares_utf8() {
+        let mut xml = br#"<?xml version="1.0" encoding="windows-1251"?><R>"#.to_vec();
+        xml.extend_from_slice(&[0xC8, 0xE2, 0xE0, 0xED]);
+        xml.extend_from_slice(b"</R>");
+
+        let xml = crate::to_utf8(&xml).unwrap();
+
+        let mut out = Vec::new();
+        reformat(&xml, XmlReaderMode::Default, None, &mut out).unwrap();
+
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><R>Иван</R>"#,
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use my_xml_reader::my_xml_reader::{AttributesIterator, ReaderOptions, XmlReaderMode, XmlTagType};
+use my_xml_reader::MyXmlReader;
+
+use super::content::{is_whitespace_only, split_content, ContentPart};
+use super::{check_document_complete, CliError};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SizeStats {
+    pub total: usize,
+    pub tags: usize,
+    pub text: usize,
+    pub cdata: usize,
+    pub whitespace: usize,
+    // The BOM, XML declaration, DOCTYPE, comments and processing instructions
+    pub markup: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct XmlStats<'t> {
+    pub size: SizeStats,
+    pub elements: usize,
+    pub attributes: usize,
+    pub max_depth: usize,
+    pub elements_by_name: HashMap<&'t str, usize>,
+    pub attributes_by_name: HashMap<&'t str, usize>,
+}
+
+impl<'t> XmlStats<'t> {
+    fn add_content(&mut self, content: &[u8]) {
+        for part in split_content(content) {
+            match part {
+                ContentPart::Text(text) if is_whitespace_only(text) => {
+                    self.size.whitespace += text.len()
+                }
+                ContentPart::Text(text) => self.size.text += text.len(),
+                ContentPart::CData(cdata) => self.size.cdata += cdata.len(),
+                ContentPart::Markup(markup) => self.size.markup += markup.len(),
+            }
+        }
+    }
+}
+
+pub fn collect_stats(xml: &[u8], mode: XmlReaderMode) -> Result<XmlStats<'_>, CliError> {
+    let mut reader = MyXmlReader::from_slice_with_options(xml, ReaderOptions::with_mode(mode))
+        .map_err(CliError::Xml)?;
+
+    let mut result = XmlStats::default();
+    result.size.total = xml.len();
+
+    let mut content_start = 0;
+
+    if xml.starts_with(UTF8_BOM) {
+        result.size.markup += UTF8_BOM.len();
+        content_start = UTF8_BOM.len();
+    }
+
+    while let Some(tag) = reader.read_next_tag().map_err(CliError::Xml)? {
+        result.add_content(&xml[content_start..tag.start_pos]);
+        result.size.tags += tag.end_pos - tag.start_pos + 1;
+        content_start = tag.end_pos + 1;
+
+        if let XmlTagType::Close = tag.tag_type {
+            continue;
+        }
+
+        result.elements += 1;
+        result.max_depth = result.max_depth.max(tag.level + 1);
+        *result.elements_by_name.entry(tag.name).or_default() += 1;
+
+        let mut attributes = AttributesIterator::new(tag.raw);
+
+        while let Some((name, _)) = attributes.get_next().map_err(CliError::Xml)? {
+            result.attributes += 1;
+            *result.attributes_by_name.entry(name).or_default() += 1;
+        }
+    }
+
+    check_document_complete(&reader)?;
+
+    result.add_content(&xml[content_start..]);
+
+    Ok(result)
+}
+
+fn sort_by_count<'a>(counts: &HashMap<&'a str, usize>) -> Vec<(&'a str, usize)> {
+    let mut result: Vec<(&str, usize)> = counts.iter().map(|(k, v)| (*k, *v)).collect();
+    result.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    result
+}
+
+pub fn print_stats(stats: &XmlStats, out: &mut impl Write) -> std::io::Result<()> {
+    let size = &stats.size;
+
+    writeln!(out, "Size:         {} bytes", size.total)?;
+    writeln!(out, "  tags:       {}", size.tags)?;
+    writeln!(out, "  text:       {}", size.text)?;
+    writeln!(out, "  cdata:      {}", size.cdata)?;
+    writeln!(out, "  whitespace: {}", size.whitespace)?;
+    writeln!(out, "  markup:     {}", size.markup)?;
+    writeln!(out, "Elements:     {}", stats.elements)?;
+    writeln!(out, "Attributes:   {}", stats.attributes)?;
+    writeln!(out, "Max depth:    {}", stats.max_depth)?;
+
+    writeln!(out, "\nElements by name:")?;
+
+    for (name, count) in sort_by_count(&stats.elements_by_name) {
+        writeln!(out, "{:>12}  {}", count, name)?;
+    }
+
+    if !stats.attributes_by_name.is_empty() {
+        writeln!(out, "\nAttributes by name:")?;
+
+        for (name, count) in sort_by_count(&stats.attributes_by_name) {
+            writeln!(out, "{:>12}  {}", count, name)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn stats(xml: &[u8], mode: XmlReaderMode, out: &mut impl Write) -> Result<(), CliError> {
+    let stats = collect_stats(xml, mode)?;
+    print_stats(&stats, out).map_err(|err| CliError::Io(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0"?>
+<!-- feed -->
+<Feed id="f">
+  <Item id="1" lang="en"><Name>A</Name></Item>
+  <Item id="2"><Name><![CDATA[B]]></Name><Tags/></Item>
+</Feed>
+"#;
+
+    #[test]
+    fn test_collect_stats() {
+        let stats = collect_stats(XML.as_bytes(), XmlReaderMode::Default).unwrap();
+
+        assert_eq!(
+            SizeStats {
+                total: XML.len(),
+                tags: 13 + 23 + 6 + 7 + 7 + 13 + 6 + 7 + 7 + 7 + 7,
+                text: 1,
+                cdata: 13,
+                whitespace: 1 + 1 + 3 + 3 + 1 + 1,
+                markup: 21 + 13,
+            },
+            stats.size
+        );
+
+        assert_eq!(
+            stats.size.total,
+            stats.size.tags
+                + stats.size.text
+                + stats.size.cdata
+                + stats.size.whitespace
+                + stats.size.markup
+        );
+
+        assert_eq!(6, stats.elements);
+        assert_eq!(4, stats.attributes);
+        assert_eq!(3, stats.max_depth);
+        assert_eq!(Some(&2), stats.elements_by_name.get("Item"));
+        assert_eq!(Some(&3), stats.attributes_by_name.get("id"));
+    }
+
+    #[test]
+    fn test_truncated_document_is_xml_error() {
+        for mode in [XmlReaderMode::Default, XmlReaderMode::Strict] {
+            let err = collect_stats(b"<R><A>1</A>", mode).unwrap_err();
+            assert_eq!(2, err.exit_code(), "{:?}", mode);
+        }
+
+        // The lenient mode closes the root implicitly
+        assert!(collect_stats(b"<R><A>1</A>", XmlReaderMode::Lenient).is_ok());
+    }
+
+    #[test]
+    fn test_print_stats() {
+        let mut out = Vec::new();
+        stats(
+            b"<R a=\"1\"><B/><A/><B/></R>",
+            XmlReaderMode::Strict,
+            &mut out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            "Size:         25 bytes
+  tags:       25
+  text:       0
+  cdata:      0
+  whitespace: 0
+  markup:     0
+Elements:     4
+Attributes:   1
+Max depth:    2
+
+Elements by name:
+           2  B
+           1  A
+           1  R
+
+Attributes by name:
+           1  a
+",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use super::CliError;
+
+// Converts byte positions into 1-based lines and columns counted in chars.
+// Positions are expected to grow, so the document is scanned only once.
+struct LineCounter<'t> {
+    xml: &'t [u8],
+    pos: usize,
+    line: usize,
+    line_start: usize,
+}
+
+impl<'t> LineCounter<'t> {
+    fn new(xml: &'t [u8]) -> Self {
+        Self {
+            xml,
+            pos: 0,
+            line: 1,
+            line_start: 0,
+        }
+    }
+
+    fn get_line_and_column(&mut self, pos: usize) -> (usize, usize) {
+        let pos = pos.min(self.xml.len());
+
+        if pos < self.pos {
+            *self = Self::new(self.xml);
+        }
+
+        for (i, b) in self.xml[self.pos..pos].iter().enumerate() {
+            if *b == b'\n' {
+                self.line += 1;
+                self.line_start = self.pos + i + 1;
+            }
+        }
+
+        self.pos = pos;
+
+        let column = self.xml[self.line_start..pos]
+            .iter()
+            .filter(|b| (**b & 0xC0) != 0x80)
+            .count();
+
+        (self.line, column + 1)
+    }
+}
+
+pub fn validate_document(
+    xml: &[u8],
+    file_name: &str,
+    out: &mut impl Write,
+) -> Result<(), CliError> {
+    let Err(errors) = my_xml_reader::my_xml_reader::validate(xml) else {
+        return Ok(());
+    };
+
+    let mut line_counter = LineCounter::new(xml);
+
+    for err in &errors {
+        let (line, column) = line_counter.get_line_and_column(err.pos);
+
+        writeln!(out, "{}:{}:{}: {}", file_name, line, column, err.kind)
+            .map_err(|err| CliError::Io(err.to_string()))?;
+    }
+
+    Err(CliError::Xml(format!(
+        "{} is not well-formed: {} error(s)",
+        file_name,
+        errors.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_and_column() {
+        let xml = "<R>\n  <Имя>\n\t<A/>".as_bytes();
+
+        let mut line_counter = LineCounter::new(xml);
+
+        assert_eq!((1, 1), line_counter.get_line_and_column(0));
+        assert_eq!((2, 3), line_counter.get_line_and_column(6));
+        assert_eq!((2, 8), line_counter.get_line_and_column(14));
+        assert_eq!((3, 2), line_counter.get_line_and_column(16));
+        assert_eq!((1, 4), line_counter.get_line_and_column(3));
+    }
+
+    #[test]
+    fn test_validate_document() {
+        let mut out = Vec::new();
+        validate_document("<R><A/></R>".as_bytes(), "ok.xml", &mut out).unwrap();
+        assert!(out.is_empty());
+
+        let xml = "<R>\n  <A>\n  </B>\n</R>\n<Second/>";
+
+        let err = validate_document(xml.as_bytes(), "bad.xml", &mut out).unwrap_err();
+
+        assert_eq!(2, err.exit_code());
+        assert_eq!(
+            "bad.xml:3:3: Attempt to close tag </B>. But last opened tag has the name <A>\n\
+             bad.xml:4:1: Attempt to close tag </R>. But last opened tag has the name <A>\n\
+             bad.xml:5:1: Document has more than one root element\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -11,137 +11,109 @@ impl<'s> AttributesIterator<'s> {
         }
     }
 
-    pub fn get_next(&mut self) -> Option<(&'s str, &'s str)> {
+    pub fn get_next(&mut self) -> Result<Option<(&'s str, &'s str)>, String> {
         let pos = self.pos;
 
         if pos >= self.data.len() {
-            return None;
+            return Ok(None);
         }
 
         if self.data[pos] == b'/' {
-            return None;
+            return Ok(None);
         }
 
         if self.data[pos] == b'>' {
-            return None;
+            return Ok(None);
         }
 
-        let result = extract_attr_and_value(&self.data[pos..]).unwrap();
+        let result = extract_attr_and_value(&self.data[pos..])?;
 
         self.pos = pos + result.len;
 
-        result.key_value
+        Ok(result.key_value)
     }
 }
 
+fn is_xml_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+fn skip_whitespace(data: &[u8], mut pos: usize) -> usize {
+    while pos < data.len() && is_xml_whitespace(data[pos]) {
+        pos += 1;
+    }
+
+    pos
+}
+
 fn find_start_of_attribute(data: &[u8]) -> usize {
-    let mut found_space = false;
     for (i, b) in data.iter().enumerate() {
-        let b = *b;
-
-        if found_space {
-            match b {
-                b'/' => {
-                    return data.len();
-                }
-                b'>' => {
-                    return data.len();
-                }
-                b' ' => {}
-                _ => return i,
-            }
-        } else {
-            match b {
-                b' ' => {
-                    found_space = true;
-                }
-                b'/' => {
-                    return data.len();
-                }
-                b'>' => {
-                    return data.len();
-                }
-                _ => {}
-            }
+        match *b {
+            b'/' | b'>' if i > 1 => return data.len(),
+            b if is_xml_whitespace(b) => return i,
+            _ => {}
         }
     }
 
     data.len()
 }
 
+// Attributes are separated by whitespace which is also allowed around '='.
+// Values are quoted with either '"' or '\''.
 fn extract_attr_and_value<'s>(src: &'s [u8]) -> Result<FoundTagData<'s>, String> {
-    let mut attr_start = None;
-    let mut eq_pos = None;
-    let mut end_pos = src.len();
-
-    let mut quote_amount = 0;
-
-    let mut esc_charged = false;
-
-    for (i, b) in src.iter().enumerate() {
-        let b = *b;
-        if eq_pos.is_none() {
-            if attr_start.is_none() {
-                if b == b' ' {
-                    continue;
-                }
-
-                attr_start = Some(i);
-            }
-            match b {
-                b'=' => eq_pos = Some(i),
-                b'>' => {
-                    return Ok(FoundTagData {
-                        key_value: None,
-                        len: i,
-                    });
-                }
-                b'/' => {
-                    return Ok(FoundTagData {
-                        key_value: None,
-                        len: i,
-                    });
-                }
-                _ => {}
-            }
-        } else {
-            if esc_charged {
-                esc_charged = false;
-                continue;
-            }
-
-            match b {
-                b'"' => {
-                    quote_amount += 1;
-                }
-                b'\\' => {
-                    esc_charged = true;
-                }
-                _ => {}
-            }
-
-            if quote_amount == 2 {
-                end_pos = i;
-                break;
-            }
+    let attr_start = skip_whitespace(src, 0);
+
+    match src.get(attr_start) {
+        None | Some(b'/') | Some(b'>') => {
+            return Ok(FoundTagData {
+                key_value: None,
+                len: attr_start,
+            })
         }
+        _ => {}
     }
 
-    let Some(eq_pos) = eq_pos else {
+    let attr_end = src[attr_start..]
+        .iter()
+        .position(|b| is_xml_whitespace(*b) || matches!(b, b'=' | b'/' | b'>'))
+        .map_or(src.len(), |pos| attr_start + pos);
+
+    let eq_pos = skip_whitespace(src, attr_end);
+
+    if src.get(eq_pos) != Some(&b'=') {
         return Err("Can not read attribute. Eq position is not found".to_string());
+    }
+
+    let value_start = skip_whitespace(src, eq_pos + 1);
+
+    let quote = match src.get(value_start) {
+        Some(quote @ (b'"' | b'\'')) => *quote,
+        _ => {
+            return Err(format!(
+                "Value of attribute '{}' must be quoted",
+                String::from_utf8_lossy(&src[attr_start..attr_end])
+            ))
+        }
     };
 
-    let attr_start = attr_start.unwrap();
+    let Some(end_pos) = super::xml_utils::find_value_end(src, quote, value_start + 1) else {
+        return Err(format!(
+            "Value of attribute '{}' is not closed",
+            String::from_utf8_lossy(&src[attr_start..attr_end])
+        ));
+    };
 
-    let result = FoundTagData {
+    Ok(FoundTagData {
         key_value: Some((
-            std::str::from_utf8(&src[attr_start..eq_pos]).unwrap(),
-            std::str::from_utf8(&src[eq_pos + 2..end_pos]).unwrap(),
+            to_str(&src[attr_start..attr_end])?,
+            to_str(&src[value_start + 1..end_pos])?,
         )),
         len: end_pos + 1,
-    };
+    })
+}
 
-    Ok(result)
+fn to_str(src: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(src).map_err(|err| format!("Attribute is not valid UTF-8: {}", err))
 }
 
 pub struct FoundTagData<'s> {
@@ -157,7 +129,7 @@ mod tests {
         let xml = "<test></test>";
 
         let mut attrs_iterator = AttributesIterator::new(xml.as_bytes());
-        let next = attrs_iterator.get_next();
+        let next = attrs_iterator.get_next().unwrap();
         assert!(next.is_none());
     }
 
@@ -166,7 +138,7 @@ mod tests {
         let xml = "<test/>";
 
         let mut attrs_iterator = AttributesIterator::new(xml.as_bytes());
-        let next = attrs_iterator.get_next();
+        let next = attrs_iterator.get_next().unwrap();
         assert!(next.is_none());
     }
 
@@ -175,7 +147,7 @@ mod tests {
         let xml = "<test />";
 
         let mut attrs_iterator = AttributesIterator::new(xml.as_bytes());
-        let next = attrs_iterator.get_next();
+        let next = attrs_iterator.get_next().unwrap();
         assert!(next.is_none());
     }
 
@@ -184,14 +156,56 @@ mod tests {
         let xml = "<test attr1=\"value1\" attr2=\"value2\"/>";
 
         let mut attrs_iterator = AttributesIterator::new(xml.as_bytes());
-        let next = attrs_iterator.get_next().unwrap();
+        let next = attrs_iterator.get_next().unwrap().unwrap();
 
         assert_eq!(next.0, "attr1");
         assert_eq!(next.1, "value1");
 
-        let next = attrs_iterator.get_next().unwrap();
+        let next = attrs_iterator.get_next().unwrap().unwrap();
 
         assert_eq!(next.0, "attr2");
         assert_eq!(next.1, "value2");
     }
+
+    #[test]
+    fn test_invalid_utf8_attribute_is_an_error() {
+        let xml = b"<test attr1=\"\xFF\"/>";
+
+        let mut attrs_iterator = AttributesIterator::new(xml);
+
+        assert!(attrs_iterator.get_next().is_err());
+    }
+
+    #[test]
+    fn test_attributes_with_any_quotes_and_whitespace() {
+        let xml = "<test\n\ta='1'  b = \"x 'y' z\"\r\nc=\"say \\\"hi\\\"\"\td=''/>";
+
+        let mut attrs_iterator = AttributesIterator::new(xml.as_bytes());
+
+        let mut result = Vec::new();
+
+        while let Some(attr) = attrs_iterator.get_next().unwrap() {
+            result.push(attr);
+        }
+
+        assert_eq!(
+            vec![
+                ("a", "1"),
+                ("b", "x 'y' z"),
+                ("c", "say \\\"hi\\\""),
+                ("d", "")
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn test_unquoted_attribute_is_an_error() {
+        let mut attrs_iterator = AttributesIterator::new("<test a=1>".as_bytes());
+
+        assert_eq!(
+            "Value of attribute 'a' must be quoted",
+            attrs_iterator.get_next().unwrap_err()
+        );
+    }
 }
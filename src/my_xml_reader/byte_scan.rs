@@ -0,0 +1,144 @@
+#[cfg(feature = "memchr")]
+pub fn find_byte(haystack: &[u8], needle: u8) -> Option<usize> {
+    memchr::memchr(needle, haystack)
+}
+
+#[cfg(not(feature = "memchr"))]
+pub fn find_byte(haystack: &[u8], needle: u8) -> Option<usize> {
+    swar::find_any(haystack, [needle])
+}
+
+#[cfg(feature = "memchr")]
+pub fn find_any_of_three_bytes(haystack: &[u8], b1: u8, b2: u8, b3: u8) -> Option<usize> {
+    memchr::memchr3(b1, b2, b3, haystack)
+}
+
+#[cfg(not(feature = "memchr"))]
+pub fn find_any_of_three_bytes(haystack: &[u8], b1: u8, b2: u8, b3: u8) -> Option<usize> {
+    swar::find_any(haystack, [b1, b2, b3])
+}
+
+#[cfg(feature = "memchr")]
+pub fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    memchr::memmem::find(haystack, needle)
+}
+
+#[cfg(not(feature = "memchr"))]
+pub fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let (first, rest) = needle.split_first()?;
+
+    let mut pos = 0;
+
+    while let Some(found) = find_byte(&haystack[pos..], *first) {
+        let candidate = pos + found;
+
+        if haystack[candidate + 1..].starts_with(rest) {
+            return Some(candidate);
+        }
+
+        pos = candidate + 1;
+    }
+
+    None
+}
+
+#[cfg(not(feature = "memchr"))]
+mod swar {
+    // Portable word-at-a-time scanning: eight bytes are checked with a couple of
+    // integer operations and only the word which contains a match is scanned
+    // byte by byte.
+    const WORD_SIZE: usize = std::mem::size_of::<u64>();
+    const LO: u64 = u64::from_ne_bytes([0x01; WORD_SIZE]);
+    const HI: u64 = u64::from_ne_bytes([0x80; WORD_SIZE]);
+
+    #[inline]
+    fn has_zero_byte(word: u64) -> bool {
+        word.wrapping_sub(LO) & !word & HI != 0
+    }
+
+    #[inline]
+    fn has_any_byte<const N: usize>(word: u64, needles: [u8; N]) -> bool {
+        needles
+            .iter()
+            .any(|needle| has_zero_byte(word ^ (LO * *needle as u64)))
+    }
+
+    // Tokens are often only a few bytes away (short tags and values), so the
+    // first bytes are checked directly before switching to whole words.
+    const SHORT_DISTANCE: usize = 2 * WORD_SIZE;
+
+    #[inline]
+    pub fn find_any<const N: usize>(haystack: &[u8], needles: [u8; N]) -> Option<usize> {
+        let short_len = haystack.len().min(SHORT_DISTANCE);
+
+        if let Some(pos) = haystack[..short_len]
+            .iter()
+            .position(|b| needles.contains(b))
+        {
+            return Some(pos);
+        }
+
+        let mut chunks = haystack[short_len..].chunks_exact(WORD_SIZE);
+        let mut offset = short_len;
+
+        for chunk in &mut chunks {
+            let word = u64::from_ne_bytes(chunk.try_into().unwrap());
+
+            if has_any_byte(word, needles) {
+                let pos = chunk.iter().position(|b| needles.contains(b)).unwrap();
+                return Some(offset + pos);
+            }
+
+            offset += WORD_SIZE;
+        }
+
+        let pos = chunks
+            .remainder()
+            .iter()
+            .position(|b| needles.contains(b))?;
+
+        Some(offset + pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_byte_in_every_position() {
+        let src = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+        for (i, b) in src.iter().enumerate() {
+            assert_eq!(Some(i), find_byte(src, *b));
+        }
+
+        assert_eq!(None, find_byte(src, b'<'));
+        assert_eq!(None, find_byte(b"", b'<'));
+    }
+
+    #[test]
+    fn test_find_any_of_three_bytes() {
+        let src = b"<Tag attr=\"a>b\" other='c'>";
+
+        assert_eq!(Some(0), find_any_of_three_bytes(src, b'<', b'"', b'\''));
+        assert_eq!(
+            Some(10),
+            find_any_of_three_bytes(&src[1..], b'>', b'"', b'\'').map(|p| p + 1)
+        );
+        assert_eq!(
+            None,
+            find_any_of_three_bytes(b"abcdefghijk", b'>', b'"', b'\'')
+        );
+    }
+
+    #[test]
+    fn test_find_bytes() {
+        let src = b"<?xml version=\"1.0\" ?? ?><Root/>";
+
+        assert_eq!(Some(23), find_bytes(src, b"?>"));
+        assert_eq!(Some(0), find_bytes(src, b"<?xml"));
+        assert_eq!(None, find_bytes(src, b"<!--"));
+        assert_eq!(None, find_bytes(b"?", b"?>"));
+    }
+}
@@ -0,0 +1,349 @@
+use std::io::Write;
+
+use serde_json::{Map, Value};
+
+use super::{is_valid_name, JsonAttributes, MyXmlWriter, XmlToJsonOptions};
+
+// Writes {"Name": value} as the <Name> element following the conventions
+// of xml_to_json, so converted documents can be converted back.
+pub fn json_to_xml<W: Write>(
+    json: &Value,
+    options: &XmlToJsonOptions,
+    out: W,
+) -> Result<W, String> {
+    let Value::Object(root) = json else {
+        return Err("JSON to convert must be an object with a single root key".to_string());
+    };
+
+    let mut keys = root.iter();
+
+    let (Some((name, value)), None) = (keys.next(), keys.next()) else {
+        return Err(format!(
+            "JSON to convert must have a single root key. It has {} keys",
+            root.len()
+        ));
+    };
+
+    if value.is_array() {
+        return Err(format!("Root element <{}> can not be an array", name));
+    }
+
+    let mut writer = match options.indent.as_deref() {
+        Some(indent) => MyXmlWriter::with_indent(out, indent),
+        None => MyXmlWriter::new(out),
+    };
+
+    write_element(&mut writer, options, &get_key_path("$", name), name, value)?;
+
+    writer.finish()
+}
+
+// Path is the JSON path of the value, such as $["Feed"]["Item"][0]
+fn write_element<W: Write>(
+    writer: &mut MyXmlWriter<W>,
+    options: &XmlToJsonOptions,
+    path: &str,
+    name: &str,
+    value: &Value,
+) -> Result<(), String> {
+    if !is_valid_name(name) {
+        return Err(format!("Element name '{}' at {} is invalid", name, path));
+    }
+
+    match value {
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                if item.is_array() {
+                    return Err(format!("Array of <{}> can not contain arrays", name));
+                }
+
+                write_element(writer, options, &format!("{}[{}]", path, i), name, item)?;
+            }
+
+            Ok(())
+        }
+        Value::Object(fields) => {
+            writer.start_element(name)?;
+            write_attributes(writer, options, path, name, fields)?;
+
+            for (key, value) in fields {
+                if is_attribute_key(options, key) {
+                    continue;
+                }
+
+                if *key == options.text_key {
+                    writer.write_text(&scalar_to_string(name, key, value)?)?;
+                } else {
+                    write_element(writer, options, &get_key_path(path, key), key, value)?;
+                }
+            }
+
+            writer.end_element()
+        }
+        _ => {
+            writer.start_element(name)?;
+            writer.write_text(&scalar_to_string(name, name, value)?)?;
+            writer.end_element()
+        }
+    }
+}
+
+fn write_attributes<W: Write>(
+    writer: &mut MyXmlWriter<W>,
+    options: &XmlToJsonOptions,
+    path: &str,
+    name: &str,
+    fields: &Map<String, Value>,
+) -> Result<(), String> {
+    match options.attributes {
+        JsonAttributes::Prefixed => {
+            for (key, value) in fields {
+                if is_attribute_key(options, key) {
+                    let attr_name = &key[options.attribute_prefix.len()..];
+                    check_attribute_name(attr_name, &get_key_path(path, key))?;
+                    writer.write_attribute(attr_name, &scalar_to_string(name, key, value)?)?;
+                }
+            }
+        }
+        JsonAttributes::Nested => {
+            let Some(attributes) = fields.get(&options.attributes_key) else {
+                return Ok(());
+            };
+
+            let Value::Object(attributes) = attributes else {
+                return Err(format!(
+                    "'{}' of <{}> must be an object",
+                    options.attributes_key, name
+                ));
+            };
+
+            let attributes_path = get_key_path(path, &options.attributes_key);
+
+            for (attr_name, value) in attributes {
+                check_attribute_name(attr_name, &get_key_path(&attributes_path, attr_name))?;
+                writer.write_attribute(attr_name, &scalar_to_string(name, attr_name, value)?)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_attribute_name(name: &str, path: &str) -> Result<(), String> {
+    if is_valid_name(name) {
+        Ok(())
+    } else {
+        Err(format!("Attribute name '{}' at {} is invalid", name, path))
+    }
+}
+
+// Keys are quoted as JSON strings, so any key can be told apart
+fn get_key_path(path: &str, key: &str) -> String {
+    format!("{}[{}]", path, Value::String(key.to_string()))
+}
+
+fn is_attribute_key(options: &XmlToJsonOptions, key: &str) -> bool {
+    match options.attributes {
+        JsonAttributes::Prefixed => {
+            !options.attribute_prefix.is_empty()
+                && key.starts_with(&options.attribute_prefix)
+                && *key != options.text_key
+        }
+        JsonAttributes::Nested => *key == options.attributes_key,
+    }
+}
+
+// Null is written as an empty value
+fn scalar_to_string(element: &str, key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::Bool(value) => Ok(value.to_string()),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::String(value) => Ok(value.clone()),
+        Value::Array(_) | Value::Object(_) => Err(format!(
+            "Value of '{}' in <{}> must be a string, number, boolean or null",
+            key, element
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::my_xml_reader::xml_to_json;
+    use crate::MyXmlReader;
+
+    fn to_xml(json: &str, options: &XmlToJsonOptions) -> Result<String, String> {
+        let json: Value = serde_json::from_str(json).unwrap();
+        let out = json_to_xml(&json, options, Vec::new())?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn to_json(xml: &str, options: &XmlToJsonOptions) -> String {
+        let mut reader = MyXmlReader::from_slice(xml.as_bytes()).unwrap();
+
+        let mut out = Vec::new();
+        xml_to_json(&mut reader, options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_json_to_xml() {
+        let json = r##"{"Feed": {
+            "@version": 2,
+            "Title": "A & B <news>",
+            "Item": [
+                {"@id": "1", "@featured": true, "Name": "First", "Tag": ["a", "b"]},
+                {"@id": "2", "Price": {"@currency": "EUR", "#text": 10.5}, "Empty": null}
+            ],
+            "Note": "Line \"one\"\n"
+        }}"##;
+
+        assert_eq!(
+            r#"<Feed version="2">
+  <Title>A &amp; B &lt;news&gt;</Title>
+  <Item id="1" featured="true">
+    <Name>First</Name>
+    <Tag>a</Tag>
+    <Tag>b</Tag>
+  </Item>
+  <Item id="2">
+    <Price currency="EUR">10.5</Price>
+    <Empty/>
+  </Item>
+  <Note>Line "one"
+</Note>
+</Feed>
+"#,
+            to_xml(
+                json,
+                &XmlToJsonOptions {
+                    indent: Some("  ".to_string()),
+                    ..Default::default()
+                }
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_nested_attributes() {
+        let options = XmlToJsonOptions {
+            attributes: JsonAttributes::Nested,
+            text_key: "$".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            r#"<R a="1" b="x&#xA;y"><C>text<D/></C></R>"#,
+            to_xml(
+                r#"{"R": {"@attributes": {"a": 1, "b": "x\ny"}, "C": {"$": "text", "D": ""}}}"#,
+                &options
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let xml = r#"<?xml version="1.0"?>
+<Feed version="2">
+  <Title>A &amp; B</Title>
+  <Item id="1"><Name>First</Name><Tag>a</Tag><Tag>b</Tag></Item>
+  <Item id="2"><Name><![CDATA[<Second>]]></Name><Price currency="EUR">10.50</Price></Item>
+  <Single><Tag>c</Tag></Single>
+  <Empty/>
+</Feed>"#;
+
+        for options in [
+            XmlToJsonOptions {
+                force_array_paths: vec!["Feed/Single/Tag".to_string()],
+                ..Default::default()
+            },
+            XmlToJsonOptions {
+                attributes: JsonAttributes::Nested,
+                coerce_booleans: true,
+                indent: Some("\t".to_string()),
+                ..Default::default()
+            },
+        ] {
+            let json = to_json(xml, &options);
+            let converted_xml = to_xml(&json, &options).unwrap();
+
+            assert_eq!(json, to_json(&converted_xml, &options));
+        }
+
+        let options = XmlToJsonOptions::default();
+
+        assert_eq!(
+            r#"<Feed version="2"><Title>A &amp; B</Title><Item id="1"><Name>First</Name><Tag>a</Tag><Tag>b</Tag></Item><Item id="2"><Name>&lt;Second&gt;</Name><Price currency="EUR">10.50</Price></Item><Single><Tag>c</Tag></Single><Empty/></Feed>"#,
+            to_xml(&to_json(xml, &options), &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_to_xml_errors() {
+        let options = XmlToJsonOptions::default();
+
+        assert!(to_xml("[1]", &options).is_err());
+        assert!(to_xml("{}", &options).is_err());
+        assert!(to_xml(r#"{"A": 1, "B": 2}"#, &options).is_err());
+        assert!(to_xml(r#"{"A": [1, 2]}"#, &options).is_err());
+        assert!(to_xml(r#"{"A": {"B": [[1]]}}"#, &options).is_err());
+        assert!(to_xml(r#"{"A": {"@b": {"c": 1}}}"#, &options).is_err());
+
+        assert_eq!(
+            "Value of '#text' in <A> must be a string, number, boolean or null",
+            to_xml(r##"{"A": {"#text": [1]}}"##, &options).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_names_are_errors() {
+        let options = XmlToJsonOptions::default();
+
+        let test_cases = [
+            (r#"{"": 1}"#, r#"Element name '' at $[""] is invalid"#),
+            (
+                r#"{"a b": {"@": "x", "1c": 2, "d>e": 3}}"#,
+                r#"Element name 'a b' at $["a b"] is invalid"#,
+            ),
+            (
+                r#"{"A": {"@": "x", "1c": 2, "d>e": 3}}"#,
+                r#"Attribute name '' at $["A"]["@"] is invalid"#,
+            ),
+            (
+                r#"{"A": {"1c": 2, "d>e": 3}}"#,
+                r#"Element name '1c' at $["A"]["1c"] is invalid"#,
+            ),
+            (
+                r#"{"A": {"B": [{"C": 1}, {"d>e": 3}]}}"#,
+                r#"Element name 'd>e' at $["A"]["B"][1]["d>e"] is invalid"#,
+            ),
+            (
+                r#"{"A": {"@x\"y": 1}}"#,
+                r#"Attribute name 'x"y' at $["A"]["@x\"y"] is invalid"#,
+            ),
+        ];
+
+        for (json, expected) in test_cases {
+            assert_eq!(expected, to_xml(json, &options).unwrap_err(), "{}", json);
+        }
+
+        let options = XmlToJsonOptions {
+            attributes: JsonAttributes::Nested,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            r#"Attribute name 'b c' at $["A"]["@attributes"]["b c"] is invalid"#,
+            to_xml(r#"{"A": {"@attributes": {"b c": 1}}}"#, &options).unwrap_err()
+        );
+
+        // "@" is not special in this convention
+        assert_eq!(
+            r#"Element name '@a' at $["R"]["@a"] is invalid"#,
+            to_xml(r#"{"R": {"@a": 1}}"#, &options).unwrap_err()
+        );
+    }
+}
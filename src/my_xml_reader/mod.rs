@@ -1,3 +1,4 @@
+mod byte_scan;
 mod my_xml_node;
 mod my_xml_reader;
 mod xml_tag_info;
@@ -9,5 +10,53 @@ pub use xml_tag_info::XmlTagType;
 
 pub use my_xml_reader::MyXmlReader;
 pub use my_xml_reader::OpenedNode;
+pub use my_xml_reader::XmlReaderCheckpoint;
+pub use my_xml_reader::XmlReaderMode;
 mod attributes_iterator;
 pub use attributes_iterator::*;
+mod parallel_reader;
+pub use parallel_reader::*;
+mod xml_validator;
+pub use xml_validator::*;
+mod xml_reader_warning;
+pub use xml_reader_warning::*;
+mod reader_options;
+pub use reader_options::*;
+mod xml_entities;
+pub use xml_entities::*;
+#[cfg(feature = "encoding")]
+mod xml_encoding;
+#[cfg(feature = "encoding")]
+pub use xml_encoding::*;
+mod xml_declaration;
+pub use xml_declaration::*;
+mod xml_whitespace;
+pub use xml_whitespace::*;
+mod my_xml_writer;
+pub use my_xml_writer::*;
+mod xml_to_json;
+pub use xml_to_json::*;
+#[cfg(feature = "json")]
+mod json_to_xml;
+#[cfg(feature = "json")]
+pub use json_to_xml::*;
+mod xml_c14n;
+pub use xml_c14n::*;
+#[cfg(feature = "xmldsig")]
+mod xml_dsig;
+#[cfg(feature = "xmldsig")]
+pub use xml_dsig::*;
+mod xml_diff;
+pub use xml_diff::*;
+mod xsd_pattern;
+pub use xsd_pattern::*;
+mod xsd_schema;
+pub use xsd_schema::*;
+mod xsd_validator;
+pub use xsd_validator::*;
+mod xml_dtd;
+pub use xml_dtd::*;
+mod xml_dtd_validator;
+pub use xml_dtd_validator::*;
+mod xml_transform;
+pub use xml_transform::*;
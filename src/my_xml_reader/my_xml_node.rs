@@ -1,4 +1,6 @@
-use super::XmlTagInfo;
+use std::borrow::Cow;
+
+use super::{XmlTagInfo, XmlWhitespaceHandling};
 
 pub struct MyXmlNode<'t> {
     pub xml: &'t [u8],
@@ -20,24 +22,74 @@ impl<'t> MyXmlNode<'t> {
         result
     }
 
-    pub fn get_value(&self) -> Option<String> {
-        let inner_content = self.get_inner_content()?;
+    pub fn get_value(&self) -> Result<Option<String>, String> {
+        let Some(inner_content) = self.get_inner_content() else {
+            return Ok(None);
+        };
 
-        let value = std::str::from_utf8(inner_content).unwrap();
+        let value = std::str::from_utf8(inner_content).map_err(|err| {
+            format!(
+                "Value of node <{}> is not valid UTF-8: {}",
+                self.get_node_name(),
+                err
+            )
+        })?;
 
-        if super::xml_utils::has_special_symbol(value) {
-            return Some(super::xml_utils::decode_xml_string(value));
+        Ok(Some(decode_value(value)))
+    }
+
+    // Only xml:space of the node itself is honored here. The reader also knows
+    // about its ancestors, see MyXmlReader::get_node_value.
+    pub fn get_value_normalized(
+        &self,
+        handling: XmlWhitespaceHandling,
+    ) -> Result<Option<String>, String> {
+        let Some(value) = self.get_value()? else {
+            return Ok(None);
+        };
+
+        // An invalid xml:space is ignored
+        if super::get_xml_space(self.open_node.raw) == Ok(Some(true)) {
+            return Ok(Some(value));
         }
 
-        return Some(value.to_string());
+        Ok(Some(
+            super::normalize_whitespace(&value, handling).into_owned(),
+        ))
+    }
+
+    // Invalid UTF-8 sequences are replaced with U+FFFD
+    pub fn get_value_lossy(&self) -> Option<String> {
+        let value = String::from_utf8_lossy(self.get_inner_content()?);
+        Some(decode_value(&value))
     }
 
-    pub fn get_xml(&self) -> &'t str {
-        let xml = match &self.close_node {
+    pub fn get_xml(&self) -> Result<&'t str, String> {
+        std::str::from_utf8(self.get_xml_bytes()).map_err(|err| {
+            format!(
+                "Node <{}> is not valid UTF-8: {}",
+                self.get_node_name(),
+                err
+            )
+        })
+    }
+
+    pub fn get_xml_lossy(&self) -> Cow<'t, str> {
+        String::from_utf8_lossy(self.get_xml_bytes())
+    }
+
+    fn get_xml_bytes(&self) -> &'t [u8] {
+        match &self.close_node {
             Some(close_node) => &self.xml[self.open_node.start_pos..close_node.end_pos + 1],
             None => &self.xml[self.open_node.start_pos..self.open_node.end_pos + 1],
-        };
+        }
+    }
+}
 
-        return std::str::from_utf8(xml).unwrap();
+fn decode_value(value: &str) -> String {
+    if super::xml_utils::has_special_symbol(value) {
+        return super::xml_utils::decode_xml_string(value);
     }
+
+    value.to_string()
 }
@@ -1,26 +1,383 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
+
 use super::XmlTagType;
-use super::{MyXmlNode, XmlTagInfo};
+use super::{MyXmlNode, XmlTagInfo, XmlValidationError, XmlWellFormednessChecker};
+use super::{ReaderOptions, XmlDeclaration, XmlDtd, XmlEntities, XmlLimit, XmlLimitError};
+use super::{XmlReaderWarning, XmlReaderWarningKind};
 
-#[derive(Debug)]
-pub struct OpenedNode {
-    pub name: String,
+#[derive(Debug, Clone)]
+pub struct OpenedNode<'t> {
+    pub name: &'t str,
     pub start_pos: usize,
     pub end_pos: usize,
+    // Effective xml:space="preserve" inherited from the ancestors. It is
+    // tracked only when ReaderOptions::value_whitespace normalizes values.
+    pub preserve_space: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum XmlReaderMode {
+    #[default]
+    Default,
+    Strict,
+    Lenient,
+}
+
+#[derive(Debug, Clone)]
+pub struct XmlReaderCheckpoint<'t> {
+    current_pos: usize,
+    opened_nodes: Vec<OpenedNode<'t>>,
+    root_closed: bool,
+    checker: Option<XmlWellFormednessChecker<'t>>,
+    warnings_amount: usize,
 }
 
+#[derive(Clone)]
 pub struct MyXmlReader<'t> {
     pub xml: &'t [u8],
+    // Is set when the whole document is validated as UTF-8 up front
+    xml_str: Option<&'t str>,
     current_pos: usize,
-    pub opened_nodes: Vec<OpenedNode>,
+    pub opened_nodes: Vec<OpenedNode<'t>>,
+    root_closed: bool,
+    options: ReaderOptions,
+    checker: Option<XmlWellFormednessChecker<'t>>,
+    warnings: Vec<XmlReaderWarning>,
+    exceeded_limit: Option<XmlLimitError>,
+    entities: XmlEntities,
+    declaration: Option<XmlDeclaration>,
+    declaration_error: Option<XmlValidationError>,
+    // Shared with the clones, as a DTD may be large
+    dtd: Option<Arc<XmlDtd>>,
 }
 
 impl<'t> MyXmlReader<'t> {
     pub fn from_slice(xml: &'t [u8]) -> Result<Self, String> {
+        Self::from_slice_with_mode(xml, XmlReaderMode::Default)
+    }
+
+    pub fn from_slice_with_mode(xml: &'t [u8], mode: XmlReaderMode) -> Result<Self, String> {
+        Self::from_slice_with_options(xml, ReaderOptions::with_mode(mode))
+    }
+
+    pub fn from_slice_with_options(xml: &'t [u8], options: ReaderOptions) -> Result<Self, String> {
+        super::reader_options::check_limit(
+            XmlLimit::DocumentSize,
+            options.max_document_size,
+            xml.len(),
+            0,
+        )
+        .map_err(|err| err.to_string())?;
+
+        let xml_str = if options.validate_utf8 {
+            let xml_str = std::str::from_utf8(xml)
+                .map_err(|err| format!("Document is not valid UTF-8: {}", err))?;
+            Some(xml_str)
+        } else {
+            None
+        };
+
+        let mut warnings = vec![];
+
+        // Only the strict mode fails on a declaration which is not valid or not
+        // at the very start. Other modes keep it for get_declaration_error.
+        let (declaration, declaration_error) = match XmlDeclaration::parse(xml) {
+            Ok(declaration) => (declaration, None),
+            Err(err) if options.mode == XmlReaderMode::Strict => return Err(err.to_string()),
+            Err(err) => {
+                if options.mode == XmlReaderMode::Lenient {
+                    warnings.push(XmlReaderWarning::new(
+                        XmlReaderWarningKind::InvalidXmlDeclaration(err.kind.to_string()),
+                        err.pos,
+                    ));
+                }
+
+                (None, Some(err))
+            }
+        };
+
         let current_pos = super::xml_utils::init_pos_start(xml)?;
+
+        let entities = XmlEntities::from_prolog(xml, current_pos)?;
+
+        let checker = match options.mode {
+            XmlReaderMode::Default | XmlReaderMode::Lenient => None,
+            XmlReaderMode::Strict => {
+                let mut checker = XmlWellFormednessChecker::default();
+                let mut errors = Vec::new();
+
+                let prolog_end = checker.check_prolog(xml, &mut errors);
+                checker.check_content(xml, prolog_end, current_pos, &mut errors);
+
+                check_errors(errors)?;
+
+                Some(checker)
+            }
+        };
+
         Ok(Self {
             xml,
+            xml_str,
             current_pos,
             opened_nodes: vec![],
+            root_closed: false,
+            options,
+            checker,
+            warnings,
+            exceeded_limit: None,
+            entities,
+            declaration,
+            declaration_error,
+            dtd: None,
+        })
+    }
+
+    pub fn get_mode(&self) -> XmlReaderMode {
+        self.options.mode
+    }
+
+    pub fn get_options(&self) -> &ReaderOptions {
+        &self.options
+    }
+
+    pub fn get_exceeded_limit(&self) -> Option<&XmlLimitError> {
+        self.exceeded_limit.as_ref()
+    }
+
+    pub fn get_warnings(&self) -> &[XmlReaderWarning] {
+        &self.warnings
+    }
+
+    pub fn get_declaration(&self) -> Option<&XmlDeclaration> {
+        self.declaration.as_ref()
+    }
+
+    // Why the XML declaration was not accepted: it is not valid or goes after
+    // whitespace. The reader is created anyway in the default and lenient modes.
+    pub fn get_declaration_error(&self) -> Option<&XmlValidationError> {
+        self.declaration_error.as_ref()
+    }
+
+    pub fn get_entities(&self) -> &XmlEntities {
+        &self.entities
+    }
+
+    // Replaces the entities declared in the prolog, e.g. with the entities of
+    // the document a fragment is cut from
+    pub fn set_entities(&mut self, entities: XmlEntities) {
+        self.entities = entities;
+
+        if let Some(checker) = self.checker.as_mut() {
+            checker.set_doctype_found();
+        }
+    }
+
+    // Declared attribute defaults of the DTD are returned for the attributes
+    // which are not specified
+    pub fn set_dtd(&mut self, dtd: XmlDtd) {
+        self.dtd = Some(Arc::new(dtd));
+    }
+
+    pub fn get_dtd(&self) -> Option<&XmlDtd> {
+        self.dtd.as_deref()
+    }
+
+    pub fn expand_entities(&self, text: &str) -> Result<String, String> {
+        self.entities
+            .expand(
+                text,
+                self.options.max_entity_expansion_size,
+                self.options.max_entity_expansion_depth,
+            )
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn get_node_value(&self, node: &MyXmlNode<'t>) -> Result<Option<String>, String> {
+        let Some(inner_content) = node.get_inner_content() else {
+            return Ok(None);
+        };
+
+        let start_pos = node.open_node.end_pos + 1;
+
+        let value = self
+            .get_str(start_pos..start_pos + inner_content.len())
+            .map_err(|err| {
+                format!(
+                    "Value of node <{}> is not valid UTF-8: {}",
+                    node.get_node_name(),
+                    err
+                )
+            })?;
+
+        let value = if !self.is_xml_space_tracked() || self.is_space_preserved(&node.open_node)? {
+            Cow::Borrowed(value)
+        } else {
+            super::normalize_whitespace(value, self.options.value_whitespace)
+        };
+
+        Ok(Some(self.expand_entities(&value)?))
+    }
+
+    // Checks xml:space of the tag and then of its opened ancestors. Opened
+    // nodes keep the inherited value only when values are normalized, otherwise
+    // the raw tags of the ancestors are read here.
+    pub fn is_space_preserved(&self, tag: &XmlTagInfo<'t>) -> Result<bool, String> {
+        if let Some(preserve_space) = self.read_xml_space(tag.raw)? {
+            return Ok(preserve_space);
+        }
+
+        let ancestors = match self.opened_nodes.get(..tag.level) {
+            Some(ancestors) => ancestors,
+            None => &self.opened_nodes,
+        };
+
+        let parent = match ancestors.last() {
+            Some(parent) if parent.start_pos < tag.start_pos => parent,
+            _ => return Ok(false),
+        };
+
+        if self.is_xml_space_tracked() {
+            return Ok(parent.preserve_space);
+        }
+
+        for ancestor in ancestors.iter().rev() {
+            let raw = &self.xml[ancestor.start_pos..ancestor.end_pos + 1];
+
+            if let Some(preserve_space) = self.read_xml_space(raw)? {
+                return Ok(preserve_space);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn is_xml_space_tracked(&self) -> bool {
+        self.options.value_whitespace != super::XmlWhitespaceHandling::Preserve
+    }
+
+    // An invalid xml:space is ignored, so the value is inherited. Only the
+    // strict mode fails on it.
+    fn read_xml_space(&self, raw: &[u8]) -> Result<Option<bool>, String> {
+        match super::get_xml_space(raw) {
+            Ok(preserve_space) => Ok(preserve_space),
+            Err(err) if self.options.mode == XmlReaderMode::Strict => Err(err),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn get_attribute_value(
+        &self,
+        tag: &XmlTagInfo<'t>,
+        attr_name: &str,
+    ) -> Result<Option<String>, String> {
+        let mut attributes = tag.iterate_attributes();
+
+        while let Some((name, value)) = attributes.get_next()? {
+            if name == attr_name {
+                return Ok(Some(self.expand_entities(value)?));
+            }
+        }
+
+        let default = self
+            .get_dtd()
+            .and_then(|dtd| dtd.find_attribute(tag.name, attr_name))
+            .and_then(|attribute| attribute.get_default_value());
+
+        match default {
+            Some(value) => Ok(Some(self.expand_entities(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Specified attributes with entities expanded, followed by the defaults
+    // declared in the DTD for the missing ones
+    pub fn get_attributes(&self, tag: &XmlTagInfo<'t>) -> Result<Vec<(String, String)>, String> {
+        let mut result = vec![];
+        let mut attributes = tag.iterate_attributes();
+
+        while let Some((name, value)) = attributes.get_next()? {
+            result.push((name.to_string(), self.expand_entities(value)?));
+        }
+
+        let declared = self
+            .get_dtd()
+            .and_then(|dtd| dtd.attributes.get(tag.name))
+            .map_or(&[][..], |declared| &declared[..]);
+
+        for attribute in declared {
+            let Some(value) = attribute.get_default_value() else {
+                continue;
+            };
+
+            if !result.iter().any(|(name, _)| *name == attribute.name) {
+                result.push((attribute.name.clone(), self.expand_entities(value)?));
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn save_checkpoint(&self) -> XmlReaderCheckpoint<'t> {
+        XmlReaderCheckpoint {
+            current_pos: self.current_pos,
+            opened_nodes: self.opened_nodes.clone(),
+            root_closed: self.root_closed,
+            checker: self.checker.clone(),
+            warnings_amount: self.warnings.len(),
+        }
+    }
+
+    pub fn restore_checkpoint(&mut self, checkpoint: &XmlReaderCheckpoint<'t>) {
+        self.current_pos = checkpoint.current_pos;
+        self.opened_nodes.clone_from(&checkpoint.opened_nodes);
+        self.root_closed = checkpoint.root_closed;
+        self.checker.clone_from(&checkpoint.checker);
+        self.warnings.truncate(checkpoint.warnings_amount);
+    }
+
+    pub fn clone_at(&self, tag: &XmlTagInfo<'t>) -> Result<Self, String> {
+        let depth = match tag.tag_type {
+            XmlTagType::Close => tag.level + 1,
+            _ => tag.level,
+        };
+
+        let is_inside_opened_nodes = match depth.checked_sub(1) {
+            Some(index) => match self.opened_nodes.get(index) {
+                Some(opened_node) => opened_node.start_pos < tag.start_pos,
+                None => false,
+            },
+            None => true,
+        };
+
+        if !is_inside_opened_nodes || tag.start_pos > self.current_pos {
+            return Err(format!(
+                "Can not clone reader at tag <{}> with pos {}. Its parent nodes are not opened anymore",
+                tag.name, tag.start_pos
+            ));
+        }
+
+        let mut checker = self.checker.clone();
+
+        if let Some(checker) = checker.as_mut() {
+            checker.rewind_to_level(depth);
+        }
+
+        Ok(Self {
+            xml: self.xml,
+            xml_str: self.xml_str,
+            current_pos: tag.start_pos,
+            opened_nodes: self.opened_nodes[..depth].to_vec(),
+            root_closed: false,
+            options: self.options.clone(),
+            checker,
+            warnings: self.warnings.clone(),
+            exceeded_limit: None,
+            entities: self.entities.clone(),
+            declaration: self.declaration.clone(),
+            declaration_error: self.declaration_error.clone(),
+            dtd: self.dtd.clone(),
         })
     }
 
@@ -58,7 +415,7 @@ impl<'t> MyXmlReader<'t> {
                             }
                         }
                         XmlTagType::Close => {
-                            if parent_tag.level == node.level && parent_tag.name == node.name {
+                            if is_parent_closed(parent_tag, &node) {
                                 return Ok(None);
                             }
                         }
@@ -101,7 +458,7 @@ impl<'t> MyXmlReader<'t> {
                         }
                     }
                     XmlTagType::Close => {
-                        if parent_tag.level == node.level && parent_tag.name == node.name {
+                        if is_parent_closed(parent_tag, &node) {
                             return Ok(None);
                         }
                     }
@@ -110,8 +467,21 @@ impl<'t> MyXmlReader<'t> {
         }
     }
 
-    pub fn get_unread_slice(&self) -> &'t str {
-        std::str::from_utf8(&self.xml[self.current_pos..]).unwrap()
+    pub fn get_unread_slice(&self) -> Result<&'t str, String> {
+        self.get_str(self.current_pos..self.xml.len())
+            .map_err(|err| format!("Unread part is not valid UTF-8: {}", err))
+    }
+
+    // Ranges always start and end at ASCII markup bytes, so once the document
+    // is validated they are on char boundaries and no check is needed.
+    fn get_str(&self, range: Range<usize>) -> Result<&'t str, std::str::Utf8Error> {
+        if let Some(xml_str) = self.xml_str {
+            if let Some(result) = xml_str.get(range.clone()) {
+                return Ok(result);
+            }
+        }
+
+        std::str::from_utf8(&self.xml[range])
     }
 
     pub fn find_the_open_node(&mut self, x_path: &str) -> Result<Option<XmlTagInfo<'t>>, String> {
@@ -162,75 +532,297 @@ impl<'t> MyXmlReader<'t> {
         Ok(None)
     }
 
-    fn move_to_the_next_open_tag_pos(&mut self) -> Result<bool, String> {
-        if self.current_pos == self.xml.len() {
-            return Ok(false);
+    pub fn read_next_tag(&mut self) -> Result<Option<XmlTagInfo<'t>>, String> {
+        loop {
+            let content_start = self.current_pos;
+            let tag_info = self.scan_for_the_next_tag()?;
+
+            self.check_limits(content_start, tag_info.as_ref())?;
+
+            if let Some(checker) = self.checker.as_mut() {
+                let mut errors = Vec::new();
+
+                match &tag_info {
+                    Some(tag_info) => {
+                        checker.check_content(
+                            self.xml,
+                            content_start,
+                            tag_info.start_pos,
+                            &mut errors,
+                        );
+                        checker.check_tag(
+                            self.xml,
+                            tag_info.start_pos,
+                            tag_info.end_pos,
+                            &mut errors,
+                        );
+                    }
+                    None => {
+                        checker.check_content(self.xml, content_start, self.xml.len(), &mut errors);
+                        checker.check_end(self.xml.len(), &mut errors);
+                    }
+                }
+
+                check_errors(errors)?;
+            }
+
+            if self.options.mode == XmlReaderMode::Lenient {
+                let content_end = match &tag_info {
+                    Some(tag_info) => tag_info.start_pos,
+                    None => self.xml.len(),
+                };
+
+                self.check_bare_ampersands(content_start, content_end);
+            }
+
+            let Some(mut tag_info) = tag_info else {
+                if self.options.mode == XmlReaderMode::Lenient {
+                    self.close_implicitly(0, self.xml.len());
+                }
+
+                return Ok(None);
+            };
+
+            self.current_pos = tag_info.end_pos + 1;
+
+            match tag_info.tag_type {
+                XmlTagType::Open => {
+                    let preserve_space = if self.is_xml_space_tracked() {
+                        self.check_xml_space(&tag_info);
+                        self.is_space_preserved(&tag_info)?
+                    } else {
+                        false
+                    };
+
+                    self.opened_nodes.push(OpenedNode {
+                        name: tag_info.name,
+                        start_pos: tag_info.start_pos,
+                        end_pos: tag_info.end_pos,
+                        preserve_space,
+                    });
+                }
+
+                XmlTagType::Close => {
+                    if self.options.mode == XmlReaderMode::Lenient {
+                        if !self.recover_close_tag(&mut tag_info) {
+                            continue;
+                        }
+                    } else {
+                        self.close_tag(&tag_info)?;
+                    }
+                }
+
+                XmlTagType::OpenClose => {}
+            }
+
+            if self.opened_nodes.is_empty() {
+                self.root_closed = true;
+            }
+
+            return Ok(Some(tag_info));
+        }
+    }
+
+    fn close_tag(&mut self, tag_info: &XmlTagInfo<'t>) -> Result<(), String> {
+        let last_opened_tag = self.opened_nodes.last();
+
+        if last_opened_tag.is_none() {
+            return Err(format!(
+                "Attempt to close tag with name {}. There are no opened tags",
+                tag_info.name
+            ));
         }
 
-        if self.xml[self.current_pos] == b'<' {
-            return Ok(true);
+        let last_opened_tag = last_opened_tag.unwrap();
+
+        if last_opened_tag.name != tag_info.name {
+            return Err(format!(
+                "Attempt to close tag with name </{}>. But last opened tag has the name <{}>",
+                tag_info.name, last_opened_tag.name
+            ));
         }
 
-        let next_pos = super::xml_utils::find_next_token(&self.xml, b'<', self.current_pos);
+        self.opened_nodes.pop();
+        Ok(())
+    }
 
-        match next_pos {
-            Some(pos) => {
-                self.current_pos = pos;
-                Ok(true)
+    fn check_limits(
+        &mut self,
+        content_start: usize,
+        tag_info: Option<&XmlTagInfo<'t>>,
+    ) -> Result<(), String> {
+        let result = self.get_exceeded_limit_error(content_start, tag_info);
+
+        if let Err(err) = result {
+            let message = err.to_string();
+            self.exceeded_limit = Some(err);
+            return Err(message);
+        }
+
+        Ok(())
+    }
+
+    fn get_exceeded_limit_error(
+        &self,
+        content_start: usize,
+        tag_info: Option<&XmlTagInfo<'t>>,
+    ) -> Result<(), XmlLimitError> {
+        use super::reader_options::check_limit;
+
+        let content_end = tag_info.map_or(self.xml.len(), |tag_info| tag_info.start_pos);
+
+        check_limit(
+            XmlLimit::TextSize,
+            self.options.max_text_size,
+            content_end - content_start,
+            content_start,
+        )?;
+
+        let Some(tag_info) = tag_info else {
+            return Ok(());
+        };
+
+        check_limit(
+            XmlLimit::NameLength,
+            self.options.max_name_length,
+            tag_info.name.len(),
+            tag_info.start_pos,
+        )?;
+
+        if !matches!(tag_info.tag_type, XmlTagType::Close) {
+            check_limit(
+                XmlLimit::Depth,
+                self.options.max_depth,
+                self.opened_nodes.len() + 1,
+                tag_info.start_pos,
+            )?;
+
+            if self.options.max_attributes_per_tag.is_some()
+                || self.options.max_name_length.is_some()
+            {
+                let (attributes_amount, max_attribute_name_len) =
+                    super::xml_utils::get_attributes_stats(tag_info.raw);
+
+                check_limit(
+                    XmlLimit::AttributesPerTag,
+                    self.options.max_attributes_per_tag,
+                    attributes_amount,
+                    tag_info.start_pos,
+                )?;
+
+                check_limit(
+                    XmlLimit::NameLength,
+                    self.options.max_name_length,
+                    max_attribute_name_len,
+                    tag_info.start_pos,
+                )?;
             }
-            None => Err(format!(
-                "Can not find the next open tag positin from pos {}",
-                self.current_pos
-            )),
         }
+
+        Ok(())
     }
 
-    pub fn read_next_tag(&mut self) -> Result<Option<XmlTagInfo<'t>>, String> {
-        let tag_info = self.scan_for_the_next_tag()?;
+    // Returns false if the close tag matches no opened tag and has to be ignored.
+    // Elements without close tags, such as <br>, are closed here by the close
+    // tag of an ancestor, so the elements between are read as their children.
+    fn recover_close_tag(&mut self, tag_info: &mut XmlTagInfo<'t>) -> bool {
+        let index = self
+            .opened_nodes
+            .iter()
+            .rposition(|opened| opened.name == tag_info.name)
+            .or_else(|| {
+                self.opened_nodes
+                    .iter()
+                    .rposition(|opened| opened.name.eq_ignore_ascii_case(tag_info.name))
+            });
+
+        let Some(index) = index else {
+            self.warnings.push(XmlReaderWarning::new(
+                XmlReaderWarningKind::StrayCloseTag(tag_info.name.to_string()),
+                tag_info.start_pos,
+            ));
+            return false;
+        };
 
-        if tag_info.is_none() {
-            return Ok(None);
+        self.close_implicitly(index + 1, tag_info.start_pos);
+
+        let opened_name = self.opened_nodes[index].name;
+
+        if opened_name != tag_info.name {
+            self.warnings.push(XmlReaderWarning::new(
+                XmlReaderWarningKind::CaseInsensitiveCloseTag {
+                    opened: opened_name.to_string(),
+                    found: tag_info.name.to_string(),
+                },
+                tag_info.start_pos,
+            ));
+
+            tag_info.name = opened_name;
         }
 
-        let tag_info = tag_info.unwrap();
+        self.opened_nodes.pop();
+        tag_info.level = index;
+        true
+    }
 
-        self.current_pos = tag_info.end_pos + 1;
+    fn check_xml_space(&mut self, tag_info: &XmlTagInfo<'t>) {
+        if self.options.mode != XmlReaderMode::Lenient {
+            return;
+        }
 
-        match tag_info.tag_type {
-            XmlTagType::Open => {
-                self.opened_nodes.push(OpenedNode {
-                    name: tag_info.name.to_string(),
-                    start_pos: tag_info.start_pos,
-                    end_pos: tag_info.end_pos,
-                });
-            }
+        if let Err(err) = super::get_xml_space(tag_info.raw) {
+            self.warnings.push(XmlReaderWarning::new(
+                XmlReaderWarningKind::InvalidXmlSpace(err),
+                tag_info.start_pos,
+            ));
+        }
+    }
 
-            XmlTagType::Close => {
-                let last_opened_tag = self.opened_nodes.last();
+    fn close_implicitly(&mut self, level: usize, pos: usize) {
+        while self.opened_nodes.len() > level {
+            let opened = self.opened_nodes.pop().unwrap();
 
-                if last_opened_tag.is_none() {
-                    return Err(format!(
-                        "Attempt to close tag with name {}. There are no opened tags",
-                        tag_info.name
+            self.warnings.push(XmlReaderWarning::new(
+                XmlReaderWarningKind::ImplicitlyClosedElement(opened.name.to_string()),
+                pos,
+            ));
+        }
+    }
+
+    fn check_bare_ampersands(&mut self, start_pos: usize, end_pos: usize) {
+        let mut pos = start_pos;
+
+        while pos < end_pos {
+            let text_end = super::xml_utils::find_next_token(self.xml, b'<', pos)
+                .filter(|text_end| *text_end < end_pos)
+                .unwrap_or(end_pos);
+
+            let mut amp_pos = pos;
+
+            while let Some(found) = super::xml_utils::find_next_token(self.xml, b'&', amp_pos) {
+                if found >= text_end {
+                    break;
+                }
+
+                if !super::xml_utils::is_reference_at(&self.xml[..text_end], found) {
+                    self.warnings.push(XmlReaderWarning::new(
+                        XmlReaderWarningKind::BareAmpersand,
+                        found,
                     ));
                 }
 
-                let last_opened_tag = last_opened_tag.unwrap();
+                amp_pos = found + 1;
+            }
 
-                if last_opened_tag.name != tag_info.name {
-                    return Err(format!(
-                            "Attempt to close tag with name </{}>. But last opened tag has the name <{}>",
-                            tag_info.name, last_opened_tag.name
-                        ));
-                } else {
-                    self.opened_nodes.pop();
-                }
+            if text_end == end_pos {
+                break;
             }
 
-            XmlTagType::OpenClose => {}
+            match super::xml_utils::find_markup_end(self.xml, text_end) {
+                Some(markup_end) => pos = markup_end + 1,
+                None => break,
+            }
         }
-
-        return Ok(Some(tag_info));
     }
 
     fn find_corelated_closed_node(
@@ -253,8 +845,8 @@ impl<'t> MyXmlReader<'t> {
             self.current_pos = next_node.end_pos + 1;
 
             if matches!(next_node.tag_type, XmlTagType::Close)
-                && next_node.level == node_level
-                && next_node.name == node_name
+                && (next_node.level < node_level
+                    || (next_node.level == node_level && next_node.name == node_name))
             {
                 return Ok(next_node);
             }
@@ -275,7 +867,7 @@ impl<'t> MyXmlReader<'t> {
             return Ok(result);
         }
 
-        let close_node = self.find_corelated_closed_node(open_node.level, &open_node.name)?;
+        let close_node = self.find_corelated_closed_node(open_node.level, open_node.name)?;
 
         let reuslt = MyXmlNode {
             xml: self.xml,
@@ -286,38 +878,123 @@ impl<'t> MyXmlReader<'t> {
         return Ok(reuslt);
     }
 
-    fn scan_for_the_next_tag(&mut self) -> Result<Option<XmlTagInfo<'t>>, String> {
-        if !self.move_to_the_next_open_tag_pos()? {
-            return Ok(None);
+    pub fn skip_node(&mut self, open_tag: &XmlTagInfo<'t>) -> Result<Range<usize>, String> {
+        if matches!(open_tag.tag_type, XmlTagType::OpenClose) {
+            return Ok(open_tag.start_pos..open_tag.end_pos + 1);
         }
 
-        let start_pos = self.current_pos;
-        let end_of_open_tag_pos = super::xml_utils::find_next_token(
-            self.xml,
-            super::xml_utils::CLOSE_TAG_TOKEN,
-            self.current_pos,
-        );
+        if open_tag.level + 1 != self.get_level() {
+            return Err(format!(
+                "Can not skip node <{}>. It is not the last opened node",
+                open_tag.name
+            ));
+        }
+
+        self.fast_forward_to_close_tag(open_tag)
+    }
 
-        if end_of_open_tag_pos.is_none() {
+    pub fn skip_to_parent_end(
+        &mut self,
+        parent_tag: &XmlTagInfo<'t>,
+    ) -> Result<Range<usize>, String> {
+        if matches!(parent_tag.tag_type, XmlTagType::OpenClose) {
+            return Ok(parent_tag.start_pos..parent_tag.end_pos + 1);
+        }
+
+        self.fast_forward_to_close_tag(parent_tag)
+    }
+
+    fn fast_forward_to_close_tag(
+        &mut self,
+        open_tag: &XmlTagInfo<'t>,
+    ) -> Result<Range<usize>, String> {
+        if !matches!(open_tag.tag_type, XmlTagType::Open) {
+            return Err(format!(
+                "Can not skip node </{}>. Open tag is expected",
+                open_tag.name
+            ));
+        }
+
+        let is_opened = match self.opened_nodes.get(open_tag.level) {
+            Some(opened_node) => opened_node.start_pos == open_tag.start_pos,
+            None => false,
+        };
+
+        if !is_opened {
             return Err(format!(
-                "Can not find the close. Start pos is {}",
-                start_pos
+                "Can not skip node <{}>. Node is not opened",
+                open_tag.name
             ));
         }
 
-        let end_pos = end_of_open_tag_pos.unwrap();
+        if self.options.mode != XmlReaderMode::Default {
+            // Skipped content still has to be checked or recovered.
+            let close_tag = self.find_corelated_closed_node(open_tag.level, open_tag.name)?;
+            return Ok(open_tag.start_pos..close_tag.end_pos + 1);
+        }
+
+        let mut depth = self.get_level() - open_tag.level;
+        let mut pos = self.current_pos;
+
+        loop {
+            let Some((start_pos, end_pos)) = super::xml_utils::find_next_tag(self.xml, pos)? else {
+                return Err(format!(
+                    "Can not find the node to close with name: {} and level {}",
+                    open_tag.name, open_tag.level
+                ));
+            };
+
+            let raw = &self.xml[start_pos..end_pos + 1];
+
+            // Tags are classified the same way as read_next_tag does
+            let (tag_type, name) = super::xml_utils::parse_tag(raw, start_pos)?;
+
+            match tag_type {
+                XmlTagType::Open => depth += 1,
+                XmlTagType::Close => depth -= 1,
+                XmlTagType::OpenClose => {}
+            }
+
+            pos = end_pos + 1;
 
-        let raw = &self.xml[self.current_pos..end_pos + 1];
+            if depth == 0 {
+                let name = &raw[name];
 
-        let mut tag_type = XmlTagType::Open;
+                if name != open_tag.name.as_bytes() {
+                    return Err(format!(
+                        "Attempt to close tag with name </{}>. But last opened tag has the name <{}>",
+                        String::from_utf8_lossy(name),
+                        open_tag.name
+                    ));
+                }
 
-        if raw[raw.len() - 2] == b'/' {
-            tag_type = XmlTagType::OpenClose;
-        } else if raw[1] == b'/' {
-            tag_type = XmlTagType::Close;
+                self.current_pos = pos;
+                self.opened_nodes.truncate(open_tag.level);
+                self.root_closed = open_tag.level == 0;
+                return Ok(open_tag.start_pos..pos);
+            }
         }
+    }
 
-        let name = std::str::from_utf8(super::xml_utils::extract_tag_name(raw)).unwrap();
+    fn scan_for_the_next_tag(&mut self) -> Result<Option<XmlTagInfo<'t>>, String> {
+        let Some((start_pos, end_pos)) =
+            super::xml_utils::find_next_tag(self.xml, self.current_pos)?
+        else {
+            return Ok(None);
+        };
+
+        let raw = &self.xml[start_pos..end_pos + 1];
+
+        let (tag_type, name) = super::xml_utils::parse_tag(raw, start_pos)?;
+
+        let name = self
+            .get_str(start_pos + name.start..start_pos + name.end)
+            .map_err(|err| {
+                format!(
+                    "Name of the tag at pos {} is not valid UTF-8: {}",
+                    start_pos, err
+                )
+            })?;
 
         let mut result = XmlTagInfo {
             name,
@@ -328,8 +1005,10 @@ impl<'t> MyXmlReader<'t> {
             level: self.get_level(),
         };
 
+        // A close tag without opened tags keeps level 0. read_next_tag rejects
+        // it or, in the lenient mode, skips it.
         if matches!(result.tag_type, XmlTagType::Close) {
-            result.level -= 1;
+            result.level = result.level.saturating_sub(1);
         }
 
         Ok(Some(result))
@@ -338,17 +1017,46 @@ impl<'t> MyXmlReader<'t> {
     pub fn get_level(&self) -> usize {
         return self.opened_nodes.len();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn get_unclosed_nodes(&self) -> &[OpenedNode<'t>] {
+        &self.opened_nodes
+    }
 
-    #[test]
-    fn test_find_open_tag_after_header_node() {
-        let xml_src = r#"<?xml version="1.0" encoding="utf-8"?><RootNode></RootNode>"#;
+    pub fn is_document_complete(&self) -> bool {
+        if !self.root_closed || !self.opened_nodes.is_empty() {
+            return false;
+        }
 
-        let xml = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+        matches!(
+            super::xml_utils::find_next_tag(self.xml, self.current_pos),
+            Ok(None)
+        )
+    }
+}
+
+// A close tag with a lower level ends the parent implicitly in the lenient mode.
+fn is_parent_closed(parent_tag: &XmlTagInfo, close_tag: &XmlTagInfo) -> bool {
+    close_tag.level < parent_tag.level
+        || (close_tag.level == parent_tag.level && close_tag.name == parent_tag.name)
+}
+
+fn check_errors(errors: Vec<XmlValidationError>) -> Result<(), String> {
+    match errors.into_iter().next() {
+        Some(err) => Err(err.to_string()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::my_xml_reader::XmlWhitespaceHandling;
+
+    #[test]
+    fn test_find_open_tag_after_header_node() {
+        let xml_src = r#"<?xml version="1.0" encoding="utf-8"?><RootNode></RootNode>"#;
+
+        let xml = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
 
         assert_eq!("<RootNode></RootNode>", &xml_src[xml.current_pos..])
     }
@@ -440,7 +1148,7 @@ mod tests {
             .unwrap();
         let whole_node = reader.read_the_whole_node(found.unwrap()).unwrap();
 
-        assert_eq!("Val1", whole_node.get_value().unwrap());
+        assert_eq!("Val1", whole_node.get_value().unwrap().unwrap());
 
         assert_eq!("Tag1", whole_node.get_node_name());
 
@@ -449,7 +1157,7 @@ mod tests {
             .unwrap();
         let whole_node = reader.read_the_whole_node(found.unwrap()).unwrap();
 
-        assert_eq!("Val2", whole_node.get_value().unwrap());
+        assert_eq!("Val2", whole_node.get_value().unwrap().unwrap());
         assert_eq!("Tag1", whole_node.get_node_name());
 
         let found = reader
@@ -472,21 +1180,21 @@ mod tests {
 
         let whole_node = reader.read_the_whole_node(el_item.unwrap()).unwrap();
 
-        assert_eq!("<I>V1</I>", whole_node.get_xml());
+        assert_eq!("<I>V1</I>", whole_node.get_xml().unwrap());
 
         let el_item = reader
             .find_the_node_inside_parent(&array_node, "I")
             .unwrap();
         let whole_node = reader.read_the_whole_node(el_item.unwrap()).unwrap();
 
-        assert_eq!("<I>V2</I>", whole_node.get_xml());
+        assert_eq!("<I>V2</I>", whole_node.get_xml().unwrap());
 
         let el_item = reader
             .find_the_node_inside_parent(&array_node, "I")
             .unwrap();
 
         assert_eq!(true, el_item.is_none());
-        assert_eq!("<S2>V2</S2></R>", reader.get_unread_slice())
+        assert_eq!("<S2>V2</S2></R>", reader.get_unread_slice().unwrap())
     }
 
     #[test]
@@ -498,7 +1206,7 @@ mod tests {
 
         assert_eq!(
             "<S1><I>V1</I><I>V2</I></S1><S2>V2</S2></A></R>",
-            reader.get_unread_slice()
+            reader.get_unread_slice().unwrap()
         );
 
         let node_names = vec!["S1", "S2"];
@@ -532,6 +1240,1118 @@ mod tests {
 
         assert_eq!(true, found_s1);
         assert_eq!(true, found_s2);
-        assert_eq!("</R>", reader.get_unread_slice())
+        assert_eq!("</R>", reader.get_unread_slice().unwrap())
+    }
+
+    #[test]
+    fn test_comments_and_processing_instructions_are_skipped() {
+        let xml_src = r#"<?xml version="1.0"?><!-- <A> --><R><?pi <B>?><![CDATA[<C>]]><D/></R>
+"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let r = reader.read_next_tag().unwrap().unwrap();
+        assert_eq!("R", r.name);
+
+        let d = reader.read_next_tag().unwrap().unwrap();
+        assert_eq!("D", d.name);
+
+        let r = reader.read_next_tag().unwrap().unwrap();
+        assert_eq!("R", r.name);
+        assert!(matches!(r.tag_type, XmlTagType::Close));
+
+        assert!(reader.read_next_tag().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_strict_mode_reads_well_formed_document() {
+        let xml_src = r#"<?xml version="1.0"?><!-- c --><R a="1"><A>V &amp; V</A><B/></R>"#;
+        let mut reader =
+            MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Strict).unwrap();
+
+        let a = reader.find_the_open_node("R/A").unwrap().unwrap();
+        assert_eq!(
+            "V & V",
+            reader
+                .read_the_whole_node(a)
+                .unwrap()
+                .get_value()
+                .unwrap()
+                .unwrap()
+        );
+
+        while reader.read_next_tag().unwrap().is_some() {}
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_malformed_input() {
+        let cases = [
+            (r#"<R a="1" a="2"/>"#, "Duplicate attribute 'a' at pos 9"),
+            ("<R><1A/></R>", "Invalid name '1A' at pos 4"),
+            (
+                "<R/><R2/>",
+                "Document has more than one root element at pos 4",
+            ),
+            (
+                "<R/>text",
+                "Text is not allowed outside the root element at pos 4",
+            ),
+            (
+                "<R><A>",
+                "Document ended while elements are still opened: <R> (pos 0), <A> (pos 3) at pos 6",
+            ),
+        ];
+
+        for (xml_src, expected_err) in cases {
+            let mut reader =
+                MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Strict)
+                    .unwrap();
+
+            let err = loop {
+                match reader.read_next_tag() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("Error is expected for {}", xml_src),
+                    Err(err) => break err,
+                }
+            };
+
+            assert_eq!(expected_err, err);
+        }
+    }
+
+    #[test]
+    fn test_greater_than_in_attribute_value() {
+        let xml_src = r#"<R><A expr="a > b" c='>'>V</A></R>"#;
+
+        for mode in [XmlReaderMode::Default, XmlReaderMode::Strict] {
+            let mut reader = MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), mode).unwrap();
+
+            let a = reader.find_the_open_node("R/A").unwrap().unwrap();
+            assert_eq!(r#"<A expr="a > b" c='>'>"#, a.raw_as_string().unwrap());
+            assert_eq!(
+                Some("a > b".to_string()),
+                reader.get_attribute_value(&a, "expr").unwrap()
+            );
+
+            let a = reader.read_the_whole_node(a).unwrap();
+            assert_eq!("V", a.get_value().unwrap().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_escaped_quote_in_attribute_value() {
+        let xml_src = r#"<r><a c="x\"y" d='>\''>t</a><b/></r>"#;
+
+        for mode in [
+            XmlReaderMode::Default,
+            XmlReaderMode::Strict,
+            XmlReaderMode::Lenient,
+        ] {
+            let mut reader = MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), mode).unwrap();
+
+            reader.read_next_tag().unwrap().unwrap();
+
+            let a = reader.read_next_tag().unwrap().unwrap();
+            assert_eq!(XmlTagType::Open, a.tag_type);
+            assert_eq!(r#"<a c="x\"y" d='>\''>"#, a.raw_as_string().unwrap());
+            assert_eq!(
+                Some(r#"x\"y"#.to_string()),
+                reader.get_attribute_value(&a, "c").unwrap()
+            );
+
+            let a = reader.read_the_whole_node(a).unwrap();
+            assert_eq!("t", a.get_value().unwrap().unwrap());
+
+            let b = reader.read_next_tag().unwrap().unwrap();
+            assert_eq!(XmlTagType::OpenClose, b.tag_type);
+            assert_eq!("b", b.name);
+        }
+    }
+
+    #[test]
+    fn test_close_tag_without_opened_tags() {
+        let cases = [
+            (
+                "<R></R></X>",
+                2,
+                XmlReaderMode::Default,
+                "Attempt to close tag with name X. There are no opened tags",
+            ),
+            (
+                "</X><R/>",
+                0,
+                XmlReaderMode::Default,
+                "Attempt to close tag with name X. There are no opened tags",
+            ),
+            (
+                "<R></R></X>",
+                2,
+                XmlReaderMode::Strict,
+                "Attempt to close tag </X>. There are no opened tags at pos 7",
+            ),
+            (
+                "</X><R/>",
+                0,
+                XmlReaderMode::Strict,
+                "Attempt to close tag </X>. There are no opened tags at pos 0",
+            ),
+        ];
+
+        for (xml_src, tags_amount, mode, expected_err) in cases {
+            let mut reader = MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), mode).unwrap();
+
+            for _ in 0..tags_amount {
+                reader.read_next_tag().unwrap().unwrap();
+            }
+
+            let err = reader.read_next_tag().unwrap_err();
+
+            assert_eq!(expected_err, err);
+        }
+    }
+
+    #[test]
+    fn test_default_mode_keeps_accepting_malformed_input() {
+        let xml_src = "<R><1A/></R><R2/>";
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        while reader.read_next_tag().unwrap().is_some() {}
+
+        assert_eq!(XmlReaderMode::Default, reader.get_mode());
+    }
+
+    #[test]
+    fn test_truncated_document_is_not_complete() {
+        let xml_src = r#"<R><A><B>V</B>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        while reader.read_next_tag().unwrap().is_some() {}
+
+        assert!(!reader.is_document_complete());
+
+        let unclosed: Vec<(&str, usize)> = reader
+            .get_unclosed_nodes()
+            .iter()
+            .map(|node| (node.name, node.start_pos))
+            .collect();
+
+        assert_eq!(vec![("R", 0), ("A", 3)], unclosed);
+    }
+
+    #[test]
+    fn test_document_is_complete() {
+        let xml_src = "<R><A><B>V</B></A></R>\n<!-- c -->\n";
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        reader.find_the_open_node("R/A/B").unwrap().unwrap();
+        assert!(!reader.is_document_complete());
+
+        while reader.read_next_tag().unwrap().is_some() {}
+
+        assert!(reader.is_document_complete());
+    }
+
+    #[test]
+    fn test_document_with_skipped_root_is_complete() {
+        let xml_src = "<R><A><B>V</B></A></R>";
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let root = reader.read_next_tag().unwrap().unwrap();
+        reader.skip_node(&root).unwrap();
+
+        assert!(reader.is_document_complete());
+    }
+
+    #[test]
+    fn test_lenient_mode_recovers_html_like_input() {
+        let xml_src = r#"<Root><P>Line 1<br>Line 2 & more</p><Item>V</ITEM></Extra></Root>"#;
+        let mut reader =
+            MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Lenient).unwrap();
+
+        let root = reader.find_the_open_node("Root").unwrap().unwrap();
+
+        let p = reader
+            .find_the_node_inside_parent(&root, "P")
+            .unwrap()
+            .unwrap();
+        let p = reader.read_the_whole_node(p).unwrap();
+        assert_eq!("Line 1<br>Line 2 & more", p.get_value().unwrap().unwrap());
+
+        let item = reader
+            .find_the_node_inside_parent(&root, "Item")
+            .unwrap()
+            .unwrap();
+        let item = reader.read_the_whole_node(item).unwrap();
+        assert_eq!("V", item.get_value().unwrap().unwrap());
+        assert_eq!("Item", item.close_node.unwrap().name);
+
+        assert!(reader
+            .find_the_node_inside_parent(&root, "Other")
+            .unwrap()
+            .is_none());
+        assert!(reader.is_document_complete());
+
+        let warnings: Vec<XmlReaderWarningKind> = reader
+            .get_warnings()
+            .iter()
+            .map(|w| w.kind.clone())
+            .collect();
+
+        assert_eq!(
+            vec![
+                XmlReaderWarningKind::BareAmpersand,
+                XmlReaderWarningKind::ImplicitlyClosedElement("br".to_string()),
+                XmlReaderWarningKind::CaseInsensitiveCloseTag {
+                    opened: "P".to_string(),
+                    found: "p".to_string()
+                },
+                XmlReaderWarningKind::CaseInsensitiveCloseTag {
+                    opened: "Item".to_string(),
+                    found: "ITEM".to_string()
+                },
+                XmlReaderWarningKind::StrayCloseTag("Extra".to_string()),
+            ],
+            warnings
+        );
+    }
+
+    #[test]
+    fn test_lenient_mode_skips_close_tag_without_opened_tags() {
+        let xml_src = "<R></R></X>";
+        let mut reader =
+            MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Lenient).unwrap();
+
+        let mut tags = vec![];
+
+        while let Some(tag) = reader.read_next_tag().unwrap() {
+            tags.push((tag.name, tag.level));
+        }
+
+        assert_eq!(vec![("R", 0), ("R", 0)], tags);
+        assert_eq!(
+            "Close tag </X> has no opened tag and was ignored at pos 7",
+            reader.get_warnings()[0].to_string()
+        );
+
+        let xml_src = "</X><R/>";
+        let mut reader =
+            MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Lenient).unwrap();
+
+        let r = reader.read_next_tag().unwrap().unwrap();
+        assert_eq!(("R", 0), (r.name, r.level));
+        assert!(reader.read_next_tag().unwrap().is_none());
+        assert_eq!(
+            vec![XmlReaderWarningKind::StrayCloseTag("X".to_string())],
+            reader
+                .get_warnings()
+                .iter()
+                .map(|w| w.kind.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_lenient_mode_closes_unclosed_element_at_ancestor_close_tag() {
+        // Nothing tells that <br> is empty, so the following elements are its
+        // children until </P> closes it
+        let xml_src = "<P>A<br>B<b>C</b></P><Q/>";
+        let mut reader =
+            MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Lenient).unwrap();
+
+        let mut tags = vec![];
+
+        while let Some(tag) = reader.read_next_tag().unwrap() {
+            tags.push((tag.name, tag.level));
+        }
+
+        assert_eq!(
+            vec![("P", 0), ("br", 1), ("b", 2), ("b", 2), ("P", 0), ("Q", 0)],
+            tags
+        );
+        assert_eq!(
+            vec![XmlReaderWarningKind::ImplicitlyClosedElement(
+                "br".to_string()
+            )],
+            reader
+                .get_warnings()
+                .iter()
+                .map(|w| w.kind.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_lenient_mode_closes_elements_at_the_end() {
+        let xml_src = r#"<Root><A>V"#;
+        let mut reader =
+            MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Lenient).unwrap();
+
+        while reader.read_next_tag().unwrap().is_some() {}
+
+        assert_eq!(2, reader.get_warnings().len());
+        assert_eq!(
+            "Element <A> was closed implicitly at pos 10",
+            reader.get_warnings()[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_limits_are_reported_with_distinct_errors() {
+        let cases = [
+            (
+                "<R><A><B><C/></B></A></R>",
+                ReaderOptions {
+                    max_depth: Some(3),
+                    ..Default::default()
+                },
+                XmlLimit::Depth,
+            ),
+            (
+                r#"<R><A a="1" b="2" c="3"/></R>"#,
+                ReaderOptions {
+                    max_attributes_per_tag: Some(2),
+                    ..Default::default()
+                },
+                XmlLimit::AttributesPerTag,
+            ),
+            (
+                "<R><VeryLongName/></R>",
+                ReaderOptions {
+                    max_name_length: Some(8),
+                    ..Default::default()
+                },
+                XmlLimit::NameLength,
+            ),
+            (
+                r#"<R><A VeryLongAttributeName="1"/></R>"#,
+                ReaderOptions {
+                    max_name_length: Some(8),
+                    ..Default::default()
+                },
+                XmlLimit::NameLength,
+            ),
+            (
+                "<R><A>Very long text</A></R>",
+                ReaderOptions {
+                    max_text_size: Some(8),
+                    ..Default::default()
+                },
+                XmlLimit::TextSize,
+            ),
+        ];
+
+        for (xml_src, options, expected_limit) in cases {
+            let mut reader =
+                MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+            let err = loop {
+                match reader.read_next_tag() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("Limit error is expected for {}", xml_src),
+                    Err(err) => break err,
+                }
+            };
+
+            let exceeded_limit = reader.get_exceeded_limit().unwrap();
+            assert_eq!(expected_limit, exceeded_limit.limit);
+            assert_eq!(exceeded_limit.to_string(), err);
+        }
+    }
+
+    #[test]
+    fn test_document_size_limit() {
+        let options = ReaderOptions {
+            max_document_size: Some(8),
+            ..Default::default()
+        };
+
+        let err = MyXmlReader::from_slice_with_options("<R>Text</R>".as_bytes(), options)
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            "Limit of document size is exceeded: 11 while max is 8 at pos 0",
+            err
+        );
+    }
+
+    #[test]
+    fn test_limits_are_not_exceeded() {
+        let options = ReaderOptions {
+            max_depth: Some(3),
+            max_attributes_per_tag: Some(2),
+            max_name_length: Some(4),
+            max_text_size: Some(4),
+            max_document_size: Some(64),
+            ..Default::default()
+        };
+
+        let xml_src = r#"<R><A a="1" bb="2"><B>Text</B></A></R>"#;
+        let mut reader = MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+        while reader.read_next_tag().unwrap().is_some() {}
+
+        assert!(reader.get_exceeded_limit().is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let xml_src = r#"<R><A><S1>V1</S1><S2>V2</S2></A><B/></R>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let parent = reader.find_the_open_node("R/A").unwrap().unwrap();
+
+        let checkpoint = reader.save_checkpoint();
+
+        let s2 = reader
+            .find_the_node_inside_parent(&parent, "S2")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            "V2",
+            reader
+                .read_the_whole_node(s2)
+                .unwrap()
+                .get_value()
+                .unwrap()
+                .unwrap()
+        );
+
+        let s3 = reader.find_the_node_inside_parent(&parent, "S3").unwrap();
+        assert!(s3.is_none());
+        assert_eq!(1, reader.get_level());
+
+        reader.restore_checkpoint(&checkpoint);
+        assert_eq!(2, reader.get_level());
+
+        let s1 = reader
+            .find_the_node_inside_parent(&parent, "S1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            "V1",
+            reader
+                .read_the_whole_node(s1)
+                .unwrap()
+                .get_value()
+                .unwrap()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_clone_at_tag() {
+        let xml_src = r#"<R><A><S1>V1</S1><S2>V2</S2></A><B/></R>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let parent = reader.find_the_open_node("R/A").unwrap().unwrap();
+
+        let s1 = reader
+            .find_the_node_inside_parent(&parent, "S1")
+            .unwrap()
+            .unwrap();
+
+        let mut cloned = reader.clone_at(&s1).unwrap();
+        assert_eq!(2, cloned.get_level());
+
+        reader.skip_to_parent_end(&parent).unwrap();
+        assert_eq!("<B/></R>", reader.get_unread_slice().unwrap());
+
+        let s1 = cloned.read_next_tag().unwrap().unwrap();
+        assert_eq!("S1", s1.name);
+        assert_eq!(3, cloned.get_level());
+
+        let s2 = cloned
+            .find_the_node_inside_parent(&parent, "S2")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            "V2",
+            cloned
+                .read_the_whole_node(s2)
+                .unwrap()
+                .get_value()
+                .unwrap()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_clone_at_tag_of_closed_parent() {
+        let xml_src = r#"<R><A><S1>V1</S1></A><B><S2/></B></R>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let s1 = reader.find_the_open_node("R/A/S1").unwrap().unwrap();
+        reader.find_the_open_node("B").unwrap().unwrap();
+
+        assert!(reader.clone_at(&s1).is_err());
+    }
+
+    #[test]
+    fn test_skip_node() {
+        let xml_src = r#"<R><Big><A><B/><C>V</C></A><A/></Big><S2>V2</S2></R>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let big_node = reader.find_the_open_node("R/Big").unwrap().unwrap();
+
+        let span = reader.skip_node(&big_node).unwrap();
+
+        assert_eq!("<Big><A><B/><C>V</C></A><A/></Big>", &xml_src[span]);
+        assert_eq!(1, reader.get_level());
+        assert_eq!("<S2>V2</S2></R>", reader.get_unread_slice().unwrap());
+
+        let s2 = reader.read_next_tag().unwrap().unwrap();
+        let whole_node = reader.read_the_whole_node(s2).unwrap();
+        assert_eq!("V2", whole_node.get_value().unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_skip_open_close_node() {
+        let xml_src = r#"<R><Empty/><S2>V2</S2></R>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let empty = reader.find_the_open_node("R/Empty").unwrap().unwrap();
+
+        let span = reader.skip_node(&empty).unwrap();
+
+        assert_eq!("<Empty/>", &xml_src[span]);
+        assert_eq!("<S2>V2</S2></R>", reader.get_unread_slice().unwrap());
+    }
+
+    #[test]
+    fn test_skip_node_which_is_not_the_last_opened() {
+        let xml_src = r#"<R><A><B>V</B></A></R>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let a = reader.find_the_open_node("R/A").unwrap().unwrap();
+        reader.read_next_tag().unwrap();
+
+        assert!(reader.skip_node(&a).is_err());
+    }
+
+    #[test]
+    fn test_skip_node_classifies_tags_as_read_next_tag() {
+        let xml_src = "<R><A><B\n/><C ></C\n></A><D/></R>";
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let a = reader.find_the_open_node("R/A").unwrap().unwrap();
+        let span = reader.skip_node(&a).unwrap();
+
+        assert_eq!("<A><B\n/><C ></C\n></A>", &xml_src[span]);
+
+        for xml_src in ["<R><A><B/ ></A></R>", "<R><A>< B></B></A></R>"] {
+            let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+            let a = reader.find_the_open_node("R/A").unwrap().unwrap();
+            let mut read_ahead = reader.clone();
+
+            let expected = loop {
+                match read_ahead.read_next_tag() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("Error is expected for {}", xml_src),
+                    Err(err) => break err,
+                }
+            };
+
+            assert_eq!(expected, reader.skip_node(&a).unwrap_err(), "{}", xml_src);
+        }
+    }
+
+    #[test]
+    fn test_skip_to_parent_end() {
+        let xml_src = r#"<R><A><S1>V1</S1><S2><I/><I>V</I></S2><S3/></A><B/></R>"#;
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let parent = reader.find_the_open_node("R/A").unwrap().unwrap();
+
+        let s2 = reader
+            .find_the_node_inside_parent(&parent, "S2")
+            .unwrap()
+            .unwrap();
+        assert_eq!("S2", s2.name);
+        reader.read_next_tag().unwrap();
+
+        let span = reader.skip_to_parent_end(&parent).unwrap();
+
+        assert_eq!(
+            "<A><S1>V1</S1><S2><I/><I>V</I></S2><S3/></A>",
+            &xml_src[span]
+        );
+        assert_eq!(1, reader.get_level());
+        assert_eq!("<B/></R>", reader.get_unread_slice().unwrap());
+    }
+
+    #[test]
+    fn test_expand_internal_entities_in_value_and_attribute() {
+        let xml_src = r#"<?xml version="1.0"?>
+<!DOCTYPE Feed [
+    <!ENTITY company "Acme &amp; Sons">
+    <!ENTITY signature "&company; &#169;">
+]>
+<Feed title="&company;"><Item>&signature;</Item></Feed>"#;
+
+        let mut xml = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        assert_eq!(2, xml.get_entities().len());
+
+        let feed = xml.read_next_tag().unwrap().unwrap();
+
+        assert_eq!(
+            Some("Acme & Sons".to_string()),
+            xml.get_attribute_value(&feed, "title").unwrap()
+        );
+        assert_eq!(None, xml.get_attribute_value(&feed, "id").unwrap());
+
+        let item = xml.read_next_tag().unwrap().unwrap();
+        let item = xml.read_the_whole_node(item).unwrap();
+
+        assert_eq!(
+            Some("Acme & Sons ©".to_string()),
+            xml.get_node_value(&item).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dtd_attribute_defaults() {
+        let xml_src = r#"<!DOCTYPE Feed [
+    <!ENTITY company "Acme">
+    <!ATTLIST Item
+        kind (news|sale) "news"
+        source CDATA #FIXED "&company;"
+        id ID #IMPLIED>
+]>
+<Feed><Item id="a1" kind="sale"/><Item/></Feed>"#;
+
+        let mut xml = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+        let dtd = XmlDtd::from_document(xml_src.as_bytes(), None).unwrap();
+        xml.set_dtd(dtd.unwrap());
+
+        let feed = xml.read_next_tag().unwrap().unwrap();
+        assert_eq!(None, xml.get_attribute_value(&feed, "kind").unwrap());
+
+        let item = xml.read_next_tag().unwrap().unwrap();
+
+        assert_eq!(
+            Some("sale".to_string()),
+            xml.get_attribute_value(&item, "kind").unwrap()
+        );
+        assert_eq!(
+            vec![
+                ("id".to_string(), "a1".to_string()),
+                ("kind".to_string(), "sale".to_string()),
+                ("source".to_string(), "Acme".to_string()),
+            ],
+            xml.get_attributes(&item).unwrap()
+        );
+
+        let item = xml.read_next_tag().unwrap().unwrap();
+        let clone = xml.clone_at(&item).unwrap();
+
+        assert_eq!(
+            Some("news".to_string()),
+            clone.get_attribute_value(&item, "kind").unwrap()
+        );
+        assert_eq!(
+            Some("Acme".to_string()),
+            xml.get_attribute_value(&item, "source").unwrap()
+        );
+        assert_eq!(None, xml.get_attribute_value(&item, "id").unwrap());
+    }
+
+    #[test]
+    fn test_entity_expansion_limits() {
+        let xml_src = r#"<!DOCTYPE R [
+    <!ENTITY a "0123456789">
+    <!ENTITY b "&a;&a;&a;&a;">
+]>
+<R>&b;</R>"#;
+
+        let options = ReaderOptions {
+            max_entity_expansion_size: 32,
+            ..Default::default()
+        };
+
+        let mut xml = MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+        let root = xml.read_next_tag().unwrap().unwrap();
+        let root = xml.read_the_whole_node(root).unwrap();
+
+        let err = xml.get_node_value(&root).unwrap_err();
+
+        assert!(err.starts_with("Limit of entity expansion size is exceeded"));
+        assert_eq!(
+            Some("0123456789".to_string()),
+            xml.expand_entities("&a;").ok()
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_an_error_not_a_panic() {
+        let xml_src = b"<R><N\xFF>V</N\xFF><V>\xC3\x28</V></R>";
+
+        let mut reader = MyXmlReader::from_slice(xml_src).unwrap();
+        reader.read_next_tag().unwrap().unwrap();
+
+        let err = reader.read_next_tag().unwrap_err();
+        assert!(err.starts_with("Name of the tag at pos 3 is not valid UTF-8"));
+
+        let mut reader = MyXmlReader::from_slice(&xml_src[12..]).unwrap();
+        let v = reader.read_next_tag().unwrap().unwrap();
+        let v = reader.read_the_whole_node(v).unwrap();
+
+        assert!(v.get_value().is_err());
+        assert!(v.get_xml().is_err());
+        assert!(reader.get_node_value(&v).is_err());
+        assert_eq!(Some("\u{FFFD}(".to_string()), v.get_value_lossy());
+        assert_eq!("<V>\u{FFFD}(</V>", v.get_xml_lossy());
+    }
+
+    #[test]
+    fn test_validate_utf8_at_from_slice() {
+        let options = ReaderOptions {
+            validate_utf8: true,
+            ..Default::default()
+        };
+
+        let err = MyXmlReader::from_slice_with_options(b"<R>\xC3\x28</R>", options.clone())
+            .err()
+            .unwrap();
+        assert_eq!(
+            "Document is not valid UTF-8: invalid utf-8 sequence of 1 bytes from index 3",
+            err
+        );
+
+        let xml_src = "<Корень><Имя>Иван</Имя></Корень>";
+
+        let mut reader = MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+        let name = reader.find_the_open_node("Корень/Имя").unwrap().unwrap();
+        let name = reader.read_the_whole_node(name).unwrap();
+
+        assert_eq!(
+            Some("Иван".to_string()),
+            reader.get_node_value(&name).unwrap()
+        );
+        assert_eq!("</Корень>", reader.get_unread_slice().unwrap());
+    }
+
+    #[test]
+    fn test_xml_declaration_is_exposed() {
+        let xml_src = r#"<?xml version="1.1" encoding="UTF-8" standalone="no"?><R/>"#;
+
+        let reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let declaration = reader.get_declaration().unwrap();
+        assert_eq!("1.1", declaration.version);
+        assert_eq!(Some("UTF-8"), declaration.encoding.as_deref());
+        assert_eq!(Some(false), declaration.standalone);
+
+        let reader = MyXmlReader::from_slice("<R/>".as_bytes()).unwrap();
+        assert!(reader.get_declaration().is_none());
+    }
+
+    #[test]
+    fn test_xml_declaration_must_be_at_start() {
+        let xml_src = "\n<?xml version=\"1.0\"?><R/>";
+
+        let err = MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Strict)
+            .err()
+            .unwrap();
+        assert_eq!(
+            "XML declaration is allowed only at the start of the document at pos 1",
+            err
+        );
+
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+        assert!(reader.get_declaration().is_none());
+        assert_eq!(
+            "XML declaration is allowed only at the start of the document at pos 1",
+            reader.get_declaration_error().unwrap().to_string()
+        );
+        assert_eq!("R", reader.read_next_tag().unwrap().unwrap().name);
+
+        let mut reader =
+            MyXmlReader::from_slice("<R><?xml version=\"1.0\"?><A/></R>".as_bytes()).unwrap();
+        reader.read_next_tag().unwrap();
+
+        assert_eq!(
+            "XML declaration is allowed only at the start of the document. Pos is 3",
+            reader.read_next_tag().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_xml_declaration() {
+        let xml_src = "<?xml version=\"1.0\" encoding=\"UTF-8\" version=\"1.1\"?><R/>";
+
+        let err = MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Strict)
+            .err()
+            .unwrap();
+        assert_eq!(
+            "Invalid XML declaration: attribute 'version' is duplicated or out of order at pos 0",
+            err
+        );
+
+        let errors = crate::my_xml_reader::validate(xml_src.as_bytes()).unwrap_err();
+        assert!(matches!(
+            errors[0].kind,
+            crate::my_xml_reader::XmlValidationErrorKind::InvalidXmlDeclaration(_)
+        ));
+
+        let mut reader =
+            MyXmlReader::from_slice_with_mode(xml_src.as_bytes(), XmlReaderMode::Lenient).unwrap();
+
+        assert!(reader.get_declaration().is_none());
+        assert!(reader.get_declaration_error().is_some());
+        assert_eq!("R", reader.read_next_tag().unwrap().unwrap().name);
+        assert_eq!(
+            XmlReaderWarningKind::InvalidXmlDeclaration(
+                "Invalid XML declaration: attribute 'version' is duplicated or out of order"
+                    .to_string()
+            ),
+            reader.get_warnings()[0].kind
+        );
+    }
+
+    #[test]
+    fn test_default_mode_reads_documents_with_invalid_declaration() {
+        let cases = [
+            (
+                "<?xml version=\"1.0\" standalone=\"true\"?><R/>",
+                "Invalid XML declaration: standalone must be 'yes' or 'no' but it is 'true' at pos 0",
+            ),
+            (
+                "<?xml encoding=\"UTF-8\"?><R/>",
+                "Invalid XML declaration: version is missing at pos 0",
+            ),
+            (
+                "<?xml version=\"1.0\" encoding=\"UTF-8\" version=\"1.1\"?><R/>",
+                "Invalid XML declaration: attribute 'version' is duplicated or out of order at pos 0",
+            ),
+        ];
+
+        for (xml_src, expected_err) in cases {
+            let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+            assert!(reader.get_declaration().is_none());
+            assert_eq!(
+                expected_err,
+                reader.get_declaration_error().unwrap().to_string()
+            );
+            assert!(reader.get_warnings().is_empty());
+            assert_eq!("R", reader.read_next_tag().unwrap().unwrap().name);
+        }
+    }
+
+    #[test]
+    fn test_read_tags_with_legal_whitespace() {
+        let xml_src = "<Root\n  id=\"1\"\n><A\r\n/><B >V</B\n></Root >";
+
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let mut tags = Vec::new();
+
+        while let Some(tag) = reader.read_next_tag().unwrap() {
+            tags.push(format!("{:?} {} {}", tag.tag_type, tag.name, tag.level));
+        }
+
+        assert_eq!(
+            vec![
+                "Open Root 0",
+                "OpenClose A 1",
+                "Open B 1",
+                "Close B 1",
+                "Close Root 0"
+            ],
+            tags
+        );
+        assert!(reader.is_document_complete());
+    }
+
+    #[test]
+    fn test_read_tags_with_illegal_whitespace() {
+        let test_cases = [
+            (
+                "<Root>< A/></Root>",
+                "Tag name is expected right after '<'. Pos is 6",
+            ),
+            (
+                "<Root><A></ A></Root>",
+                "Tag name is expected right after '</'. Pos is 9",
+            ),
+            (
+                "<Root><A/ ></Root>",
+                "'/' must go right before '>' in tag <A>. Pos is 8",
+            ),
+        ];
+
+        for (xml_src, expected) in test_cases {
+            let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+            let err = loop {
+                match reader.read_next_tag() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("Error is expected for {}", xml_src),
+                    Err(err) => break err,
+                }
+            };
+
+            assert_eq!(expected, err, "{}", xml_src);
+        }
+    }
+
+    #[test]
+    fn test_value_whitespace_honors_xml_space() {
+        let xml_src = r#"<Doc>
+    <Title>
+        Annual   report
+    </Title>
+    <Code xml:space="preserve">
+  fn main() {}
+</Code>
+    <Pre xml:space="preserve"><Line>  a  b  </Line><Text xml:space="default">  c  d  </Text></Pre>
+</Doc>"#;
+
+        let options = ReaderOptions {
+            value_whitespace: XmlWhitespaceHandling::Collapse,
+            ..Default::default()
+        };
+
+        let mut reader = MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+        let mut values = Vec::new();
+
+        while let Some(tag) = reader.read_next_tag().unwrap() {
+            if let "Title" | "Code" | "Line" | "Text" = tag.name {
+                let node = reader.read_the_whole_node(tag).unwrap();
+                values.push(reader.get_node_value(&node).unwrap().unwrap());
+            }
+        }
+
+        assert_eq!(
+            vec!["Annual report", "\n  fn main() {}\n", "  a  b  ", "c d"],
+            values
+        );
+    }
+
+    #[test]
+    fn test_invalid_xml_space_is_inherited() {
+        let xml_src = "<R xml:space=\"preserve\"><A xml:space=\"keep\"> a  b </A><B xml:space=default> c  d </B></R>";
+
+        for mode in [XmlReaderMode::Default, XmlReaderMode::Lenient] {
+            let options = ReaderOptions {
+                mode,
+                value_whitespace: XmlWhitespaceHandling::Collapse,
+                ..Default::default()
+            };
+
+            let mut reader =
+                MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+            let mut values = Vec::new();
+
+            while let Some(tag) = reader.read_next_tag().unwrap() {
+                if let "A" | "B" = tag.name {
+                    let node = reader.read_the_whole_node(tag).unwrap();
+                    values.push(reader.get_node_value(&node).unwrap().unwrap());
+                }
+            }
+
+            assert_eq!(vec![" a  b ", " c  d "], values, "{:?}", mode);
+
+            let warnings: Vec<String> = reader
+                .get_warnings()
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect();
+
+            if mode == XmlReaderMode::Lenient {
+                assert_eq!(2, warnings.len(), "{:?}", warnings);
+                assert!(
+                    warnings[0].starts_with("xml:space is ignored"),
+                    "{:?}",
+                    warnings
+                );
+            } else {
+                assert!(warnings.is_empty(), "{:?}", warnings);
+            }
+        }
+
+        let options = ReaderOptions {
+            mode: XmlReaderMode::Strict,
+            value_whitespace: XmlWhitespaceHandling::Collapse,
+            ..Default::default()
+        };
+
+        let xml_src = "<R><A xml:space=\"keep\">a</A></R>";
+        let mut reader = MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+        reader.read_next_tag().unwrap();
+
+        assert_eq!(
+            "xml:space must be 'preserve' or 'default' but it is 'keep'",
+            reader.read_next_tag().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_xml_space_is_not_read_without_value_whitespace() {
+        let xml_src = "<R xml:space=\"keep\"><A xml:space=preserve><B> b </B></A></R>";
+
+        for mode in [XmlReaderMode::Default, XmlReaderMode::Lenient] {
+            let options = ReaderOptions {
+                mode,
+                ..Default::default()
+            };
+
+            let mut reader =
+                MyXmlReader::from_slice_with_options(xml_src.as_bytes(), options).unwrap();
+
+            let mut preserved = Vec::new();
+
+            while let Some(tag) = reader.read_next_tag().unwrap() {
+                assert!(!reader.opened_nodes.iter().any(|node| node.preserve_space));
+
+                if tag.tag_type == XmlTagType::Open {
+                    preserved.push(reader.is_space_preserved(&tag).unwrap());
+                }
+            }
+
+            // Ancestors are read on demand, so B inherits from A
+            assert_eq!(vec![false, false, false], preserved, "{:?}", mode);
+            assert!(reader.get_warnings().is_empty(), "{:?}", mode);
+        }
+
+        let xml_src = "<R xml:space=\"preserve\"><A><B> b </B></A></R>";
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let b = reader.find_the_open_node("R/A/B").unwrap().unwrap();
+
+        assert!(reader.is_space_preserved(&b).unwrap());
+    }
+
+    #[test]
+    fn test_node_value_normalized_without_reader_options() {
+        let xml_src = "<R><A>\n  x &amp;\n  y\n</A></R>";
+
+        let mut reader = MyXmlReader::from_slice(xml_src.as_bytes()).unwrap();
+
+        let a = reader.find_the_open_node("R/A").unwrap().unwrap();
+        let a = reader.read_the_whole_node(a).unwrap();
+
+        assert_eq!(
+            "\n  x &\n  y\n",
+            reader.get_node_value(&a).unwrap().unwrap()
+        );
+        assert_eq!(
+            "x &\n  y",
+            a.get_value_normalized(XmlWhitespaceHandling::Trim)
+                .unwrap()
+                .unwrap()
+        );
+        assert_eq!(
+            "x & y",
+            a.get_value_normalized(XmlWhitespaceHandling::Collapse)
+                .unwrap()
+                .unwrap()
+        );
     }
 }
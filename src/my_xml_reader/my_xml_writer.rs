@@ -0,0 +1,462 @@
+use std::io::Write;
+
+use super::{is_valid_name, XmlDeclaration};
+
+struct OpenedElement {
+    name: String,
+    has_child_elements: bool,
+    // Indentation is not written inside elements with text
+    has_text: bool,
+}
+
+pub struct MyXmlWriter<W: Write> {
+    out: W,
+    indent: Option<String>,
+    opened_elements: Vec<OpenedElement>,
+    start_tag_is_open: bool,
+    has_root: bool,
+    has_prolog: bool,
+}
+
+impl<W: Write> MyXmlWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            indent: None,
+            opened_elements: vec![],
+            start_tag_is_open: false,
+            has_root: false,
+            has_prolog: false,
+        }
+    }
+
+    pub fn with_indent(out: W, indent: &str) -> Self {
+        let mut result = Self::new(out);
+        result.indent = Some(indent.to_string());
+        result
+    }
+
+    pub fn get_level(&self) -> usize {
+        self.opened_elements.len()
+    }
+
+    pub fn write_declaration(&mut self, declaration: &XmlDeclaration) -> Result<(), String> {
+        if self.has_prolog || self.has_root {
+            return Err("XML declaration must be written first".to_string());
+        }
+
+        self.write(b"<?xml version=\"")?;
+        self.write(declaration.version.as_bytes())?;
+        self.write(b"\"")?;
+
+        if let Some(encoding) = &declaration.encoding {
+            self.write(b" encoding=\"")?;
+            self.write(encoding.as_bytes())?;
+            self.write(b"\"")?;
+        }
+
+        if let Some(standalone) = declaration.standalone {
+            self.write(if standalone {
+                b" standalone=\"yes\""
+            } else {
+                b" standalone=\"no\""
+            })?;
+        }
+
+        self.write(b"?>")?;
+        self.has_prolog = true;
+        Ok(())
+    }
+
+    pub fn start_element(&mut self, name: &str) -> Result<(), String> {
+        if !is_valid_name(name) {
+            return Err(format!("Can not write element <{}>. Name is invalid", name));
+        }
+
+        if self.opened_elements.is_empty() && self.has_root {
+            return Err(format!(
+                "Can not write <{}>. Root element is already written",
+                name
+            ));
+        }
+
+        self.close_start_tag()?;
+
+        if let Some(parent) = self.opened_elements.last_mut() {
+            parent.has_child_elements = true;
+        }
+
+        self.write_indent()?;
+        self.write(b"<")?;
+        self.write(name.as_bytes())?;
+
+        self.opened_elements.push(OpenedElement {
+            name: name.to_string(),
+            has_child_elements: false,
+            has_text: false,
+        });
+
+        self.start_tag_is_open = true;
+        self.has_root = true;
+        Ok(())
+    }
+
+    pub fn write_attribute(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if !self.start_tag_is_open {
+            return Err(format!(
+                "Can not write attribute '{}'. Start tag is already closed",
+                name
+            ));
+        }
+
+        check_attribute_name(name)?;
+
+        self.write(b" ")?;
+        self.write(name.as_bytes())?;
+        self.write(b"=\"")?;
+        self.write(encode_attribute_value(value).as_bytes())?;
+        self.write(b"\"")
+    }
+
+    // Writes a value copied from another document as is. A raw value can not
+    // contain the quote it was written with, so the other one is used if needed,
+    // and the quote is escaped if the value contains both.
+    pub fn write_raw_attribute(&mut self, name: &str, raw_value: &str) -> Result<(), String> {
+        if !self.start_tag_is_open {
+            return Err(format!(
+                "Can not write attribute '{}'. Start tag is already closed",
+                name
+            ));
+        }
+
+        check_attribute_name(name)?;
+
+        let (quote, raw_value) = if raw_value.contains('"') {
+            ("'", raw_value.replace('\'', "&apos;"))
+        } else {
+            ("\"", raw_value.to_string())
+        };
+
+        self.write(b" ")?;
+        self.write(name.as_bytes())?;
+        self.write(b"=")?;
+        self.write(quote.as_bytes())?;
+        self.write(raw_value.as_bytes())?;
+        self.write(quote.as_bytes())
+    }
+
+    pub fn write_text(&mut self, text: &str) -> Result<(), String> {
+        self.write_raw_text(&encode_text(text))
+    }
+
+    // Writes already escaped content such as text, comments or CDATA sections
+    // copied from another document.
+    pub fn write_raw_text(&mut self, text: &str) -> Result<(), String> {
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let Some(element) = self.opened_elements.last_mut() else {
+            return Err("Text can be written only inside an element".to_string());
+        };
+
+        element.has_text = true;
+
+        self.close_start_tag()?;
+        self.write(text.as_bytes())
+    }
+
+    pub fn write_cdata(&mut self, text: &str) -> Result<(), String> {
+        self.write_raw_text(&format!(
+            "<![CDATA[{}]]>",
+            text.replace("]]>", "]]]]><![CDATA[>")
+        ))
+    }
+
+    // Comments and processing instructions may go before or after the root.
+    // Inside an element they are written as child nodes.
+    pub fn write_raw_markup(&mut self, markup: &str) -> Result<(), String> {
+        self.close_start_tag()?;
+
+        if let Some(parent) = self.opened_elements.last_mut() {
+            parent.has_child_elements = true;
+        }
+
+        self.write_indent()?;
+        self.write(markup.as_bytes())?;
+
+        if self.opened_elements.is_empty() {
+            self.has_prolog = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn end_element(&mut self) -> Result<(), String> {
+        let Some(element) = self.opened_elements.pop() else {
+            return Err("There is no opened element to end".to_string());
+        };
+
+        if self.start_tag_is_open {
+            self.start_tag_is_open = false;
+            return self.write(b"/>");
+        }
+
+        if element.has_child_elements && !element.has_text {
+            self.write_indent()?;
+        }
+
+        self.write(b"</")?;
+        self.write(element.name.as_bytes())?;
+        self.write(b">")
+    }
+
+    pub fn finish(mut self) -> Result<W, String> {
+        if let Some(element) = self.opened_elements.last() {
+            return Err(format!("Element <{}> is not ended", element.name));
+        }
+
+        if !self.has_root {
+            return Err("Root element is not written".to_string());
+        }
+
+        if self.indent.is_some() && (self.has_root || self.has_prolog) {
+            self.write(b"\n")?;
+        }
+
+        self.out.flush().map_err(|err| err.to_string())?;
+        Ok(self.out)
+    }
+
+    fn close_start_tag(&mut self) -> Result<(), String> {
+        if !self.start_tag_is_open {
+            return Ok(());
+        }
+
+        self.start_tag_is_open = false;
+        self.write(b">")
+    }
+
+    fn write_indent(&mut self) -> Result<(), String> {
+        let Some(indent) = self.indent.as_ref() else {
+            return Ok(());
+        };
+
+        if self.opened_elements.iter().any(|element| element.has_text) {
+            return Ok(());
+        }
+
+        if !self.has_root && !self.has_prolog {
+            return Ok(());
+        }
+
+        let mut line = String::with_capacity(1 + indent.len() * self.opened_elements.len());
+        line.push('\n');
+
+        for _ in 0..self.opened_elements.len() {
+            line.push_str(indent);
+        }
+
+        self.write(line.as_bytes())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.out.write_all(data).map_err(|err| err.to_string())
+    }
+}
+
+fn check_attribute_name(name: &str) -> Result<(), String> {
+    if is_valid_name(name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Can not write attribute '{}'. Name is invalid",
+            name
+        ))
+    }
+}
+
+pub fn encode_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+// Whitespace other than space is escaped so it survives attribute value
+// normalization when the document is read back.
+pub fn encode_attribute_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '"' => result.push_str("&quot;"),
+            '\t' => result.push_str("&#x9;"),
+            '\n' => result.push_str("&#xA;"),
+            '\r' => result.push_str("&#xD;"),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_sample(writer: &mut MyXmlWriter<Vec<u8>>) {
+        writer
+            .write_declaration(&XmlDeclaration {
+                version: "1.0".to_string(),
+                encoding: Some("UTF-8".to_string()),
+                standalone: None,
+                start_pos: 0,
+                end_pos: 0,
+            })
+            .unwrap();
+        writer.write_raw_markup("<!-- Export -->").unwrap();
+        writer.start_element("Feed").unwrap();
+        writer.write_attribute("title", "A & \"B\"\n").unwrap();
+        writer.write_raw_markup("<!-- Items -->").unwrap();
+        writer.start_element("Item").unwrap();
+        writer.write_text("1 < 2").unwrap();
+        writer.end_element().unwrap();
+        writer.start_element("Empty").unwrap();
+        writer.end_element().unwrap();
+        writer.start_element("Mixed").unwrap();
+        writer.write_text("Hello ").unwrap();
+        writer.start_element("b").unwrap();
+        writer.write_cdata("x]]>y").unwrap();
+        writer.end_element().unwrap();
+        writer.end_element().unwrap();
+        writer.end_element().unwrap();
+    }
+
+    #[test]
+    fn test_write_compact() {
+        let mut writer = MyXmlWriter::new(Vec::new());
+        write_sample(&mut writer);
+
+        let result = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><!-- Export --><Feed title="A &amp; &quot;B&quot;&#xA;"><!-- Items --><Item>1 &lt; 2</Item><Empty/><Mixed>Hello <b><![CDATA[x]]]]><![CDATA[>y]]></b></Mixed></Feed>"#,
+            result
+        );
+    }
+
+    #[test]
+    fn test_write_indented() {
+        let mut writer = MyXmlWriter::with_indent(Vec::new(), "  ");
+        write_sample(&mut writer);
+
+        let result = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Export -->
+<Feed title="A &amp; &quot;B&quot;&#xA;">
+  <!-- Items -->
+  <Item>1 &lt; 2</Item>
+  <Empty/>
+  <Mixed>Hello <b><![CDATA[x]]]]><![CDATA[>y]]></b></Mixed>
+</Feed>
+"#,
+            result
+        );
+    }
+
+    #[test]
+    fn test_writer_errors() {
+        let mut writer = MyXmlWriter::new(Vec::new());
+
+        assert!(writer.write_text("text").is_err());
+        assert!(writer.end_element().is_err());
+
+        writer.start_element("R").unwrap();
+        writer.write_text("text").unwrap();
+        assert!(writer.write_attribute("a", "1").is_err());
+        writer.end_element().unwrap();
+
+        assert!(writer.start_element("Second").is_err());
+
+        let mut writer = MyXmlWriter::new(Vec::new());
+        writer.start_element("R").unwrap();
+
+        assert_eq!("Element <R> is not ended", writer.finish().err().unwrap());
+    }
+
+    #[test]
+    fn test_invalid_names_are_errors() {
+        let mut writer = MyXmlWriter::new(Vec::new());
+
+        for name in ["", "1A", "a b", "d>e"] {
+            assert_eq!(
+                format!("Can not write element <{}>. Name is invalid", name),
+                writer.start_element(name).unwrap_err()
+            );
+        }
+
+        writer.start_element("R").unwrap();
+
+        for name in ["", "1c", "a=b"] {
+            assert_eq!(
+                format!("Can not write attribute '{}'. Name is invalid", name),
+                writer.write_attribute(name, "1").unwrap_err()
+            );
+            assert!(writer.write_raw_attribute(name, "1").is_err());
+        }
+
+        writer.write_attribute("xml:lang", "en").unwrap();
+        writer.end_element().unwrap();
+
+        assert_eq!(
+            "<R xml:lang=\"en\"/>",
+            String::from_utf8(writer.finish().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_raw_attribute_quotes() {
+        let mut writer = MyXmlWriter::new(Vec::new());
+        writer.start_element("R").unwrap();
+        writer.write_raw_attribute("a", "it's").unwrap();
+        writer.write_raw_attribute("b", "say \"hi\"").unwrap();
+        writer
+            .write_raw_attribute("c", "it's \"hi\" &amp;")
+            .unwrap();
+        writer.end_element().unwrap();
+
+        assert_eq!(
+            r#"<R a="it's" b='say "hi"' c='it&apos;s "hi" &amp;'/>"#,
+            String::from_utf8(writer.finish().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_finish_without_root_is_an_error() {
+        let writer = MyXmlWriter::new(Vec::new());
+        assert_eq!(
+            "Root element is not written",
+            writer.finish().err().unwrap()
+        );
+
+        let mut writer = MyXmlWriter::new(Vec::new());
+        writer.write_raw_markup("<!-- c -->").unwrap();
+        assert_eq!(
+            "Root element is not written",
+            writer.finish().err().unwrap()
+        );
+    }
+}
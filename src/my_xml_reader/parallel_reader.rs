@@ -0,0 +1,266 @@
+use std::ops::Range;
+
+use super::xml_utils;
+use super::{MyXmlReader, ReaderOptions, XmlTagType};
+
+pub fn split_top_level_elements(xml: &[u8]) -> Result<Vec<Range<usize>>, String> {
+    let mut pos = xml_utils::init_pos_start(xml)?;
+    let mut depth = 0;
+    let mut element_start = 0;
+    let mut result = Vec::new();
+
+    while let Some((start_pos, end_pos)) = xml_utils::find_next_tag(xml, pos)? {
+        let (tag_type, _) = xml_utils::parse_tag(&xml[start_pos..end_pos + 1], start_pos)?;
+
+        match tag_type {
+            XmlTagType::Open => {
+                if depth == 1 {
+                    element_start = start_pos;
+                }
+
+                depth += 1;
+            }
+            XmlTagType::Close => {
+                if depth == 0 {
+                    return Err(format!(
+                        "Attempt to close tag at pos {}. There are no opened tags",
+                        start_pos
+                    ));
+                }
+
+                depth -= 1;
+
+                if depth == 1 {
+                    result.push(element_start..end_pos + 1);
+                }
+            }
+            XmlTagType::OpenClose => {
+                if depth == 1 {
+                    result.push(start_pos..end_pos + 1);
+                }
+            }
+        }
+
+        pos = end_pos + 1;
+
+        if depth == 0 {
+            return Ok(result);
+        }
+    }
+
+    Err(format!(
+        "Can not find the end of the root node. {} nodes are still opened",
+        depth
+    ))
+}
+
+// Every element is read with its own reader created with the options. The
+// prolog of the document is read once and its entities are available to the
+// element readers.
+pub fn read_top_level_elements_in_parallel<'t, TResult, TRead>(
+    xml: &'t [u8],
+    options: &ReaderOptions,
+    threads_amount: usize,
+    read: TRead,
+) -> Result<Vec<TResult>, String>
+where
+    TResult: Send,
+    TRead: Fn(MyXmlReader<'t>) -> Result<TResult, String> + Sync,
+{
+    let document = MyXmlReader::from_slice_with_options(xml, options.clone())?;
+    let entities = document.get_entities();
+
+    // Elements are one level below the root in the document, but the roots of
+    // their readers
+    let mut element_options = options.clone();
+    element_options.max_depth = options
+        .max_depth
+        .map(|max_depth| max_depth.saturating_sub(1));
+    let element_options = &element_options;
+
+    let elements = split_top_level_elements(xml)?;
+
+    if elements.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let threads_amount = threads_amount.clamp(1, elements.len());
+    let chunk_size = elements.len().div_ceil(threads_amount);
+    let read = &read;
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = elements
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut result = Vec::with_capacity(chunk.len());
+
+                    for element in chunk {
+                        let mut reader = MyXmlReader::from_slice_with_options(
+                            &xml[element.clone()],
+                            element_options.clone(),
+                        )?;
+                        reader.set_entities(entities.clone());
+                        result.push(read(reader)?);
+                    }
+
+                    Ok::<_, String>(result)
+                })
+            })
+            .collect();
+
+        let mut result = Vec::with_capacity(elements.len());
+
+        for handle in handles {
+            match handle.join() {
+                Ok(chunk_result) => result.extend(chunk_result?),
+                Err(_) => return Err("Thread reading top level elements panicked".to_string()),
+            }
+        }
+
+        Ok(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::my_xml_reader::XmlReaderMode;
+
+    #[test]
+    fn test_split_top_level_elements() {
+        let xml_src = r#"<?xml version="1.0"?>
+            <Export>
+                <Record id="1"><Value>V1</Value></Record>
+                <Record id="2"/>
+                <Record id="3"><Nested><Record id="inner"/></Nested></Record>
+            </Export>"#;
+
+        let elements = split_top_level_elements(xml_src.as_bytes()).unwrap();
+
+        let elements: Vec<&str> = elements.into_iter().map(|r| &xml_src[r]).collect();
+
+        assert_eq!(
+            vec![
+                r#"<Record id="1"><Value>V1</Value></Record>"#,
+                r#"<Record id="2"/>"#,
+                r#"<Record id="3"><Nested><Record id="inner"/></Nested></Record>"#,
+            ],
+            elements
+        );
+    }
+
+    #[test]
+    fn test_split_top_level_elements_of_unclosed_root() {
+        let xml_src = r#"<Export><Record id="1"></Record>"#;
+
+        assert!(split_top_level_elements(xml_src.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_split_top_level_elements_classifies_tags_as_read_next_tag() {
+        let xml_src = "<Export><Record\n/><Record ></Record\n></Export >";
+
+        let elements = split_top_level_elements(xml_src.as_bytes()).unwrap();
+        let elements: Vec<&str> = elements.into_iter().map(|r| &xml_src[r]).collect();
+
+        assert_eq!(vec!["<Record\n/>", "<Record ></Record\n>"], elements);
+
+        assert_eq!(
+            "'/' must go right before '>' in tag <Record>. Pos is 15",
+            split_top_level_elements(b"<Export><Record/ ><Record/></Export>").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_read_top_level_elements_in_parallel_keeps_document_order() {
+        let mut xml_src = String::from("<Export>");
+
+        for i in 0..1000 {
+            xml_src.push_str(&format!("<Record><Id>{}</Id></Record>", i));
+        }
+
+        xml_src.push_str("</Export>");
+
+        let options = ReaderOptions::default();
+
+        let ids =
+            read_top_level_elements_in_parallel(xml_src.as_bytes(), &options, 4, |mut reader| {
+                let id = reader.find_the_open_node("Record/Id")?.unwrap();
+                let id = reader.read_the_whole_node(id)?.get_value()?.unwrap();
+                Ok(id.parse::<usize>().unwrap())
+            })
+            .unwrap();
+
+        assert_eq!((0..1000).collect::<Vec<_>>(), ids);
+    }
+
+    #[test]
+    fn test_read_top_level_elements_in_parallel_returns_error() {
+        let xml_src = "<Export><Record>1</Record><Record>2</Record></Export>";
+
+        let options = ReaderOptions::default();
+
+        let result =
+            read_top_level_elements_in_parallel(xml_src.as_bytes(), &options, 2, |mut reader| {
+                let record = reader.read_next_tag()?.unwrap();
+                let value = reader.read_the_whole_node(record)?.get_value()?.unwrap();
+
+                if value == "2" {
+                    return Err("Invalid record".to_string());
+                }
+
+                Ok(value)
+            });
+
+        assert_eq!(Err("Invalid record".to_string()), result);
+    }
+
+    #[test]
+    fn test_read_top_level_elements_in_parallel_with_options_and_entities() {
+        let xml_src = r#"<!DOCTYPE Export [<!ENTITY company "Acme">]>
+            <Export><Record><Name>&company; 1</Name></Record><Record><Name>&company; 2</Name></Record></Export>"#;
+
+        let options = ReaderOptions::with_mode(XmlReaderMode::Strict);
+
+        let names =
+            read_top_level_elements_in_parallel(xml_src.as_bytes(), &options, 2, |mut reader| {
+                assert_eq!(XmlReaderMode::Strict, reader.get_mode());
+
+                let name = reader.find_the_open_node("Record/Name")?.unwrap();
+                let name = reader.read_the_whole_node(name)?;
+                Ok(reader.get_node_value(&name)?.unwrap())
+            })
+            .unwrap();
+
+        assert_eq!(vec!["Acme 1", "Acme 2"], names);
+    }
+
+    #[test]
+    fn test_read_top_level_elements_in_parallel_applies_limits() {
+        let xml_src = "<Export><Record><A><B/></A></Record></Export>";
+
+        let options = ReaderOptions {
+            max_depth: Some(3),
+            ..Default::default()
+        };
+
+        let result =
+            read_top_level_elements_in_parallel(xml_src.as_bytes(), &options, 1, |mut reader| {
+                while reader.read_next_tag()?.is_some() {}
+                Ok(())
+            });
+
+        assert!(result.unwrap_err().contains("depth"));
+
+        let options = ReaderOptions {
+            max_document_size: Some(10),
+            ..Default::default()
+        };
+
+        let result =
+            read_top_level_elements_in_parallel(xml_src.as_bytes(), &options, 1, |_| Ok(()));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use super::{XmlReaderMode, XmlWhitespaceHandling};
+use super::{DEFAULT_MAX_ENTITY_EXPANSION_DEPTH, DEFAULT_MAX_ENTITY_EXPANSION_SIZE};
+
+#[derive(Debug, Clone)]
+pub struct ReaderOptions {
+    pub mode: XmlReaderMode,
+    pub max_depth: Option<usize>,
+    pub max_attributes_per_tag: Option<usize>,
+    pub max_name_length: Option<usize>,
+    pub max_text_size: Option<usize>,
+    pub max_document_size: Option<usize>,
+    pub max_entity_expansion_size: usize,
+    pub max_entity_expansion_depth: usize,
+    pub validate_utf8: bool,
+    pub value_whitespace: XmlWhitespaceHandling,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            mode: XmlReaderMode::Default,
+            max_depth: None,
+            max_attributes_per_tag: None,
+            max_name_length: None,
+            max_text_size: None,
+            max_document_size: None,
+            max_entity_expansion_size: DEFAULT_MAX_ENTITY_EXPANSION_SIZE,
+            max_entity_expansion_depth: DEFAULT_MAX_ENTITY_EXPANSION_DEPTH,
+            validate_utf8: false,
+            value_whitespace: XmlWhitespaceHandling::Preserve,
+        }
+    }
+}
+
+impl ReaderOptions {
+    pub fn with_mode(mode: XmlReaderMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmlLimit {
+    Depth,
+    AttributesPerTag,
+    NameLength,
+    TextSize,
+    DocumentSize,
+    EntityExpansionSize,
+    EntityExpansionDepth,
+}
+
+impl fmt::Display for XmlLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Depth => write!(f, "depth"),
+            Self::AttributesPerTag => write!(f, "attributes per tag"),
+            Self::NameLength => write!(f, "name length"),
+            Self::TextSize => write!(f, "text size"),
+            Self::DocumentSize => write!(f, "document size"),
+            Self::EntityExpansionSize => write!(f, "entity expansion size"),
+            Self::EntityExpansionDepth => write!(f, "entity expansion depth"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlLimitError {
+    pub limit: XmlLimit,
+    pub max: usize,
+    pub actual: usize,
+    pub pos: usize,
+}
+
+impl fmt::Display for XmlLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Limit of {} is exceeded: {} while max is {} at pos {}",
+            self.limit, self.actual, self.max, self.pos
+        )
+    }
+}
+
+impl std::error::Error for XmlLimitError {}
+
+pub fn check_limit(
+    limit: XmlLimit,
+    max: Option<usize>,
+    actual: usize,
+    pos: usize,
+) -> Result<(), XmlLimitError> {
+    match max {
+        Some(max) if actual > max => Err(XmlLimitError {
+            limit,
+            max,
+            actual,
+            pos,
+        }),
+        _ => Ok(()),
+    }
+}
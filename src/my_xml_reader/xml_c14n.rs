@@ -0,0 +1,869 @@
+use std::io::Write;
+
+use super::xml_utils::{find_markup_end, OPEN_CDATA_TOKEN, OPEN_COMMENT_TOKEN, OPEN_HEADER_TOKEN};
+use super::{is_xml_declaration_at, AttributesIterator, MyXmlReader, XmlTagInfo, XmlTagType};
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum C14nMethod {
+    // Canonical XML 1.0
+    #[default]
+    Inclusive,
+    // Exclusive XML Canonicalization 1.0
+    Exclusive,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct C14nOptions {
+    pub method: C14nMethod,
+    pub with_comments: bool,
+    // InclusiveNamespaces PrefixList of the exclusive method. "#default"
+    // stands for the default namespace.
+    pub inclusive_prefixes: Vec<String>,
+    // Start position of an element left out with all its content, as the
+    // enveloped signature transform of XML signatures does
+    pub excluded_element: Option<usize>,
+}
+
+// Canonicalizes the whole document the reader was created for. The reader
+// must not have read anything yet.
+pub fn canonicalize_document(
+    reader: &mut MyXmlReader,
+    options: &C14nOptions,
+    out: impl Write,
+) -> Result<(), String> {
+    let root = loop {
+        let Some(tag) = reader.read_next_tag()? else {
+            return Err("Document has no root element".to_string());
+        };
+
+        if !matches!(tag.tag_type, XmlTagType::Close) {
+            break tag;
+        }
+    };
+
+    let xml = reader.xml;
+    let prolog_start = if xml.starts_with(UTF8_BOM) {
+        UTF8_BOM.len()
+    } else {
+        0
+    };
+
+    let mut canonicalizer = XmlCanonicalizer::new(reader, options, out);
+
+    for part in split_content(&xml[prolog_start..root.start_pos])? {
+        if canonicalizer.write_outside_root(part)? {
+            canonicalizer.write(b"\n")?;
+        }
+    }
+
+    let root_end = canonicalizer.write_subtree(root)?;
+
+    for part in split_content(&xml[root_end..])? {
+        if canonicalizer.is_written_outside_root(&part) {
+            canonicalizer.write(b"\n")?;
+            canonicalizer.write_outside_root(part)?;
+        }
+    }
+
+    canonicalizer.flush()
+}
+
+// Canonicalizes the element which has just been read, for example by
+// find_the_open_node, as a document subset with all its descendants.
+// Namespaces (and xml:* attributes for the inclusive method) in scope from
+// the ancestors are taken into account. The reader is left after the end of
+// the element.
+pub fn canonicalize_element<'t>(
+    reader: &mut MyXmlReader<'t>,
+    tag: XmlTagInfo<'t>,
+    options: &C14nOptions,
+    out: impl Write,
+) -> Result<(), String> {
+    if matches!(tag.tag_type, XmlTagType::Close) {
+        return Err(format!(
+            "Can not canonicalize </{}>. Open tag is expected",
+            tag.name
+        ));
+    }
+
+    let mut canonicalizer = XmlCanonicalizer::new(reader, options, out);
+    canonicalizer.write_subtree(tag)?;
+    canonicalizer.flush()
+}
+
+enum ContentPart<'t> {
+    Text(&'t [u8]),
+    CData(&'t [u8]),
+    Comment(&'t [u8]),
+    ProcessingInstruction(&'t [u8]),
+    // The XML declaration and the DOCTYPE
+    Declaration,
+}
+
+fn split_content(content: &[u8]) -> Result<Vec<ContentPart<'_>>, String> {
+    let mut result = Vec::new();
+    let mut pos = 0;
+
+    while pos < content.len() {
+        let markup_start = content[pos..]
+            .iter()
+            .position(|b| *b == b'<')
+            .map_or(content.len(), |i| pos + i);
+
+        if markup_start > pos {
+            result.push(ContentPart::Text(&content[pos..markup_start]));
+        }
+
+        if markup_start == content.len() {
+            break;
+        }
+
+        let Some(markup_end) = find_markup_end(content, markup_start) else {
+            return Err(format!(
+                "Markup is not closed: {}",
+                String::from_utf8_lossy(&content[markup_start..])
+            ));
+        };
+
+        let markup = &content[markup_start..=markup_end];
+
+        result.push(if markup.starts_with(OPEN_CDATA_TOKEN) {
+            ContentPart::CData(&markup[OPEN_CDATA_TOKEN.len()..markup.len() - 3])
+        } else if markup.starts_with(OPEN_COMMENT_TOKEN) {
+            ContentPart::Comment(markup)
+        } else if markup.starts_with(OPEN_HEADER_TOKEN)
+            && !is_xml_declaration_at(content, markup_start)
+        {
+            ContentPart::ProcessingInstruction(markup)
+        } else {
+            ContentPart::Declaration
+        });
+
+        pos = markup_end + 1;
+    }
+
+    Ok(result)
+}
+
+struct Attribute<'a> {
+    name: &'a str,
+    namespace: String,
+    local_name: &'a str,
+    value: String,
+}
+
+struct XmlCanonicalizer<'r, 't, W: Write> {
+    reader: &'r mut MyXmlReader<'t>,
+    options: &'r C14nOptions,
+    out: W,
+    // Namespace declarations in scope as (prefix, URI). The default namespace
+    // has an empty prefix, xmlns="" is stored with an empty URI.
+    in_scope: Vec<(String, String)>,
+    // Declarations rendered by output ancestors
+    rendered: Vec<(String, String)>,
+    // Lengths of in_scope and rendered before each opened element
+    scope_stack: Vec<(usize, usize)>,
+}
+
+impl<'r, 't, W: Write> XmlCanonicalizer<'r, 't, W> {
+    fn new(reader: &'r mut MyXmlReader<'t>, options: &'r C14nOptions, out: W) -> Self {
+        Self {
+            reader,
+            options,
+            out,
+            in_scope: vec![],
+            rendered: vec![],
+            scope_stack: vec![],
+        }
+    }
+
+    // Returns the position right after the end of the element
+    fn write_subtree(&mut self, apex: XmlTagInfo<'t>) -> Result<usize, String> {
+        let inherited_xml_attributes = self.read_ancestors(&apex)?;
+
+        self.write_start_tag(&apex, inherited_xml_attributes)?;
+
+        if matches!(apex.tag_type, XmlTagType::OpenClose) {
+            self.write_end_tag(apex.name)?;
+            return Ok(apex.end_pos + 1);
+        }
+
+        let mut content_start = apex.end_pos + 1;
+
+        loop {
+            let Some(tag) = self.reader.read_next_tag()? else {
+                return Err(format!("Element <{}> is not closed", apex.name));
+            };
+
+            let xml = self.reader.xml;
+
+            for part in split_content(&xml[content_start..tag.start_pos])? {
+                self.write_content(part)?;
+            }
+
+            content_start = tag.end_pos + 1;
+
+            if self.options.excluded_element == Some(tag.start_pos) {
+                if let XmlTagType::Open = tag.tag_type {
+                    content_start = self.reader.skip_node(&tag)?.end;
+                }
+
+                continue;
+            }
+
+            match tag.tag_type {
+                XmlTagType::Open => self.write_start_tag(&tag, vec![])?,
+                XmlTagType::OpenClose => {
+                    self.write_start_tag(&tag, vec![])?;
+                    self.write_end_tag(tag.name)?;
+                }
+                XmlTagType::Close => {
+                    self.write_end_tag(tag.name)?;
+
+                    if tag.level <= apex.level {
+                        return Ok(content_start);
+                    }
+                }
+            }
+        }
+    }
+
+    // Collects namespace declarations and xml:* attributes of the elements
+    // which contain the apex. They are opened in the reader.
+    fn read_ancestors(&mut self, apex: &XmlTagInfo<'t>) -> Result<Vec<Attribute<'t>>, String> {
+        let xml = self.reader.xml;
+        let ancestors = &self.reader.opened_nodes[..apex.level.min(self.reader.opened_nodes.len())];
+        let ancestors: Vec<(usize, usize)> = ancestors
+            .iter()
+            .map(|node| (node.start_pos, node.end_pos))
+            .collect();
+
+        let mut xml_attributes: Vec<Attribute<'t>> = vec![];
+
+        for (start_pos, end_pos) in ancestors {
+            let mut attributes = AttributesIterator::new(&xml[start_pos..=end_pos]);
+
+            while let Some((name, value)) = attributes.get_next()? {
+                if let Some(prefix) = get_declared_prefix(name) {
+                    let uri = self.normalize_attribute_value(value)?;
+                    self.in_scope.push((prefix.to_string(), uri));
+                } else if let Some(local_name) = name.strip_prefix("xml:") {
+                    let value = self.normalize_attribute_value(value)?;
+                    xml_attributes.retain(|attr| attr.name != name);
+                    xml_attributes.push(Attribute {
+                        name,
+                        namespace: XML_NAMESPACE.to_string(),
+                        local_name,
+                        value,
+                    });
+                }
+            }
+        }
+
+        Ok(xml_attributes)
+    }
+
+    fn write_start_tag(
+        &mut self,
+        tag: &XmlTagInfo<'t>,
+        inherited_xml_attributes: Vec<Attribute<'t>>,
+    ) -> Result<(), String> {
+        self.scope_stack
+            .push((self.in_scope.len(), self.rendered.len()));
+
+        let mut attributes = vec![];
+        let mut iterator = AttributesIterator::new(tag.raw);
+
+        while let Some((name, value)) = iterator.get_next()? {
+            let value = self.normalize_attribute_value(value)?;
+
+            match get_declared_prefix(name) {
+                Some(prefix) => self.in_scope.push((prefix.to_string(), value)),
+                None => attributes.push((name, value)),
+            }
+        }
+
+        let mut attributes = attributes
+            .into_iter()
+            .map(|(name, value)| {
+                let (namespace, local_name) = match name.split_once(':') {
+                    Some((prefix, local_name)) => (self.get_namespace(prefix, tag)?, local_name),
+                    None => (String::new(), name),
+                };
+
+                Ok(Attribute {
+                    name,
+                    namespace,
+                    local_name,
+                    value,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if self.options.method == C14nMethod::Inclusive {
+            for attr in inherited_xml_attributes {
+                if !attributes.iter().any(|a| a.name == attr.name) {
+                    attributes.push(attr);
+                }
+            }
+        }
+
+        let namespaces = self.get_namespaces_to_render(tag.name, &attributes);
+
+        attributes.sort_by(|a, b| {
+            (a.namespace.as_str(), a.local_name).cmp(&(b.namespace.as_str(), b.local_name))
+        });
+
+        self.write(b"<")?;
+        self.write(tag.name.as_bytes())?;
+
+        for (prefix, uri) in &namespaces {
+            if prefix.is_empty() {
+                self.write(b" xmlns=\"")?;
+            } else {
+                self.write(b" xmlns:")?;
+                self.write(prefix.as_bytes())?;
+                self.write(b"=\"")?;
+            }
+
+            self.write(escape_attribute_value(uri).as_bytes())?;
+            self.write(b"\"")?;
+        }
+
+        for attr in &attributes {
+            self.write(b" ")?;
+            self.write(attr.name.as_bytes())?;
+            self.write(b"=\"")?;
+            self.write(escape_attribute_value(&attr.value).as_bytes())?;
+            self.write(b"\"")?;
+        }
+
+        self.rendered.extend(namespaces);
+
+        self.write(b">")
+    }
+
+    fn get_namespaces_to_render(
+        &self,
+        element_name: &str,
+        attributes: &[Attribute],
+    ) -> Vec<(String, String)> {
+        let mut prefixes: Vec<&str> = match self.options.method {
+            C14nMethod::Inclusive => self
+                .in_scope
+                .iter()
+                .map(|(prefix, _)| prefix.as_str())
+                .collect(),
+            C14nMethod::Exclusive => {
+                let mut result = vec![element_name.split_once(':').map_or("", |(p, _)| p)];
+
+                for attr in attributes {
+                    if let Some((prefix, _)) = attr.name.split_once(':') {
+                        result.push(prefix);
+                    }
+                }
+
+                for prefix in &self.options.inclusive_prefixes {
+                    if prefix == "#default" {
+                        result.push("");
+                    } else if self.find_in_scope(prefix).is_some() {
+                        result.push(prefix);
+                    }
+                }
+
+                result
+            }
+        };
+
+        prefixes.sort_unstable();
+        prefixes.dedup();
+
+        let mut result = vec![];
+
+        for prefix in prefixes {
+            if prefix == "xml" {
+                continue;
+            }
+
+            let uri = self.find_in_scope(prefix).unwrap_or("");
+
+            // xmlns="" is rendered only to undeclare a rendered default
+            // namespace. Undeclared prefixes are reported when names of
+            // attributes are resolved.
+            let is_rendered = if prefix.is_empty() {
+                uri == find(&self.rendered, "").unwrap_or("")
+            } else {
+                uri.is_empty() || find(&self.rendered, prefix) == Some(uri)
+            };
+
+            if !is_rendered {
+                result.push((prefix.to_string(), uri.to_string()));
+            }
+        }
+
+        result
+    }
+
+    fn get_namespace(&self, prefix: &str, tag: &XmlTagInfo) -> Result<String, String> {
+        if prefix == "xml" {
+            return Ok(XML_NAMESPACE.to_string());
+        }
+
+        match self.find_in_scope(prefix) {
+            Some(uri) if !uri.is_empty() => Ok(uri.to_string()),
+            _ => Err(format!(
+                "Namespace prefix '{}' is not declared. Tag <{}> at pos {}",
+                prefix, tag.name, tag.start_pos
+            )),
+        }
+    }
+
+    fn find_in_scope(&self, prefix: &str) -> Option<&str> {
+        find(&self.in_scope, prefix)
+    }
+
+    fn write_end_tag(&mut self, name: &str) -> Result<(), String> {
+        if let Some((in_scope_len, rendered_len)) = self.scope_stack.pop() {
+            self.in_scope.truncate(in_scope_len);
+            self.rendered.truncate(rendered_len);
+        }
+
+        self.write(b"</")?;
+        self.write(name.as_bytes())?;
+        self.write(b">")
+    }
+
+    fn write_content(&mut self, part: ContentPart) -> Result<(), String> {
+        match part {
+            ContentPart::Text(text) => {
+                let text = normalize_line_endings(to_str(text)?);
+                let text = self.reader.expand_entities(&text)?;
+                self.write(escape_text(&text).as_bytes())
+            }
+            ContentPart::CData(text) => {
+                let text = normalize_line_endings(to_str(text)?);
+                self.write(escape_text(&text).as_bytes())
+            }
+            part => {
+                self.write_outside_root(part)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn is_written_outside_root(&self, part: &ContentPart) -> bool {
+        match part {
+            ContentPart::Comment(_) => self.options.with_comments,
+            ContentPart::ProcessingInstruction(_) => true,
+            _ => false,
+        }
+    }
+
+    // Writes comments and processing instructions. Returns false if the part
+    // is omitted.
+    fn write_outside_root(&mut self, part: ContentPart) -> Result<bool, String> {
+        if !self.is_written_outside_root(&part) {
+            return Ok(false);
+        }
+
+        match part {
+            ContentPart::Comment(comment) => {
+                let comment = normalize_line_endings(to_str(comment)?);
+                self.write(comment.as_bytes())?;
+            }
+            ContentPart::ProcessingInstruction(pi) => {
+                let pi = normalize_line_endings(to_str(pi)?);
+                let pi = &pi[2..pi.len() - 2];
+
+                let (target, data) = pi.split_once(is_xml_whitespace).unwrap_or((pi, ""));
+                let data = data.trim_start_matches(is_xml_whitespace);
+
+                self.write(b"<?")?;
+                self.write(target.as_bytes())?;
+
+                if !data.is_empty() {
+                    self.write(b" ")?;
+                    self.write(data.as_bytes())?;
+                }
+
+                self.write(b"?>")?;
+            }
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    // Literal whitespace is replaced with spaces as an XML processor does for
+    // CDATA attributes, while whitespace from character references is kept.
+    fn normalize_attribute_value(&self, value: &str) -> Result<String, String> {
+        let value = normalize_line_endings(value).replace(['\t', '\n'], " ");
+        self.reader.expand_entities(&value)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.out.write_all(data).map_err(|err| err.to_string())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.out.flush().map_err(|err| err.to_string())
+    }
+}
+
+fn find<'a>(namespaces: &'a [(String, String)], prefix: &str) -> Option<&'a str> {
+    namespaces
+        .iter()
+        .rev()
+        .find(|(p, _)| p == prefix)
+        .map(|(_, uri)| uri.as_str())
+}
+
+// Returns the prefix a namespace declaration attribute declares, an empty one
+// for the default namespace.
+fn get_declared_prefix(name: &str) -> Option<&str> {
+    if name == "xmlns" {
+        return Some("");
+    }
+
+    name.strip_prefix("xmlns:")
+}
+
+fn is_xml_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+fn to_str(data: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(data).map_err(|err| format!("Content is not valid UTF-8: {}", err))
+}
+
+fn normalize_line_endings(value: &str) -> std::borrow::Cow<'_, str> {
+    if !value.contains('\r') {
+        return std::borrow::Cow::Borrowed(value);
+    }
+
+    std::borrow::Cow::Owned(value.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+fn escape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '\r' => result.push_str("&#xD;"),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn escape_attribute_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '"' => result.push_str("&quot;"),
+            '\t' => result.push_str("&#x9;"),
+            '\n' => result.push_str("&#xA;"),
+            '\r' => result.push_str("&#xD;"),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonicalize(xml: &str, options: &C14nOptions) -> String {
+        let mut reader = MyXmlReader::from_slice(xml.as_bytes()).unwrap();
+
+        let mut out = Vec::new();
+        canonicalize_document(&mut reader, options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn canonicalize_subtree(xml: &str, path: &str, options: &C14nOptions) -> String {
+        let mut reader = MyXmlReader::from_slice(xml.as_bytes()).unwrap();
+        let tag = reader.find_the_open_node(path).unwrap().unwrap();
+
+        let mut out = Vec::new();
+        canonicalize_element(&mut reader, tag, options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn with_comments() -> C14nOptions {
+        C14nOptions {
+            with_comments: true,
+            ..Default::default()
+        }
+    }
+
+    fn exclusive() -> C14nOptions {
+        C14nOptions {
+            method: C14nMethod::Exclusive,
+            ..Default::default()
+        }
+    }
+
+    // Examples of section 3 of the Canonical XML 1.0 recommendation
+
+    #[test]
+    fn test_pis_comments_and_outside_of_document_element() {
+        let xml = r#"<?xml version="1.0"?>
+
+<?xml-stylesheet   href="doc.xsl"
+   type="text/xsl"   ?>
+
+<!DOCTYPE doc SYSTEM "doc.dtd">
+
+<doc>Hello, world!<!-- Comment 1 --></doc>
+
+<?pi-without-data     ?>
+
+<!-- Comment 2 -->
+
+<!-- Comment 3 -->"#;
+
+        assert_eq!(
+            r#"<?xml-stylesheet href="doc.xsl"
+   type="text/xsl"   ?>
+<doc>Hello, world!</doc>
+<?pi-without-data?>"#,
+            canonicalize(xml, &C14nOptions::default())
+        );
+
+        assert_eq!(
+            r#"<?xml-stylesheet href="doc.xsl"
+   type="text/xsl"   ?>
+<doc>Hello, world!<!-- Comment 1 --></doc>
+<?pi-without-data?>
+<!-- Comment 2 -->
+<!-- Comment 3 -->"#,
+            canonicalize(xml, &with_comments())
+        );
+    }
+
+    #[test]
+    fn test_whitespace_in_document_content() {
+        let xml = r#"<doc>
+   <clean>   </clean>
+   <dirty>   A   B   </dirty>
+   <mixed>
+      A
+      <clean>   </clean>
+      B
+      <dirty>   A   B   </dirty>
+      C
+   </mixed>
+</doc>"#;
+
+        assert_eq!(xml, canonicalize(xml, &C14nOptions::default()));
+    }
+
+    // The DOCTYPE of the original example declares a default value of e9/@attr,
+    // which is not applied here.
+    #[test]
+    fn test_start_and_end_tags() {
+        let xml = r#"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+
+        assert_eq!(
+            r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#,
+            canonicalize(xml, &C14nOptions::default())
+        );
+    }
+
+    // normNames and normId of the original example depend on attribute types
+    // declared in the DTD and are left out.
+    #[test]
+    fn test_character_modifications_and_character_references() {
+        let xml = r#"<doc>
+   <text>First line&#x0d;&#10;Second line</text>
+   <value>&#x32;</value>
+   <compute><![CDATA[value>"0" && value<"10" ?"valid":"error"]]></compute>
+   <compute expr='value>"0" &amp;&amp; value&lt;"10" ?"valid":"error"'>valid</compute>
+   <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>
+</doc>"#;
+
+        assert_eq!(
+            r#"<doc>
+   <text>First line&#xD;
+Second line</text>
+   <value>2</value>
+   <compute>value&gt;"0" &amp;&amp; value&lt;"10" ?"valid":"error"</compute>
+   <compute expr="value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;">valid</compute>
+   <norm attr=" '    &#xD;&#xA;&#x9;   ' "></norm>
+</doc>"#,
+            canonicalize(xml, &C14nOptions::default())
+        );
+    }
+
+    // External entities are never resolved, so ent2 of the original example is
+    // declared internally.
+    #[test]
+    fn test_entity_references() {
+        let xml = r#"<!DOCTYPE doc [
+<!ATTLIST doc attrExtEnt ENTITY #IMPLIED>
+<!ENTITY ent1 "Hello">
+<!ENTITY ent2 "world">
+<!ENTITY entExt SYSTEM "earth.gif" NDATA gif>
+<!NOTATION gif SYSTEM "viewgif.exe">
+]>
+<doc attrExtEnt="entExt">
+   &ent1;, &ent2;!
+</doc>
+
+<!-- Let world.txt contain "world" (excluding the quotes) -->"#;
+
+        assert_eq!(
+            "<doc attrExtEnt=\"entExt\">\n   Hello, world!\n</doc>",
+            canonicalize(xml, &C14nOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_utf8_encoding() {
+        let xml = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<doc>&#169;</doc>";
+
+        assert_eq!("<doc>©</doc>", canonicalize(xml, &C14nOptions::default()));
+    }
+
+    #[test]
+    fn test_line_endings_and_document_subset_with_xml_attributes() {
+        let xml = "<Root xmlns=\"urn:root\" xmlns:u=\"urn:unused\" xml:lang=\"en\" xml:space=\"preserve\">\r\n\
+                   <Signed Id=\"s\" xml:lang=\"fr\">a\r\nb\rc<Empty/></Signed></Root>";
+
+        assert_eq!(
+            "<Signed xmlns=\"urn:root\" xmlns:u=\"urn:unused\" Id=\"s\" xml:lang=\"fr\" xml:space=\"preserve\">a\nb\nc<Empty></Empty></Signed>",
+            canonicalize_subtree(xml, "Root/Signed", &C14nOptions::default())
+        );
+
+        assert_eq!(
+            "<Signed xmlns=\"urn:root\" Id=\"s\" xml:lang=\"fr\">a\nb\nc<Empty></Empty></Signed>",
+            canonicalize_subtree(xml, "Root/Signed", &exclusive())
+        );
+    }
+
+    // Example of section 2.2 of the Exclusive XML Canonicalization recommendation
+    #[test]
+    fn test_exclusive_canonicalization() {
+        let xml = r#"<n0:local xmlns:n0="foo:bar" xmlns:n3="ftp://example.org">
+   <n1:elem2 xmlns:n1="http://example.net" xml:lang="en">
+       <n3:stuff xmlns:n3="ftp://example.org"/>
+   </n1:elem2>
+</n0:local>"#;
+
+        assert_eq!(
+            r#"<n1:elem2 xmlns:n0="foo:bar" xmlns:n1="http://example.net" xmlns:n3="ftp://example.org" xml:lang="en">
+       <n3:stuff></n3:stuff>
+   </n1:elem2>"#,
+            canonicalize_subtree(xml, "n0:local/n1:elem2", &C14nOptions::default())
+        );
+
+        assert_eq!(
+            r#"<n1:elem2 xmlns:n1="http://example.net" xml:lang="en">
+       <n3:stuff xmlns:n3="ftp://example.org"></n3:stuff>
+   </n1:elem2>"#,
+            canonicalize_subtree(xml, "n0:local/n1:elem2", &exclusive())
+        );
+
+        let options = C14nOptions {
+            inclusive_prefixes: vec!["n0".to_string(), "#default".to_string()],
+            ..exclusive()
+        };
+
+        assert_eq!(
+            r#"<n1:elem2 xmlns:n0="foo:bar" xmlns:n1="http://example.net" xml:lang="en">
+       <n3:stuff xmlns:n3="ftp://example.org"></n3:stuff>
+   </n1:elem2>"#,
+            canonicalize_subtree(xml, "n0:local/n1:elem2", &options)
+        );
+    }
+
+    #[test]
+    fn test_reader_is_left_after_the_element() {
+        let xml = "<R><A><B/></A><C/></R>";
+        let mut reader = MyXmlReader::from_slice(xml.as_bytes()).unwrap();
+        let tag = reader.find_the_open_node("R/A").unwrap().unwrap();
+
+        let mut out = Vec::new();
+        canonicalize_element(&mut reader, tag, &C14nOptions::default(), &mut out).unwrap();
+
+        assert_eq!("<A><B></B></A>", String::from_utf8(out).unwrap());
+        assert_eq!("C", reader.read_next_tag().unwrap().unwrap().name);
+    }
+
+    #[test]
+    fn test_excluded_element() {
+        let xml = "<R>\n  <A>1</A>\n  <S><B/></S><E/>\n</R>";
+
+        let options = C14nOptions {
+            excluded_element: xml.find("<S>"),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "<R>\n  <A>1</A>\n  <E></E>\n</R>",
+            canonicalize(xml, &options)
+        );
+
+        let options = C14nOptions {
+            excluded_element: xml.find("<E/>"),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "<R>\n  <A>1</A>\n  <S><B></B></S>\n</R>",
+            canonicalize(xml, &options)
+        );
+    }
+
+    #[test]
+    fn test_undeclared_prefix_is_an_error() {
+        let mut reader = MyXmlReader::from_slice(b"<R p:a=\"1\"/>").unwrap();
+
+        assert_eq!(
+            "Namespace prefix 'p' is not declared. Tag <R> at pos 0",
+            canonicalize_document(&mut reader, &C14nOptions::default(), Vec::new()).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use super::xml_utils;
+use super::{XmlValidationError, XmlValidationErrorKind};
+
+const XML_DECLARATION_TOKEN: &[u8] = "<?xml".as_bytes();
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+const VERSION_ATTR: &str = "version";
+const ENCODING_ATTR: &str = "encoding";
+const STANDALONE_ATTR: &str = "standalone";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlDeclaration {
+    pub version: String,
+    pub encoding: Option<String>,
+    pub standalone: Option<bool>,
+    pub start_pos: usize,
+    pub end_pos: usize,
+}
+
+impl XmlDeclaration {
+    // Returns None if the document has no XML declaration. The declaration
+    // may only be preceded by the UTF-8 BOM.
+    pub fn parse(xml: &[u8]) -> Result<Option<Self>, XmlValidationError> {
+        let start_pos = if xml.starts_with(UTF8_BOM) {
+            UTF8_BOM.len()
+        } else {
+            0
+        };
+
+        if is_xml_declaration_at(xml, start_pos) {
+            return Self::parse_at(xml, start_pos).map(Some);
+        }
+
+        let first_markup_pos = xml[start_pos..]
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .map(|pos| start_pos + pos);
+
+        match first_markup_pos {
+            Some(pos) if is_xml_declaration_at(xml, pos) => Err(XmlValidationError::new(
+                XmlValidationErrorKind::XmlDeclarationNotAtStart,
+                pos,
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn parse_at(xml: &[u8], start_pos: usize) -> Result<Self, XmlValidationError> {
+        let Some(close_pos) =
+            xml_utils::find_next_token_ext(xml, xml_utils::CLOSE_HEADER_TOKEN, start_pos)
+        else {
+            return Err(XmlValidationError::new(
+                XmlValidationErrorKind::UnclosedMarkup,
+                start_pos,
+            ));
+        };
+
+        let invalid = |reason: String| {
+            XmlValidationError::new(
+                XmlValidationErrorKind::InvalidXmlDeclaration(reason),
+                start_pos,
+            )
+        };
+
+        let content = &xml[start_pos + XML_DECLARATION_TOKEN.len()..close_pos];
+
+        let content = std::str::from_utf8(content)
+            .map_err(|err| invalid(format!("not valid UTF-8: {}", err)))?;
+
+        let mut version = None;
+        let mut encoding = None;
+        let mut standalone = None;
+
+        // Pseudo-attributes must go in this order and each of them only once
+        let order = [VERSION_ATTR, ENCODING_ATTR, STANDALONE_ATTR];
+        let mut next_allowed = 0;
+
+        let mut rest = content;
+
+        loop {
+            let trimmed = rest.trim_start();
+
+            if trimmed.is_empty() {
+                break;
+            }
+
+            if trimmed.len() == rest.len() {
+                return Err(invalid(format!(
+                    "whitespace is expected before '{}'",
+                    trimmed
+                )));
+            }
+
+            let (name, value, after) = read_pseudo_attribute(trimmed).map_err(invalid)?;
+
+            let Some(index) = order.iter().position(|attr| *attr == name) else {
+                return Err(invalid(format!("unknown attribute '{}'", name)));
+            };
+
+            if index < next_allowed {
+                return Err(invalid(format!(
+                    "attribute '{}' is duplicated or out of order",
+                    name
+                )));
+            }
+
+            next_allowed = index + 1;
+
+            match name {
+                VERSION_ATTR => {
+                    if !is_valid_version(value) {
+                        return Err(invalid(format!("invalid version '{}'", value)));
+                    }
+                    version = Some(value.to_string());
+                }
+                ENCODING_ATTR => {
+                    if !is_valid_encoding_name(value) {
+                        return Err(invalid(format!("invalid encoding name '{}'", value)));
+                    }
+                    encoding = Some(value.to_string());
+                }
+                _ => {
+                    standalone = match value {
+                        "yes" => Some(true),
+                        "no" => Some(false),
+                        _ => {
+                            return Err(invalid(format!(
+                                "standalone must be 'yes' or 'no' but it is '{}'",
+                                value
+                            )))
+                        }
+                    };
+                }
+            }
+
+            rest = after;
+        }
+
+        let Some(version) = version else {
+            return Err(invalid("version is missing".to_string()));
+        };
+
+        Ok(Self {
+            version,
+            encoding,
+            standalone,
+            start_pos,
+            end_pos: close_pos + xml_utils::CLOSE_HEADER_TOKEN.len() - 1,
+        })
+    }
+}
+
+pub fn is_xml_declaration_at(xml: &[u8], pos: usize) -> bool {
+    let Some(markup) = xml.get(pos..) else {
+        return false;
+    };
+
+    if !markup.starts_with(XML_DECLARATION_TOKEN) {
+        return false;
+    }
+
+    match markup.get(XML_DECLARATION_TOKEN.len()) {
+        Some(b) => b.is_ascii_whitespace() || *b == b'?',
+        None => false,
+    }
+}
+
+fn read_pseudo_attribute(src: &str) -> Result<(&str, &str, &str), String> {
+    let name_end = src
+        .find(|c: char| c == '=' || c.is_ascii_whitespace())
+        .unwrap_or(src.len());
+
+    let name = &src[..name_end];
+
+    let Some(rest) = src[name_end..].trim_start().strip_prefix('=') else {
+        return Err(format!("'=' is expected after '{}'", name));
+    };
+
+    let rest = rest.trim_start();
+
+    let quote = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => quote,
+        _ => return Err(format!("value of '{}' must be quoted", name)),
+    };
+
+    let Some(value_end) = rest[1..].find(quote) else {
+        return Err(format!("value of '{}' is not closed", name));
+    };
+
+    Ok((name, &rest[1..value_end + 1], &rest[value_end + 2..]))
+}
+
+fn is_valid_version(version: &str) -> bool {
+    match version.strip_prefix("1.") {
+        Some(minor) => !minor.is_empty() && minor.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+fn is_valid_encoding_name(encoding: &str) -> bool {
+    let mut bytes = encoding.bytes();
+
+    match bytes.next() {
+        Some(b) if b.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+
+    bytes.all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_declaration() {
+        let xml =
+            "\u{FEFF}<?xml version=\"1.0\" encoding='windows-1251' standalone = \"yes\" ?><R/>";
+
+        let declaration = XmlDeclaration::parse(xml.as_bytes()).unwrap().unwrap();
+
+        assert_eq!(
+            XmlDeclaration {
+                version: "1.0".to_string(),
+                encoding: Some("windows-1251".to_string()),
+                standalone: Some(true),
+                start_pos: 3,
+                end_pos: 67,
+            },
+            declaration
+        );
+
+        assert_eq!(b"<R/>", &xml.as_bytes()[declaration.end_pos + 1..]);
+    }
+
+    #[test]
+    fn test_no_declaration() {
+        let test_cases = ["<R/>", "<?xml-stylesheet href=\"a.xsl\"?><R/>", "  <R/>"];
+
+        for xml in test_cases {
+            assert_eq!(None, XmlDeclaration::parse(xml.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_invalid_declarations() {
+        let test_cases = [
+            (" <?xml version=\"1.0\"?><R/>", "XML declaration is allowed only at the start of the document at pos 1"),
+            ("<?xml version=\"1.0\"<R/>", "Markup is not closed at pos 0"),
+            ("<?xml?><R/>", "Invalid XML declaration: version is missing at pos 0"),
+            ("<?xml encoding=\"UTF-8\"?>", "Invalid XML declaration: version is missing at pos 0"),
+            ("<?xml version=\"2.0\"?>", "Invalid XML declaration: invalid version '2.0' at pos 0"),
+            ("<?xml version=1.0?>", "Invalid XML declaration: value of 'version' must be quoted at pos 0"),
+            ("<?xml version=\"1.0\"encoding=\"UTF-8\"?>", "Invalid XML declaration: whitespace is expected before 'encoding=\"UTF-8\"' at pos 0"),
+            ("<?xml version=\"1.0\" standalone=\"yes\" encoding=\"UTF-8\"?>", "Invalid XML declaration: attribute 'encoding' is duplicated or out of order at pos 0"),
+            ("<?xml version=\"1.0\" version=\"1.0\"?>", "Invalid XML declaration: attribute 'version' is duplicated or out of order at pos 0"),
+            ("<?xml version=\"1.0\" lang=\"en\"?>", "Invalid XML declaration: unknown attribute 'lang' at pos 0"),
+            ("<?xml version=\"1.0\" encoding=\"8bit\"?>", "Invalid XML declaration: invalid encoding name '8bit' at pos 0"),
+            ("<?xml version=\"1.0\" standalone=\"true\"?>", "Invalid XML declaration: standalone must be 'yes' or 'no' but it is 'true' at pos 0"),
+        ];
+
+        for (xml, expected) in test_cases {
+            let err = XmlDeclaration::parse(xml.as_bytes()).unwrap_err();
+            assert_eq!(expected, err.to_string(), "{}", xml);
+        }
+    }
+}
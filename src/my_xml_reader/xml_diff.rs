@@ -0,0 +1,607 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::xml_to_json::append_text;
+use super::{normalize_whitespace, XmlWhitespaceHandling};
+use super::{AttributesIterator, MyXmlReader, XmlTagInfo, XmlTagType};
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlDiffOptions {
+    // Text is compared with whitespace collapsed, so indentation and
+    // whitespace-only text between elements do not matter
+    pub ignore_whitespace: bool,
+    pub ignore_attribute_order: bool,
+    // Paths as in find_the_open_node, e.g. "Feed/Header/Timestamp", with or
+    // without positions. Attributes are given as "Feed/Item/@id".
+    pub ignored_paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlDiffKind {
+    ElementAdded,
+    ElementRemoved,
+    AttributeAdded {
+        name: String,
+        value: String,
+    },
+    AttributeRemoved {
+        name: String,
+        value: String,
+    },
+    AttributeChanged {
+        name: String,
+        old_value: String,
+        new_value: String,
+    },
+    AttributeOrderChanged,
+    TextChanged {
+        old_text: String,
+        new_text: String,
+    },
+}
+
+impl fmt::Display for XmlDiffKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ElementAdded => write!(f, "element is added"),
+            Self::ElementRemoved => write!(f, "element is removed"),
+            Self::AttributeAdded { name, value } => {
+                write!(f, "attribute {} is added: {:?}", name, value)
+            }
+            Self::AttributeRemoved { name, value } => {
+                write!(f, "attribute {} is removed: {:?}", name, value)
+            }
+            Self::AttributeChanged {
+                name,
+                old_value,
+                new_value,
+            } => write!(
+                f,
+                "attribute {} is changed: {:?} -> {:?}",
+                name, old_value, new_value
+            ),
+            Self::AttributeOrderChanged => write!(f, "order of attributes is changed"),
+            Self::TextChanged { old_text, new_text } => {
+                write!(f, "text is changed: {:?} -> {:?}", old_text, new_text)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlDifference {
+    // Path of the element. Elements with same-named siblings get their
+    // 1-based position among them: "Feed/Item[2]/Name".
+    pub path: String,
+    pub kind: XmlDiffKind,
+}
+
+impl fmt::Display for XmlDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+// Compares the root elements of two documents. Children are matched by name
+// and position among siblings of the same name, so the n-th <Item> of the old
+// document is compared with the n-th <Item> of the new one. Comments and
+// processing instructions are not compared.
+//
+// Each document is read once into memory and the trees are compared then.
+pub fn diff_xml(
+    old_xml: &[u8],
+    new_xml: &[u8],
+    options: &XmlDiffOptions,
+) -> Result<Vec<XmlDifference>, String> {
+    let mut old = MyXmlReader::from_slice(old_xml)?;
+    let mut new = MyXmlReader::from_slice(new_xml)?;
+
+    let old_root = read_next_element(&mut old)?;
+    let new_root = read_next_element(&mut new)?;
+
+    let mut differ = XmlDiffer {
+        options,
+        result: vec![],
+    };
+
+    if old_root.name == new_root.name {
+        let path = ElementPath {
+            path: old_root.name.to_string(),
+            plain_path: old_root.name.to_string(),
+        };
+
+        if !differ.is_ignored(&path.path, &path.plain_path) {
+            let old_root = read_element(&mut old, old_root, options)?;
+            let new_root = read_element(&mut new, new_root, options)?;
+
+            differ.compare_elements(&old_root, &new_root, &path);
+        }
+    } else {
+        differ.add(old_root.name.to_string(), XmlDiffKind::ElementRemoved);
+        differ.add(new_root.name.to_string(), XmlDiffKind::ElementAdded);
+    }
+
+    Ok(differ.result)
+}
+
+fn read_next_element<'t>(reader: &mut MyXmlReader<'t>) -> Result<XmlTagInfo<'t>, String> {
+    while let Some(tag) = reader.read_next_tag()? {
+        if !matches!(tag.tag_type, XmlTagType::Close) {
+            return Ok(tag);
+        }
+    }
+
+    Err("Document has no root element".to_string())
+}
+
+struct ElementPath {
+    path: String,
+    // The path without positions
+    plain_path: String,
+}
+
+struct DiffElement<'t> {
+    name: &'t str,
+    attributes: Vec<(&'t str, String)>,
+    // Direct text of the element without the text of its children
+    text: String,
+    children: Vec<DiffElement<'t>>,
+}
+
+impl<'t> DiffElement<'t> {
+    // Indexes of the children by their name
+    fn index_children(&self) -> HashMap<&'t str, Vec<usize>> {
+        let mut result: HashMap<&'t str, Vec<usize>> = HashMap::new();
+
+        for (index, child) in self.children.iter().enumerate() {
+            result.entry(child.name).or_default().push(index);
+        }
+
+        result
+    }
+}
+
+struct OpenedDiffElement<'t> {
+    element: DiffElement<'t>,
+    collapse_whitespace: bool,
+    content_start: usize,
+}
+
+struct XmlDiffer<'o> {
+    options: &'o XmlDiffOptions,
+    result: Vec<XmlDifference>,
+}
+
+impl<'o> XmlDiffer<'o> {
+    fn compare_elements(
+        &mut self,
+        old_element: &DiffElement,
+        new_element: &DiffElement,
+        path: &ElementPath,
+    ) {
+        self.compare_attributes(&old_element.attributes, &new_element.attributes, path);
+
+        if old_element.text != new_element.text {
+            self.add(
+                path.path.clone(),
+                XmlDiffKind::TextChanged {
+                    old_text: old_element.text.clone(),
+                    new_text: new_element.text.clone(),
+                },
+            );
+        }
+
+        let old_index = old_element.index_children();
+        let new_index = new_element.index_children();
+
+        // Positions of the children among the siblings of the same name
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+
+        for child in &old_element.children {
+            let position = next_position(&mut positions, child.name);
+            let child_path = get_child_path(path, child.name, position, &old_index, &new_index);
+
+            if self.is_ignored(&child_path.path, &child_path.plain_path) {
+                continue;
+            }
+
+            let new_child = new_index
+                .get(child.name)
+                .and_then(|indexes| indexes.get(position))
+                .map(|index| &new_element.children[*index]);
+
+            match new_child {
+                Some(new_child) => self.compare_elements(child, new_child, &child_path),
+                None => self.add(child_path.path, XmlDiffKind::ElementRemoved),
+            }
+        }
+
+        positions.clear();
+
+        for child in &new_element.children {
+            let position = next_position(&mut positions, child.name);
+
+            if position < old_index.get(child.name).map_or(0, |indexes| indexes.len()) {
+                continue;
+            }
+
+            let child_path = get_child_path(path, child.name, position, &new_index, &old_index);
+
+            if !self.is_ignored(&child_path.path, &child_path.plain_path) {
+                self.add(child_path.path, XmlDiffKind::ElementAdded);
+            }
+        }
+    }
+
+    fn compare_attributes(
+        &mut self,
+        old_attributes: &[(&str, String)],
+        new_attributes: &[(&str, String)],
+        path: &ElementPath,
+    ) {
+        let is_ignored = |name: &str| {
+            self.is_ignored(
+                &format!("{}/@{}", path.path, name),
+                &format!("{}/@{}", path.plain_path, name),
+            )
+        };
+
+        let old_attributes: Vec<&(&str, String)> = old_attributes
+            .iter()
+            .filter(|(name, _)| !is_ignored(name))
+            .collect();
+
+        let new_attributes: Vec<&(&str, String)> = new_attributes
+            .iter()
+            .filter(|(name, _)| !is_ignored(name))
+            .collect();
+
+        let mut differences = vec![];
+
+        for (name, old_value) in &old_attributes {
+            match new_attributes.iter().find(|(n, _)| n == name) {
+                Some((_, new_value)) if new_value != old_value => {
+                    differences.push(XmlDiffKind::AttributeChanged {
+                        name: name.to_string(),
+                        old_value: old_value.clone(),
+                        new_value: new_value.clone(),
+                    })
+                }
+                Some(_) => {}
+                None => differences.push(XmlDiffKind::AttributeRemoved {
+                    name: name.to_string(),
+                    value: old_value.clone(),
+                }),
+            }
+        }
+
+        for (name, value) in &new_attributes {
+            if !old_attributes.iter().any(|(n, _)| n == name) {
+                differences.push(XmlDiffKind::AttributeAdded {
+                    name: name.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+
+        if !self.options.ignore_attribute_order {
+            let common_in_old = old_attributes
+                .iter()
+                .map(|(name, _)| name)
+                .filter(|name| new_attributes.iter().any(|(n, _)| n == *name));
+
+            let common_in_new = new_attributes
+                .iter()
+                .map(|(name, _)| name)
+                .filter(|name| old_attributes.iter().any(|(n, _)| n == *name));
+
+            if !common_in_old.eq(common_in_new) {
+                differences.push(XmlDiffKind::AttributeOrderChanged);
+            }
+        }
+
+        for kind in differences {
+            self.add(path.path.clone(), kind);
+        }
+    }
+
+    fn is_ignored(&self, path: &str, plain_path: &str) -> bool {
+        self.options
+            .ignored_paths
+            .iter()
+            .any(|ignored| ignored == path || ignored == plain_path)
+    }
+
+    fn add(&mut self, path: String, kind: XmlDiffKind) {
+        self.result.push(XmlDifference { path, kind });
+    }
+}
+
+fn next_position<'t>(positions: &mut HashMap<&'t str, usize>, name: &'t str) -> usize {
+    let position = positions.entry(name).or_default();
+    *position += 1;
+    *position - 1
+}
+
+// The position is added when there are several children of the same name in
+// either of the documents
+fn get_child_path(
+    parent: &ElementPath,
+    name: &str,
+    position: usize,
+    index: &HashMap<&str, Vec<usize>>,
+    other_index: &HashMap<&str, Vec<usize>>,
+) -> ElementPath {
+    let count_named = |index: &HashMap<&str, Vec<usize>>| index.get(name).map_or(0, Vec::len);
+    let plain_path = format!("{}/{}", parent.plain_path, name);
+
+    if count_named(index) > 1 || count_named(other_index) > 1 {
+        ElementPath {
+            path: format!("{}/{}[{}]", parent.path, name, position + 1),
+            plain_path,
+        }
+    } else {
+        ElementPath {
+            path: format!("{}/{}", parent.path, name),
+            plain_path,
+        }
+    }
+}
+
+// Reads the element up to its end with all its descendants. Opened elements
+// are kept on a stack rather than in recursive calls.
+fn read_element<'t>(
+    reader: &mut MyXmlReader<'t>,
+    tag: XmlTagInfo<'t>,
+    options: &XmlDiffOptions,
+) -> Result<DiffElement<'t>, String> {
+    let element = read_attributes(reader, &tag)?;
+
+    if matches!(tag.tag_type, XmlTagType::OpenClose) {
+        return Ok(element);
+    }
+
+    let mut opened = vec![OpenedDiffElement {
+        element,
+        collapse_whitespace: options.ignore_whitespace && !reader.is_space_preserved(&tag)?,
+        content_start: tag.end_pos + 1,
+    }];
+
+    loop {
+        let Some(current) = opened.last_mut() else {
+            return Err(format!("Element <{}> is not opened", tag.name));
+        };
+
+        let Some(next_tag) = reader.read_next_tag()? else {
+            return Err(format!("Element <{}> is not closed", current.element.name));
+        };
+
+        append_text(
+            reader,
+            &reader.xml[current.content_start..next_tag.start_pos],
+            &mut current.element.text,
+        )?;
+
+        current.content_start = next_tag.end_pos + 1;
+
+        match next_tag.tag_type {
+            XmlTagType::Open => opened.push(OpenedDiffElement {
+                element: read_attributes(reader, &next_tag)?,
+                collapse_whitespace: options.ignore_whitespace
+                    && !reader.is_space_preserved(&next_tag)?,
+                content_start: next_tag.end_pos + 1,
+            }),
+            XmlTagType::OpenClose => {
+                let child = read_attributes(reader, &next_tag)?;
+                current.element.children.push(child);
+            }
+            XmlTagType::Close => {
+                let Some(mut closed) = opened.pop() else {
+                    return Err(format!("Element <{}> is not opened", next_tag.name));
+                };
+
+                if closed.collapse_whitespace {
+                    closed.element.text =
+                        normalize_whitespace(&closed.element.text, XmlWhitespaceHandling::Collapse)
+                            .into_owned();
+                }
+
+                match opened.last_mut() {
+                    Some(parent) => {
+                        parent.content_start = next_tag.end_pos + 1;
+                        parent.element.children.push(closed.element);
+                    }
+                    None => return Ok(closed.element),
+                }
+            }
+        }
+    }
+}
+
+fn read_attributes<'t>(
+    reader: &MyXmlReader<'t>,
+    tag: &XmlTagInfo<'t>,
+) -> Result<DiffElement<'t>, String> {
+    let mut result = DiffElement {
+        name: tag.name,
+        attributes: vec![],
+        text: String::new(),
+        children: vec![],
+    };
+
+    let mut attributes = AttributesIterator::new(tag.raw);
+
+    while let Some((name, value)) = attributes.get_next()? {
+        result
+            .attributes
+            .push((name, reader.expand_entities(value)?));
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str, options: &XmlDiffOptions) -> Vec<String> {
+        diff_xml(old.as_bytes(), new.as_bytes(), options)
+            .unwrap()
+            .iter()
+            .map(|difference| difference.to_string())
+            .collect()
+    }
+
+    fn ignoring_whitespace() -> XmlDiffOptions {
+        XmlDiffOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        }
+    }
+
+    const OLD: &str = r#"<?xml version="1.0"?>
+<Response status="ok" version="1">
+  <Header>
+    <Timestamp>2024-01-01T10:00:00</Timestamp>
+  </Header>
+  <Item id="1"><Name>First</Name><Price currency="EUR">10</Price></Item>
+  <Item id="2"><Name>Second</Name></Item>
+  <Note>Obsolete</Note>
+</Response>"#;
+
+    #[test]
+    fn test_diff_xml() {
+        let new = r#"<Response version="2" status="ok">
+  <!-- re-generated -->
+  <Header>
+    <Timestamp>2024-01-02T11:00:00</Timestamp>
+  </Header>
+  <Item id="1"><Name>First</Name><Price currency="USD" discount="5">10</Price></Item>
+  <Item id="2"><Name>Second &amp; more</Name></Item>
+  <Item id="3"><Name>Third</Name></Item>
+</Response>"#;
+
+        assert_eq!(
+            vec![
+                r#"Response: attribute version is changed: "1" -> "2""#,
+                "Response: order of attributes is changed",
+                r#"Response/Header/Timestamp: text is changed: "2024-01-01T10:00:00" -> "2024-01-02T11:00:00""#,
+                r#"Response/Item[1]/Price: attribute currency is changed: "EUR" -> "USD""#,
+                r#"Response/Item[1]/Price: attribute discount is added: "5""#,
+                r#"Response/Item[2]/Name: text is changed: "Second" -> "Second & more""#,
+                "Response/Note: element is removed",
+                "Response/Item[3]: element is added",
+            ],
+            diff(OLD, new, &ignoring_whitespace())
+        );
+
+        let options = XmlDiffOptions {
+            ignore_attribute_order: true,
+            ignored_paths: vec![
+                "Response/Header/Timestamp".to_string(),
+                "Response/Item/Price/@currency".to_string(),
+                "Response/Item[3]".to_string(),
+                "Response/Note".to_string(),
+                "Response/@version".to_string(),
+            ],
+            ..ignoring_whitespace()
+        };
+
+        assert_eq!(
+            vec![
+                r#"Response/Item[1]/Price: attribute discount is added: "5""#,
+                r#"Response/Item[2]/Name: text is changed: "Second" -> "Second & more""#,
+            ],
+            diff(OLD, new, &options)
+        );
+    }
+
+    #[test]
+    fn test_same_documents() {
+        assert!(diff(OLD, OLD, &XmlDiffOptions::default()).is_empty());
+
+        // CDATA, entities and quotes are compared by value
+        assert!(diff(
+            "<R a='1'><![CDATA[a < b]]></R>",
+            "<R a=\"1\">a &lt; b</R>",
+            &XmlDiffOptions::default()
+        )
+        .is_empty());
+
+        assert!(diff("<R/>", "<R></R>", &XmlDiffOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let old = "<R><A> x  y </A><B/></R>";
+        let new = "<R>\n  <A>x y</A>\n  <B/>\n</R>";
+
+        assert_eq!(
+            vec![
+                r#"R: text is changed: "" -> "\n  \n  \n""#,
+                r#"R/A: text is changed: " x  y " -> "x y""#,
+            ],
+            diff(old, new, &XmlDiffOptions::default())
+        );
+
+        assert!(diff(old, new, &ignoring_whitespace()).is_empty());
+
+        // Whitespace of preserved text still matters
+        let old = r#"<R xml:space="preserve"><A> x </A></R>"#;
+        let new = r#"<R xml:space="preserve"><A>x</A></R>"#;
+
+        assert_eq!(
+            vec![r#"R/A: text is changed: " x " -> "x""#],
+            diff(old, new, &ignoring_whitespace())
+        );
+    }
+
+    #[test]
+    fn test_elements_are_matched_by_name_and_position() {
+        let old = "<R><A>1</A><B/><A>2</A></R>";
+        let new = "<R><B/><A>1</A></R>";
+
+        assert_eq!(
+            vec!["R/A[2]: element is removed"],
+            diff(old, new, &XmlDiffOptions::default())
+        );
+
+        assert_eq!(
+            vec!["R/A[2]: element is added"],
+            diff(new, old, &XmlDiffOptions::default())
+        );
+
+        assert_eq!(
+            vec!["Old: element is removed", "New: element is added"],
+            diff("<Old/>", "<New/>", &XmlDiffOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_many_siblings_and_deep_nesting() {
+        let items: Vec<String> = (0..20_000).map(|i| format!("<A>{}</A><B/>", i)).collect();
+        let old = format!("<R>{}</R>", items.concat());
+
+        let mut items = items;
+        items[15_000] = "<A>changed</A><B/>".to_string();
+        items.push("<C/>".to_string());
+        let new = format!("<R>{}</R>", items.concat());
+
+        assert_eq!(
+            vec![
+                r#"R/A[15001]: text is changed: "15000" -> "changed""#,
+                "R/C: element is added",
+            ],
+            diff(&old, &new, &XmlDiffOptions::default())
+        );
+
+        let depth = 500;
+        let old = format!("{}x{}", "<A><B/>".repeat(depth), "</A>".repeat(depth));
+        let new = format!("{}y{}", "<A><B/>".repeat(depth), "</A>".repeat(depth));
+
+        let differences = diff(&old, &new, &XmlDiffOptions::default());
+
+        assert_eq!(1, differences.len());
+        assert!(differences[0].ends_with(r#"/A/A: text is changed: "x" -> "y""#));
+    }
+}
@@ -0,0 +1,807 @@
+use std::ops::Range;
+
+use base64::Engine;
+use p256::ecdsa::signature::Verifier;
+use rsa::pkcs8::DecodePublicKey;
+use sha2::{Digest, Sha256};
+
+use super::{canonicalize_document, canonicalize_element, C14nMethod, C14nOptions};
+use super::{AttributesIterator, MyXmlNode, MyXmlReader, XmlTagInfo, XmlTagType};
+
+pub const XMLDSIG_NAMESPACE: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+const C14N: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315";
+const C14N_WITH_COMMENTS: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments";
+const EXCLUSIVE_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const EXCLUSIVE_C14N_WITH_COMMENTS: &str = "http://www.w3.org/2001/10/xml-exc-c14n#WithComments";
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+const SHA256: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+const RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+const ECDSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256";
+
+// Attributes which are looked up when a "#id" reference is resolved
+const ID_ATTRIBUTES: [&str; 3] = ["Id", "ID", "id"];
+
+pub enum XmlDsigPublicKey {
+    Rsa(rsa::RsaPublicKey),
+    EcdsaP256(p256::ecdsa::VerifyingKey),
+}
+
+impl XmlDsigPublicKey {
+    // Reads a "BEGIN PUBLIC KEY" (SubjectPublicKeyInfo) PEM with an RSA or a
+    // P-256 key
+    pub fn from_public_key_pem(pem: &str) -> Result<Self, String> {
+        if let Ok(key) = rsa::RsaPublicKey::from_public_key_pem(pem) {
+            return Ok(Self::Rsa(key));
+        }
+
+        match p256::ecdsa::VerifyingKey::from_public_key_pem(pem) {
+            Ok(key) => Ok(Self::EcdsaP256(key)),
+            Err(err) => Err(format!(
+                "Public key is neither an RSA nor a P-256 key: {}",
+                err
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum XmlDsigTransform {
+    EnvelopedSignature,
+    Canonicalization(C14nOptions),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlSignatureReference {
+    pub uri: String,
+    pub transforms: Vec<XmlDsigTransform>,
+    pub digest_method: String,
+    pub digest_value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlSignature {
+    // From the start of <Signature> to right after its end
+    pub range: Range<usize>,
+    pub signed_info_pos: usize,
+    pub canonicalization: C14nOptions,
+    pub signature_method: String,
+    pub references: Vec<XmlSignatureReference>,
+    pub signature_value: Vec<u8>,
+}
+
+// Finds all <Signature> elements of the XML signature namespace. Signatures
+// inside <Object> of another signature are not looked for.
+pub fn find_signatures(xml: &[u8]) -> Result<Vec<XmlSignature>, String> {
+    let mut reader = MyXmlReader::from_slice(xml)?;
+    let mut result = vec![];
+
+    while let Some(tag) = reader.read_next_tag()? {
+        if !matches!(tag.tag_type, XmlTagType::Close)
+            && is_dsig_element(&reader, &tag, "Signature")?
+        {
+            result.push(read_signature(&mut reader, tag)?);
+        }
+    }
+
+    Ok(result)
+}
+
+// Resolves a same-document reference. "" is the document element and "#id"
+// is the element with the Id (ID or id) attribute, which must be unique.
+pub fn resolve_reference<'t>(xml: &'t [u8], uri: &str) -> Result<MyXmlNode<'t>, String> {
+    let mut reader = MyXmlReader::from_slice(xml)?;
+    let tag = find_referenced_tag(&mut reader, uri)?;
+    reader.read_the_whole_node(tag)
+}
+
+// Checks digests of all the references and then the signature value of
+// <SignedInfo>. Returns the signed nodes, which are the only parts of the
+// document the signature vouches for.
+pub fn verify_signature<'t>(
+    xml: &'t [u8],
+    signature: &XmlSignature,
+    key: &XmlDsigPublicKey,
+) -> Result<Vec<MyXmlNode<'t>>, String> {
+    if signature.references.is_empty() {
+        return Err("Signature has no references".to_string());
+    }
+
+    let mut signed_nodes = vec![];
+
+    for reference in &signature.references {
+        verify_reference(xml, signature, reference)?;
+        signed_nodes.push(resolve_reference(xml, &reference.uri)?);
+    }
+
+    let mut reader = MyXmlReader::from_slice(xml)?;
+    let signed_info = read_to_tag(&mut reader, signature.signed_info_pos)?;
+
+    let mut canonical_signed_info = vec![];
+    canonicalize_element(
+        &mut reader,
+        signed_info,
+        &signature.canonicalization,
+        &mut canonical_signed_info,
+    )?;
+
+    let is_valid = match (signature.signature_method.as_str(), key) {
+        (RSA_SHA256, XmlDsigPublicKey::Rsa(key)) => key
+            .verify(
+                rsa::Pkcs1v15Sign::new::<Sha256>(),
+                &Sha256::digest(&canonical_signed_info),
+                &signature.signature_value,
+            )
+            .is_ok(),
+        (ECDSA_SHA256, XmlDsigPublicKey::EcdsaP256(key)) => {
+            // XML signatures keep r and s as two fixed size integers
+            let Ok(value) = p256::ecdsa::Signature::from_slice(&signature.signature_value) else {
+                return Err("ECDSA signature value must be 64 bytes long".to_string());
+            };
+
+            key.verify(&canonical_signed_info, &value).is_ok()
+        }
+        (RSA_SHA256 | ECDSA_SHA256, _) => {
+            return Err(format!(
+                "Public key does not match signature method '{}'",
+                signature.signature_method
+            ))
+        }
+        (method, _) => return Err(format!("Signature method '{}' is not supported", method)),
+    };
+
+    if !is_valid {
+        return Err("Signature value is not valid".to_string());
+    }
+
+    Ok(signed_nodes)
+}
+
+fn verify_reference(
+    xml: &[u8],
+    signature: &XmlSignature,
+    reference: &XmlSignatureReference,
+) -> Result<(), String> {
+    if reference.digest_method != SHA256 {
+        return Err(format!(
+            "Digest method '{}' is not supported",
+            reference.digest_method
+        ));
+    }
+
+    let mut options = C14nOptions::default();
+
+    for transform in &reference.transforms {
+        match transform {
+            XmlDsigTransform::EnvelopedSignature => {
+                options.excluded_element = Some(signature.range.start)
+            }
+            XmlDsigTransform::Canonicalization(c14n) => {
+                options = C14nOptions {
+                    excluded_element: options.excluded_element,
+                    ..c14n.clone()
+                }
+            }
+        }
+    }
+
+    // Comments are removed from same-document references before they are
+    // canonicalized
+    options.with_comments = false;
+
+    let mut reader = MyXmlReader::from_slice(xml)?;
+    let mut canonical = vec![];
+
+    if reference.uri.is_empty() {
+        canonicalize_document(&mut reader, &options, &mut canonical)?;
+    } else {
+        let tag = find_referenced_tag(&mut reader, &reference.uri)?;
+        canonicalize_element(&mut reader, tag, &options, &mut canonical)?;
+    }
+
+    if Sha256::digest(&canonical)[..] != reference.digest_value[..] {
+        return Err(format!(
+            "Digest of reference '{}' does not match",
+            reference.uri
+        ));
+    }
+
+    Ok(())
+}
+
+fn read_signature<'t>(
+    reader: &mut MyXmlReader<'t>,
+    signature_tag: XmlTagInfo<'t>,
+) -> Result<XmlSignature, String> {
+    let mut result = XmlSignature {
+        range: signature_tag.start_pos..signature_tag.end_pos + 1,
+        ..Default::default()
+    };
+
+    if matches!(signature_tag.tag_type, XmlTagType::OpenClose) {
+        return Err(format!(
+            "Signature at pos {} is empty",
+            signature_tag.start_pos
+        ));
+    }
+
+    let mut has_signed_info = false;
+
+    loop {
+        let Some(tag) = reader.read_next_tag()? else {
+            return Err("Signature is not closed".to_string());
+        };
+
+        if let XmlTagType::Close = tag.tag_type {
+            if tag.level <= signature_tag.level {
+                if !has_signed_info {
+                    return Err(format!(
+                        "Signature at pos {} has no SignedInfo",
+                        signature_tag.start_pos
+                    ));
+                }
+
+                result.range.end = tag.end_pos + 1;
+                return Ok(result);
+            }
+
+            continue;
+        }
+
+        match get_local_name(tag.name) {
+            "Signature" => {
+                return Err(format!(
+                    "Signature at pos {} is inside another signature",
+                    tag.start_pos
+                ))
+            }
+            "SignedInfo" => {
+                check_dsig_parent(reader, &tag, "Signature")?;
+
+                if has_signed_info {
+                    return Err(format!(
+                        "Signature at pos {} has more than one SignedInfo",
+                        signature_tag.start_pos
+                    ));
+                }
+
+                has_signed_info = true;
+                result.signed_info_pos = tag.start_pos;
+            }
+            "CanonicalizationMethod" => {
+                check_dsig_parent(reader, &tag, "SignedInfo")?;
+                let algorithm = get_algorithm(reader, &tag)?;
+
+                result.canonicalization = get_c14n_options(&algorithm).ok_or_else(|| {
+                    format!("Canonicalization method '{}' is not supported", algorithm)
+                })?;
+            }
+            "SignatureMethod" => {
+                check_dsig_parent(reader, &tag, "SignedInfo")?;
+                result.signature_method = get_algorithm(reader, &tag)?;
+            }
+            "Reference" => {
+                check_dsig_parent(reader, &tag, "SignedInfo")?;
+
+                result.references.push(XmlSignatureReference {
+                    uri: reader.get_attribute_value(&tag, "URI")?.unwrap_or_default(),
+                    ..Default::default()
+                });
+            }
+            "Transforms" => check_dsig_parent(reader, &tag, "Reference")?,
+            "Transform" => {
+                check_dsig_parent(reader, &tag, "Transforms")?;
+                let algorithm = get_algorithm(reader, &tag)?;
+
+                let transform = if algorithm == ENVELOPED_SIGNATURE {
+                    XmlDsigTransform::EnvelopedSignature
+                } else if let Some(options) = get_c14n_options(&algorithm) {
+                    XmlDsigTransform::Canonicalization(options)
+                } else {
+                    return Err(format!("Transform '{}' is not supported", algorithm));
+                };
+
+                get_last_reference(&mut result)?.transforms.push(transform);
+            }
+            "InclusiveNamespaces" => {
+                let prefixes: Vec<String> = reader
+                    .get_attribute_value(&tag, "PrefixList")?
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(|prefix| prefix.to_string())
+                    .collect();
+
+                let parent = reader.opened_nodes.get(tag.level.wrapping_sub(1));
+
+                let options = match parent.map(|node| get_local_name(node.name)) {
+                    Some("CanonicalizationMethod") => &mut result.canonicalization,
+                    Some("Transform") => {
+                        match get_last_reference(&mut result)?.transforms.last_mut() {
+                            Some(XmlDsigTransform::Canonicalization(options)) => options,
+                            _ => {
+                                return Err("InclusiveNamespaces are not expected here".to_string())
+                            }
+                        }
+                    }
+                    _ => return Err("InclusiveNamespaces are not expected here".to_string()),
+                };
+
+                options.inclusive_prefixes = prefixes;
+            }
+            "DigestMethod" => {
+                check_dsig_parent(reader, &tag, "Reference")?;
+                let algorithm = get_algorithm(reader, &tag)?;
+                get_last_reference(&mut result)?.digest_method = algorithm;
+            }
+            "DigestValue" => {
+                check_dsig_parent(reader, &tag, "Reference")?;
+                let value = read_base64_value(reader, tag)?;
+                get_last_reference(&mut result)?.digest_value = value;
+            }
+            "SignatureValue" => {
+                check_dsig_parent(reader, &tag, "Signature")?;
+                result.signature_value = read_base64_value(reader, tag)?;
+            }
+            "KeyInfo" | "Object" => {
+                check_dsig_parent(reader, &tag, "Signature")?;
+                reader.skip_node(&tag)?;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Elements of the signature are accepted only where the XML signature schema
+// puts them. Otherwise a <Reference> added after <SignedInfo>, which the
+// signature value does not cover, would be taken for a signed one. Nested
+// signatures are rejected, so a <Signature> parent is the one being read.
+fn check_dsig_parent(
+    reader: &MyXmlReader,
+    tag: &XmlTagInfo,
+    parent_local_name: &str,
+) -> Result<(), String> {
+    let parent = reader.opened_nodes.get(tag.level.wrapping_sub(1));
+
+    if parent.map(|node| get_local_name(node.name)) != Some(parent_local_name)
+        || !is_dsig_element(reader, tag, get_local_name(tag.name))?
+    {
+        return Err(format!(
+            "<{}> at pos {} is not expected here",
+            tag.name, tag.start_pos
+        ));
+    }
+
+    Ok(())
+}
+
+fn get_last_reference(signature: &mut XmlSignature) -> Result<&mut XmlSignatureReference, String> {
+    signature
+        .references
+        .last_mut()
+        .ok_or_else(|| "Reference is expected".to_string())
+}
+
+fn get_c14n_options(algorithm: &str) -> Option<C14nOptions> {
+    let (method, with_comments) = match algorithm {
+        C14N => (C14nMethod::Inclusive, false),
+        C14N_WITH_COMMENTS => (C14nMethod::Inclusive, true),
+        EXCLUSIVE_C14N => (C14nMethod::Exclusive, false),
+        EXCLUSIVE_C14N_WITH_COMMENTS => (C14nMethod::Exclusive, true),
+        _ => return None,
+    };
+
+    Some(C14nOptions {
+        method,
+        with_comments,
+        ..Default::default()
+    })
+}
+
+fn get_algorithm(reader: &MyXmlReader, tag: &XmlTagInfo) -> Result<String, String> {
+    reader
+        .get_attribute_value(tag, "Algorithm")?
+        .ok_or_else(|| format!("<{}> has no Algorithm attribute", tag.name))
+}
+
+fn read_base64_value<'t>(
+    reader: &mut MyXmlReader<'t>,
+    tag: XmlTagInfo<'t>,
+) -> Result<Vec<u8>, String> {
+    let node = reader.read_the_whole_node(tag)?;
+    let value = reader.get_node_value(&node)?.unwrap_or_default();
+    let value: String = value.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+
+    base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|err| format!("<{}> is not valid base64: {}", node.get_node_name(), err))
+}
+
+fn find_referenced_tag<'t>(
+    reader: &mut MyXmlReader<'t>,
+    uri: &str,
+) -> Result<XmlTagInfo<'t>, String> {
+    if uri.is_empty() {
+        while let Some(tag) = reader.read_next_tag()? {
+            if !matches!(tag.tag_type, XmlTagType::Close) {
+                return Ok(tag);
+            }
+        }
+
+        return Err("Document has no root element".to_string());
+    }
+
+    let Some(id) = uri.strip_prefix('#') else {
+        return Err(format!(
+            "Reference '{}' is not supported. Only same-document references are",
+            uri
+        ));
+    };
+
+    let mut found = None;
+    let mut search = reader.clone();
+
+    while let Some(tag) = search.read_next_tag()? {
+        if matches!(tag.tag_type, XmlTagType::Close) || !has_id(&search, &tag, id)? {
+            continue;
+        }
+
+        if found.is_some() {
+            return Err(format!("Id '{}' is used by more than one element", id));
+        }
+
+        found = Some(tag.start_pos);
+    }
+
+    match found {
+        Some(pos) => read_to_tag(reader, pos),
+        None => Err(format!("Element with Id '{}' is not found", id)),
+    }
+}
+
+fn has_id(reader: &MyXmlReader, tag: &XmlTagInfo, id: &str) -> Result<bool, String> {
+    for name in ID_ATTRIBUTES {
+        if reader.get_attribute_value(tag, name)?.as_deref() == Some(id) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn read_to_tag<'t>(reader: &mut MyXmlReader<'t>, pos: usize) -> Result<XmlTagInfo<'t>, String> {
+    while let Some(tag) = reader.read_next_tag()? {
+        if tag.start_pos == pos {
+            return Ok(tag);
+        }
+    }
+
+    Err(format!("There is no tag at pos {}", pos))
+}
+
+fn is_dsig_element(
+    reader: &MyXmlReader,
+    tag: &XmlTagInfo,
+    local_name: &str,
+) -> Result<bool, String> {
+    if get_local_name(tag.name) != local_name {
+        return Ok(false);
+    }
+
+    Ok(get_namespace(reader, tag)?.as_deref() == Some(XMLDSIG_NAMESPACE))
+}
+
+fn get_local_name(name: &str) -> &str {
+    name.split_once(':')
+        .map_or(name, |(_, local_name)| local_name)
+}
+
+// Looks up the namespace of the tag name in the tag itself and then in its
+// opened ancestors
+fn get_namespace(reader: &MyXmlReader, tag: &XmlTagInfo) -> Result<Option<String>, String> {
+    let declaration = match tag.name.split_once(':') {
+        Some((prefix, _)) => format!("xmlns:{}", prefix),
+        None => "xmlns".to_string(),
+    };
+
+    if let Some(uri) = reader.get_attribute_value(tag, &declaration)? {
+        return Ok(Some(uri));
+    }
+
+    let ancestors = &reader.opened_nodes[..tag.level.min(reader.opened_nodes.len())];
+
+    for node in ancestors.iter().rev() {
+        let mut attributes = AttributesIterator::new(&reader.xml[node.start_pos..=node.end_pos]);
+
+        while let Some((name, value)) = attributes.get_next()? {
+            if name == declaration {
+                return Ok(Some(reader.expand_entities(value)?));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixtures are signed with keys generated for these tests. Their private
+    // parts are not kept.
+
+    const RSA_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----\n\
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEApNzlEsK+oqko5O/ehVUb\n\
+lA+NZcc9liMnCIDPP5UpzTdamEoQViFSGAM79wO0de4ogqIz8Mc+fmSJLZ5nOH+c\n\
+eP9ulEjO9M8dE/Zrikcb3PGfr6Bx5ofMwu0pBz2h2bsSXlx01Mm9vvyF3Do7E3cT\n\
+s4MGsSTTsLay74ReCg5RtATbtJa5XPLgFFeM6zvyaD0j0e9ovofBfFWYYNAiysN9\n\
+HXjp7NbNYv1jBhdC65NT/tWe3E35c13/GUIVLRt7D1IE1eGID6IcJ1H/5xcocgyT\n\
+/0XacxWwULrJ7UJrJyeC53yAS1SVmbZhZ0vlddkV9KCjxtDdhEsvsAqeFD/EecGn\n\
+IQIDAQAB\n\
+-----END PUBLIC KEY-----\n\
+";
+
+    const EC_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----\n\
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE8k94mK7tiu0CYmTVUUvExAhq76t5\n\
+UUzqcBTi7MpvEsDPZ/oq4J6CZ0lL8YeOzfIuYHCs+WSQ28iJfvyaUrDlLQ==\n\
+-----END PUBLIC KEY-----\n\
+";
+
+    const OTHER_EC_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----\n\
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEawRFVKaJi/C0a/I2pJ5cyaSBgs+Q\n\
+VUnBjDVPVCSSPWsogovKx58GnGwExLjKWFv/918iB/7UFEaD6GIfOOS/Jg==\n\
+-----END PUBLIC KEY-----\n\
+";
+
+    // Exclusive canonicalization of the element referenced by Id, with the
+    // enveloped signature
+    const ORDER_SIGNED_WITH_RSA: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<Envelope xmlns="urn:example:envelope" xmlns:x="urn:example:unused">
+  <Order xmlns="urn:example:order" Id="order-1">
+    <Item sku="A-1">Widget &amp; Co</Item>
+    <Total currency="EUR">10.50</Total>
+    <Empty/>
+    <ds:Signature xmlns:ds="http://www.w3.org/2000/09/xmldsig#">
+    <ds:SignedInfo>
+      <ds:CanonicalizationMethod Algorithm="http://www.w3.org/2001/10/xml-exc-c14n#"/>
+      <ds:SignatureMethod Algorithm="http://www.w3.org/2001/04/xmldsig-more#rsa-sha256"/>
+      <ds:Reference URI="#order-1">
+        <ds:Transforms>
+          <ds:Transform Algorithm="http://www.w3.org/2000/09/xmldsig#enveloped-signature"/>
+          <ds:Transform Algorithm="http://www.w3.org/2001/10/xml-exc-c14n#"/>
+        </ds:Transforms>
+        <ds:DigestMethod Algorithm="http://www.w3.org/2001/04/xmlenc#sha256"/>
+        <ds:DigestValue>V34MQKP9qiu4YfnjaL2NO1tG3yntvDyO+Q4cTvrEQBQ=</ds:DigestValue>
+      </ds:Reference>
+    </ds:SignedInfo>
+      <ds:SignatureValue>
+      mkTFba33jUBdSaFAqhkkf2LFz2CuVIxfNs+U2rZkktl11Y1zj5s+oEK6ziB2vzZ3
+      4R6Ha3XhkBfP0tcOHSfniVCGdVFhVfBLXMg+EnuFKuhI+zaJQJ20V1AF5Dc6jW4s
+      OxbOTqncxpLEc+SMH/ihaS1zY0wO5dKBb8SoLqEMPEOLrBGFqOJSj5p21SNrRebs
+      S39OBD2UBJ4AK3gRP/F0vZ5Y8fjNmr3v7JdfRbYNCIe0rkZwNulQfDhl9u/rEJQa
+      Nc/e6V2hGehw34u4B2T0mLugRxF6F/O0sBA+8y5hjUU1A5b5ZbIhGZILS+zzzNEE
+      6mB0TAnz1SYWEz7tzv4Syw==
+      </ds:SignatureValue>
+    </ds:Signature>
+  </Order>
+</Envelope>"##;
+
+    // Inclusive canonicalization of the whole document
+    const INVOICE_SIGNED_WITH_ECDSA: &str = r##"<?xml version="1.0"?>
+<!-- header comment -->
+<Invoice xmlns="urn:example:invoice" Id="inv-7">
+  <!-- line items -->
+  <Line no="1">Service</Line>
+  <Signature xmlns="http://www.w3.org/2000/09/xmldsig#">
+    <SignedInfo>
+      <CanonicalizationMethod Algorithm="http://www.w3.org/TR/2001/REC-xml-c14n-20010315"/>
+      <SignatureMethod Algorithm="http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256"/>
+      <Reference URI="">
+        <Transforms>
+          <Transform Algorithm="http://www.w3.org/2000/09/xmldsig#enveloped-signature"/>
+        </Transforms>
+        <DigestMethod Algorithm="http://www.w3.org/2001/04/xmlenc#sha256"/>
+        <DigestValue>EUZepGRJDBddCXLSpNjCI6jg3+tz5XKU3f9Hoxk5F60=</DigestValue>
+      </Reference>
+    </SignedInfo>
+    <SignatureValue>pWwZ46ZJi2JemUyyfiEXqeCmVmWQ779w8Pl5g08UU7XkE+86WLnJIdZ6YlQf79+8h1WyzwH7ZdH/rY/lPVCjbA==</SignatureValue>
+  </Signature>
+</Invoice>"##;
+
+    fn verify(xml: &str, key: &str) -> Result<Vec<String>, String> {
+        let signatures = find_signatures(xml.as_bytes())?;
+        assert_eq!(1, signatures.len());
+
+        let key = XmlDsigPublicKey::from_public_key_pem(key)?;
+        let signed_nodes = verify_signature(xml.as_bytes(), &signatures[0], &key)?;
+
+        Ok(signed_nodes
+            .iter()
+            .map(|node| node.get_node_name().to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_find_signatures() {
+        let signatures = find_signatures(ORDER_SIGNED_WITH_RSA.as_bytes()).unwrap();
+        assert_eq!(1, signatures.len());
+
+        let signature = &signatures[0];
+
+        assert!(ORDER_SIGNED_WITH_RSA[signature.range.clone()].starts_with("<ds:Signature "));
+        assert!(ORDER_SIGNED_WITH_RSA[signature.range.clone()].ends_with("</ds:Signature>"));
+        assert!(ORDER_SIGNED_WITH_RSA[signature.signed_info_pos..].starts_with("<ds:SignedInfo>"));
+        assert_eq!(C14nMethod::Exclusive, signature.canonicalization.method);
+        assert_eq!(RSA_SHA256, signature.signature_method);
+        assert_eq!(256, signature.signature_value.len());
+
+        let reference = &signature.references[0];
+
+        assert_eq!("#order-1", reference.uri);
+        assert!(matches!(
+            reference.transforms[..],
+            [
+                XmlDsigTransform::EnvelopedSignature,
+                XmlDsigTransform::Canonicalization(C14nOptions {
+                    method: C14nMethod::Exclusive,
+                    with_comments: false,
+                    ..
+                })
+            ]
+        ));
+        assert_eq!(SHA256, reference.digest_method);
+        assert_eq!(32, reference.digest_value.len());
+
+        // Signature elements of other namespaces are not XML signatures
+        let xml = r#"<R xmlns:ds="urn:other"><ds:Signature/><Signature/></R>"#;
+        assert!(find_signatures(xml.as_bytes()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_rsa_signature() {
+        assert_eq!(
+            vec!["Order".to_string()],
+            verify(ORDER_SIGNED_WITH_RSA, RSA_PUBLIC_KEY).unwrap()
+        );
+
+        // Formatting of tags is not part of the canonical form
+        let xml = ORDER_SIGNED_WITH_RSA
+            .replace("<Item sku=\"A-1\">", "<Item  sku='A-1' >")
+            .replace("<Empty/>", "<Empty></Empty>");
+
+        assert!(verify(&xml, RSA_PUBLIC_KEY).is_ok());
+
+        // Only the Order element is signed
+        let xml = ORDER_SIGNED_WITH_RSA.replace("</Order>", "</Order><Unsigned/>");
+        assert!(verify(&xml, RSA_PUBLIC_KEY).is_ok());
+    }
+
+    #[test]
+    fn test_verify_ecdsa_signature() {
+        assert_eq!(
+            vec!["Invoice".to_string()],
+            verify(INVOICE_SIGNED_WITH_ECDSA, EC_PUBLIC_KEY).unwrap()
+        );
+
+        // Comments are not part of the signed content
+        let xml = INVOICE_SIGNED_WITH_ECDSA.replace("<!-- line items -->", "<!-- changed -->");
+        assert!(verify(&xml, EC_PUBLIC_KEY).is_ok());
+    }
+
+    #[test]
+    fn test_tampered_documents() {
+        let xml = ORDER_SIGNED_WITH_RSA.replace("10.50", "1.50");
+
+        assert_eq!(
+            "Digest of reference '#order-1' does not match",
+            verify(&xml, RSA_PUBLIC_KEY).unwrap_err()
+        );
+
+        let xml = INVOICE_SIGNED_WITH_ECDSA.replace("</Invoice>", "<Line no=\"2\"/></Invoice>");
+
+        assert_eq!(
+            "Digest of reference '' does not match",
+            verify(&xml, EC_PUBLIC_KEY).unwrap_err()
+        );
+
+        // The reference is changed along with its digest
+        let xml = ORDER_SIGNED_WITH_RSA.replace(
+            "<ds:Transform Algorithm=\"http://www.w3.org/2001/10/xml-exc-c14n#\"/>",
+            "<ds:Transform Algorithm=\"http://www.w3.org/TR/2001/REC-xml-c14n-20010315\"/>",
+        );
+
+        assert!(verify(&xml, RSA_PUBLIC_KEY).is_err());
+
+        let xml = INVOICE_SIGNED_WITH_ECDSA.replace("<SignatureValue>pW", "<SignatureValue>pV");
+
+        assert_eq!(
+            "Signature value is not valid",
+            verify(&xml, EC_PUBLIC_KEY).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_signature_wrapping_is_rejected() {
+        // A reference to an unsigned element is added after <SignedInfo>, where
+        // the signature value does not cover it
+        let evil_reference =
+            r##"<ds:Reference URI="#evil"><ds:DigestValue>AA==</ds:DigestValue></ds:Reference>"##;
+        let xml = ORDER_SIGNED_WITH_RSA
+            .replace(
+                "</ds:SignedInfo>",
+                &format!("</ds:SignedInfo>{}", evil_reference),
+            )
+            .replace("</Envelope>", "<Evil Id=\"evil\"/></Envelope>");
+
+        assert_eq!(
+            format!(
+                "<ds:Reference> at pos {} is not expected here",
+                xml.find("<ds:Reference URI=\"#evil\"").unwrap()
+            ),
+            find_signatures(xml.as_bytes()).unwrap_err()
+        );
+
+        let xml = ORDER_SIGNED_WITH_RSA.replace(
+            "</ds:SignedInfo>",
+            "</ds:SignedInfo><ds:SignedInfo></ds:SignedInfo>",
+        );
+
+        assert_eq!(
+            format!(
+                "Signature at pos {} has more than one SignedInfo",
+                xml.find("<ds:Signature").unwrap()
+            ),
+            find_signatures(xml.as_bytes()).unwrap_err()
+        );
+
+        // Only the elements of the XML signature namespace are signature parts
+        let xml = ORDER_SIGNED_WITH_RSA.replace(
+            "</ds:SignedInfo>",
+            r##"<x:Reference xmlns:x="urn:example:other" URI="#evil"/></ds:SignedInfo>"##,
+        );
+
+        assert!(find_signatures(xml.as_bytes())
+            .unwrap_err()
+            .starts_with("<x:Reference> at pos"));
+    }
+
+    #[test]
+    fn test_wrong_keys() {
+        assert_eq!(
+            "Signature value is not valid",
+            verify(INVOICE_SIGNED_WITH_ECDSA, OTHER_EC_PUBLIC_KEY).unwrap_err()
+        );
+
+        assert_eq!(
+            "Public key does not match signature method 'http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256'",
+            verify(INVOICE_SIGNED_WITH_ECDSA, RSA_PUBLIC_KEY).unwrap_err()
+        );
+
+        assert!(XmlDsigPublicKey::from_public_key_pem("not a key").is_err());
+    }
+
+    #[test]
+    fn test_resolve_reference() {
+        let xml = r#"<R Id="r"><A ID="a">1</A><B id="b"/><C Id="c"/><D Id="c"/></R>"#;
+
+        let node = resolve_reference(xml.as_bytes(), "").unwrap();
+        assert_eq!("R", node.get_node_name());
+
+        let node = resolve_reference(xml.as_bytes(), "#a").unwrap();
+        assert_eq!(Some("1".to_string()), node.get_value().unwrap());
+
+        let node = resolve_reference(xml.as_bytes(), "#b").unwrap();
+        assert_eq!("B", node.get_node_name());
+
+        assert_eq!(
+            "Id 'c' is used by more than one element",
+            resolve_reference(xml.as_bytes(), "#c").err().unwrap()
+        );
+
+        assert_eq!(
+            "Element with Id 'x' is not found",
+            resolve_reference(xml.as_bytes(), "#x").err().unwrap()
+        );
+
+        assert!(resolve_reference(xml.as_bytes(), "http://example.org/doc.xml").is_err());
+    }
+}
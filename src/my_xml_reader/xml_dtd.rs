@@ -0,0 +1,1158 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use super::xml_entities::{find_doctype, find_outside_quotes};
+use super::xml_validator::{is_name_char, is_valid_name, is_valid_name_token};
+use super::{normalize_whitespace, xml_utils, XmlWhitespaceHandling};
+use super::{XsdElement, XsdParticle, XsdTerm, XsdTypeRef};
+use super::{DEFAULT_MAX_ENTITY_EXPANSION_DEPTH, DEFAULT_MAX_ENTITY_EXPANSION_SIZE};
+
+#[derive(Debug, Clone)]
+pub enum XmlDtdContent {
+    Empty,
+    Any,
+    // (#PCDATA|a|b)*: text and the listed elements in any order
+    Mixed(Vec<String>),
+    // Element content. Every element of the model is XsdTypeRef::Any, as the
+    // content of children is declared separately.
+    Children(XsdParticle),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlDtdAttributeType {
+    CData,
+    Id,
+    IdRef,
+    IdRefs,
+    Entity,
+    Entities,
+    NmToken,
+    NmTokens,
+    Notation(Vec<String>),
+    Enumeration(Vec<String>),
+}
+
+impl XmlDtdAttributeType {
+    // Attribute values of all the types but CDATA have their whitespace
+    // collapsed
+    pub fn normalize<'v>(&self, value: &'v str) -> std::borrow::Cow<'v, str> {
+        match self {
+            Self::CData => normalize_whitespace(value, XmlWhitespaceHandling::Replace),
+            _ => normalize_whitespace(value, XmlWhitespaceHandling::Collapse),
+        }
+    }
+
+    // Checks a normalized value
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        let is_valid = match self {
+            Self::CData => true,
+            Self::Id | Self::IdRef | Self::Entity => is_valid_name(value),
+            Self::IdRefs | Self::Entities => {
+                !value.is_empty() && value.split(' ').all(is_valid_name)
+            }
+            Self::NmToken => is_valid_name_token(value),
+            Self::NmTokens => !value.is_empty() && value.split(' ').all(is_valid_name_token),
+            Self::Notation(values) | Self::Enumeration(values) => {
+                if values.iter().any(|v| v == value) {
+                    return Ok(());
+                }
+
+                return Err(format!(
+                    "'{}' is not one of the allowed values: {}",
+                    value,
+                    values.join(", ")
+                ));
+            }
+        };
+
+        match is_valid {
+            true => Ok(()),
+            false => Err(format!("'{}' is not a valid {}", value, self.get_name())),
+        }
+    }
+
+    fn get_name(&self) -> &'static str {
+        match self {
+            Self::CData => "CDATA",
+            Self::Id => "ID",
+            Self::IdRef => "IDREF",
+            Self::IdRefs => "IDREFS",
+            Self::Entity => "ENTITY",
+            Self::Entities => "ENTITIES",
+            Self::NmToken => "NMTOKEN",
+            Self::NmTokens => "NMTOKENS",
+            Self::Notation(_) => "NOTATION",
+            Self::Enumeration(_) => "enumeration",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlDtdAttributeDefault {
+    Required,
+    Implied,
+    Fixed(String),
+    Value(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct XmlDtdAttribute {
+    pub name: String,
+    pub attribute_type: XmlDtdAttributeType,
+    pub default: XmlDtdAttributeDefault,
+}
+
+impl XmlDtdAttribute {
+    // The value as it is declared, entities are not expanded yet
+    pub fn get_default_value(&self) -> Option<&str> {
+        match &self.default {
+            XmlDtdAttributeDefault::Fixed(value) | XmlDtdAttributeDefault::Value(value) => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Reads external subsets and external parameter entities from a local
+// directory. Only relative paths without ".." are accepted, so a document can
+// not make it read arbitrary files or go to the network.
+#[derive(Debug, Clone)]
+pub struct XmlDtdFileResolver {
+    pub base_dir: PathBuf,
+}
+
+impl XmlDtdFileResolver {
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+
+    pub fn resolve(&self, system_id: &str) -> Result<Vec<u8>, String> {
+        let path = Path::new(system_id);
+
+        let is_local = !system_id.is_empty()
+            && !system_id.contains(':')
+            && !system_id.starts_with(['/', '\\'])
+            && path.components().all(|c| matches!(c, Component::Normal(_)));
+
+        if !is_local {
+            return Err(format!(
+                "System identifier '{}' is not a relative local path",
+                system_id
+            ));
+        }
+
+        std::fs::read(self.base_dir.join(path))
+            .map_err(|err| format!("Can not read DTD '{}': {}", system_id, err))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlDtd {
+    // Name of the root element and the external subset of <!DOCTYPE>
+    pub root_name: Option<String>,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+    pub elements: HashMap<String, XmlDtdContent>,
+    // Attribute declarations by element name in the order of declaration
+    pub attributes: HashMap<String, Vec<XmlDtdAttribute>>,
+}
+
+impl XmlDtd {
+    // Reads the DTD of the document: the internal subset and, if there is a
+    // resolver, the external subset. Returns None if there is no <!DOCTYPE>.
+    pub fn from_document(
+        xml: &[u8],
+        resolver: Option<&XmlDtdFileResolver>,
+    ) -> Result<Option<Self>, String> {
+        let start_pos = xml_utils::init_pos_start(xml)?;
+
+        match find_doctype(xml, start_pos)? {
+            Some(doctype) => Self::from_doctype(doctype, resolver).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn from_doctype(
+        doctype: &[u8],
+        resolver: Option<&XmlDtdFileResolver>,
+    ) -> Result<Self, String> {
+        let doctype = to_str(doctype, "DOCTYPE")?;
+
+        let declaration = doctype
+            .strip_prefix("<!DOCTYPE")
+            .and_then(|declaration| declaration.strip_suffix('>'))
+            .ok_or_else(|| "DOCTYPE declaration is not valid".to_string())?;
+
+        let (header, internal_subset) = match declaration.find('[') {
+            Some(start) => match declaration.rfind(']') {
+                Some(end) if end > start => (&declaration[..start], &declaration[start + 1..end]),
+                _ => return Err("Internal subset of DOCTYPE is not closed".to_string()),
+            },
+            None => (declaration, ""),
+        };
+
+        let mut scanner = DtdScanner::new(header);
+        let mut parser = DtdParser::new(resolver);
+
+        scanner.skip_whitespace();
+        parser.dtd.root_name = Some(scanner.read_name()?.to_string());
+        (parser.dtd.public_id, parser.dtd.system_id) = scanner.read_external_id()?;
+        scanner.expect_end()?;
+
+        parser.parse_subset(internal_subset, "", 0)?;
+
+        if let (Some(system_id), Some(resolver)) = (parser.dtd.system_id.clone(), resolver) {
+            let external_subset = resolver.resolve(&system_id)?;
+            let external_subset = to_str(&external_subset, &system_id)?;
+            parser.parse_subset(external_subset, get_base_dir(&system_id), 0)?;
+        }
+
+        Ok(parser.dtd)
+    }
+
+    // Parses declarations of an external subset, e.g. a .dtd file. System
+    // identifiers inside it are resolved relative to the resolver directory.
+    pub fn parse(dtd: &[u8], resolver: Option<&XmlDtdFileResolver>) -> Result<Self, String> {
+        let mut parser = DtdParser::new(resolver);
+        parser.parse_subset(to_str(dtd, "DTD")?, "", 0)?;
+        Ok(parser.dtd)
+    }
+
+    pub fn find_attribute(&self, element: &str, attribute: &str) -> Option<&XmlDtdAttribute> {
+        self.attributes
+            .get(element)?
+            .iter()
+            .find(|declared| declared.name == attribute)
+    }
+}
+
+enum ParameterEntity {
+    Internal(String),
+    // System identifier relative to the resolver directory
+    External(String),
+}
+
+struct DtdParser<'r> {
+    dtd: XmlDtd,
+    resolver: Option<&'r XmlDtdFileResolver>,
+    parameter_entities: HashMap<String, ParameterEntity>,
+    // Total size of replacement texts of parameter entities, which is limited
+    // like the expansion of general entities
+    expanded_size: usize,
+}
+
+impl<'r> DtdParser<'r> {
+    fn new(resolver: Option<&'r XmlDtdFileResolver>) -> Self {
+        Self {
+            dtd: XmlDtd::default(),
+            resolver,
+            parameter_entities: HashMap::new(),
+            expanded_size: 0,
+        }
+    }
+
+    fn parse_subset(&mut self, subset: &str, base_dir: &str, depth: usize) -> Result<(), String> {
+        let mut rest = subset.strip_prefix('\u{FEFF}').unwrap_or(subset);
+
+        loop {
+            rest = rest.trim_start_matches(is_whitespace);
+
+            if rest.is_empty() {
+                return Ok(());
+            }
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = skip_past(comment, "-->")?;
+            } else if let Some(instruction) = rest.strip_prefix("<?") {
+                rest = skip_past(instruction, "?>")?;
+            } else if let Some(section) = rest.strip_prefix("<![") {
+                rest = self.parse_conditional_section(section, base_dir, depth)?;
+            } else if let Some(reference) = rest.strip_prefix('%') {
+                let name = read_reference(reference)?;
+                let (replacement, entity_base_dir) = self.get_replacement(name, base_dir, depth)?;
+
+                self.parse_subset(&replacement, &entity_base_dir, depth + 1)?;
+                rest = &reference[name.len() + 1..];
+            } else if let Some(declaration) = rest.strip_prefix("<!") {
+                let Some(end) = find_declaration_end(declaration) else {
+                    return Err(format!(
+                        "Declaration '<!{}' is not closed",
+                        get_excerpt(declaration)
+                    ));
+                };
+
+                self.parse_declaration(&declaration[..end], base_dir, depth)?;
+                rest = &declaration[end + 1..];
+            } else {
+                return Err(format!(
+                    "Unexpected content in DTD: '{}'",
+                    get_excerpt(rest)
+                ));
+            }
+        }
+    }
+
+    // <![INCLUDE[ ... ]]> or <![IGNORE[ ... ]]>, where the keyword may be a
+    // parameter entity reference. Returns the rest after the section.
+    fn parse_conditional_section<'s>(
+        &mut self,
+        section: &'s str,
+        base_dir: &str,
+        depth: usize,
+    ) -> Result<&'s str, String> {
+        let Some(content_start) = section.find('[') else {
+            return Err("Conditional section is not valid".to_string());
+        };
+
+        let keyword = self.expand_references(&section[..content_start], false, base_dir, depth)?;
+
+        // Sections may be nested, so the end is the "]]>" which is not
+        // balanced by an inner "<![".
+        let content = &section[content_start + 1..];
+        let mut nesting = 0;
+        let mut pos = 0;
+
+        let content_end = loop {
+            let Some(next) = content[pos..].find(['<', ']']).map(|next| pos + next) else {
+                return Err("Conditional section is not closed".to_string());
+            };
+
+            if content[next..].starts_with("<![") {
+                nesting += 1;
+                pos = next + 3;
+            } else if content[next..].starts_with("]]>") {
+                if nesting == 0 {
+                    break next;
+                }
+
+                nesting -= 1;
+                pos = next + 3;
+            } else {
+                pos = next + 1;
+            }
+        };
+
+        match keyword.trim_matches(is_whitespace) {
+            "INCLUDE" => self.parse_subset(&content[..content_end], base_dir, depth)?,
+            "IGNORE" => {}
+            keyword => {
+                return Err(format!(
+                    "Conditional section keyword '{}' is not valid",
+                    keyword
+                ))
+            }
+        }
+
+        Ok(&content[content_end + 3..])
+    }
+
+    fn parse_declaration(
+        &mut self,
+        declaration: &str,
+        base_dir: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        let keyword_end = declaration.find(is_whitespace).unwrap_or(declaration.len());
+
+        let body = &declaration[keyword_end..];
+
+        match &declaration[..keyword_end] {
+            "ELEMENT" => {
+                let body = self.expand_references(body, false, base_dir, depth)?;
+                self.parse_element(&body)
+            }
+            "ATTLIST" => {
+                let body = self.expand_references(body, false, base_dir, depth)?;
+                self.parse_attribute_list(&body)
+            }
+            "ENTITY" => self.parse_entity(body, base_dir, depth),
+            "NOTATION" => Ok(()),
+            keyword => Err(format!("Declaration <!{}> is not supported", keyword)),
+        }
+    }
+
+    fn parse_element(&mut self, body: &str) -> Result<(), String> {
+        let mut scanner = DtdScanner::new(body);
+
+        scanner.skip_whitespace();
+        let name = scanner.read_name()?.to_string();
+        scanner.skip_whitespace();
+
+        let content = parse_content_model(&mut scanner)
+            .map_err(|err| format!("Content model of element '{}' is not valid: {}", name, err))?;
+
+        scanner.expect_end()?;
+
+        if self.dtd.elements.contains_key(&name) {
+            return Err(format!("Element '{}' is declared more than once", name));
+        }
+
+        self.dtd.elements.insert(name, content);
+        Ok(())
+    }
+
+    fn parse_attribute_list(&mut self, body: &str) -> Result<(), String> {
+        let mut scanner = DtdScanner::new(body);
+
+        scanner.skip_whitespace();
+        let element = scanner.read_name()?.to_string();
+        let attributes = self.dtd.attributes.entry(element.clone()).or_default();
+
+        loop {
+            scanner.skip_whitespace();
+
+            if scanner.is_end() {
+                return Ok(());
+            }
+
+            let name = scanner.read_name()?.to_string();
+            scanner.skip_whitespace();
+
+            let attribute_type = match scanner.peek() {
+                Some('(') => XmlDtdAttributeType::Enumeration(scanner.read_enumeration()?),
+                _ => match scanner.read_name()? {
+                    "CDATA" => XmlDtdAttributeType::CData,
+                    "ID" => XmlDtdAttributeType::Id,
+                    "IDREF" => XmlDtdAttributeType::IdRef,
+                    "IDREFS" => XmlDtdAttributeType::IdRefs,
+                    "ENTITY" => XmlDtdAttributeType::Entity,
+                    "ENTITIES" => XmlDtdAttributeType::Entities,
+                    "NMTOKEN" => XmlDtdAttributeType::NmToken,
+                    "NMTOKENS" => XmlDtdAttributeType::NmTokens,
+                    "NOTATION" => {
+                        scanner.skip_whitespace();
+                        XmlDtdAttributeType::Notation(scanner.read_enumeration()?)
+                    }
+                    attribute_type => {
+                        return Err(format!(
+                            "Type '{}' of attribute '{}' of element '{}' is not valid",
+                            attribute_type, name, element
+                        ))
+                    }
+                },
+            };
+
+            scanner.skip_whitespace();
+
+            let default = match scanner.peek() {
+                Some('#') => {
+                    scanner.pos += 1;
+
+                    match scanner.read_name()? {
+                        "REQUIRED" => XmlDtdAttributeDefault::Required,
+                        "IMPLIED" => XmlDtdAttributeDefault::Implied,
+                        "FIXED" => {
+                            scanner.skip_whitespace();
+                            XmlDtdAttributeDefault::Fixed(scanner.read_literal()?.to_string())
+                        }
+                        keyword => {
+                            return Err(format!(
+                                "Default '#{}' of attribute '{}' of element '{}' is not valid",
+                                keyword, name, element
+                            ))
+                        }
+                    }
+                }
+                _ => XmlDtdAttributeDefault::Value(scanner.read_literal()?.to_string()),
+            };
+
+            let attribute = XmlDtdAttribute {
+                name,
+                attribute_type,
+                default,
+            };
+
+            if let Some(value) = attribute.get_default_value() {
+                let value = attribute.attribute_type.normalize(value);
+
+                // Values with references are checked when they are expanded
+                if !value.contains('&') {
+                    attribute.attribute_type.validate(&value).map_err(|err| {
+                        format!(
+                            "Default value of attribute '{}' of element '{}' is not valid: {}",
+                            attribute.name, element, err
+                        )
+                    })?;
+                }
+            }
+
+            // The first declaration of an attribute is binding
+            if !attributes.iter().any(|a| a.name == attribute.name) {
+                attributes.push(attribute);
+            }
+        }
+    }
+
+    // Only parameter entities are kept. General entities of the internal
+    // subset are read by XmlEntities.
+    fn parse_entity(&mut self, body: &str, base_dir: &str, depth: usize) -> Result<(), String> {
+        let mut scanner = DtdScanner::new(body);
+
+        scanner.skip_whitespace();
+
+        if scanner.peek() != Some('%') {
+            return Ok(());
+        }
+
+        scanner.pos += 1;
+        scanner.skip_whitespace();
+
+        let name = scanner.read_name()?.to_string();
+        scanner.skip_whitespace();
+
+        let entity = match scanner.peek() {
+            Some('"' | '\'') => {
+                let value = scanner.read_literal()?;
+                ParameterEntity::Internal(self.expand_references(value, true, base_dir, depth)?)
+            }
+            _ => match scanner.read_external_id()? {
+                (_, Some(system_id)) => ParameterEntity::External(join_path(base_dir, &system_id)),
+                _ => return Err(format!("Parameter entity '{}' is not valid", name)),
+            },
+        };
+
+        scanner.expect_end()?;
+
+        // The first declaration is binding
+        self.parameter_entities.entry(name).or_insert(entity);
+        Ok(())
+    }
+
+    // Returns the replacement text of a parameter entity and the directory
+    // against which system identifiers inside it are resolved
+    fn get_replacement(
+        &mut self,
+        name: &str,
+        base_dir: &str,
+        depth: usize,
+    ) -> Result<(String, String), String> {
+        if depth >= DEFAULT_MAX_ENTITY_EXPANSION_DEPTH {
+            return Err(format!(
+                "Parameter entity '%{};' is nested too deeply",
+                name
+            ));
+        }
+
+        let result = match self.parameter_entities.get(name) {
+            Some(ParameterEntity::Internal(value)) => (value.clone(), base_dir.to_string()),
+            Some(ParameterEntity::External(system_id)) => {
+                let Some(resolver) = self.resolver else {
+                    return Err(format!(
+                        "External parameter entity '%{};' can not be read without a resolver",
+                        name
+                    ));
+                };
+
+                let text = resolver.resolve(system_id)?;
+                let text = to_str(&text, system_id)?;
+
+                (text.to_string(), get_base_dir(system_id).to_string())
+            }
+            None => return Err(format!("Parameter entity '%{};' is not declared", name)),
+        };
+
+        self.expanded_size += result.0.len();
+
+        if self.expanded_size > DEFAULT_MAX_ENTITY_EXPANSION_SIZE {
+            return Err(format!(
+                "Parameter entities are expanded to more than {} bytes",
+                DEFAULT_MAX_ENTITY_EXPANSION_SIZE
+            ));
+        }
+
+        Ok(result)
+    }
+
+    // Replaces parameter entity references. Outside of literals references
+    // in quotes are not recognized, and the replacement is surrounded by
+    // spaces, so it is never joined with the text around it.
+    fn expand_references(
+        &mut self,
+        text: &str,
+        is_literal: bool,
+        base_dir: &str,
+        depth: usize,
+    ) -> Result<String, String> {
+        let mut result = String::with_capacity(text.len());
+        let mut quote = None;
+        let mut pos = 0;
+
+        while let Some(c) = text[pos..].chars().next() {
+            match (c, quote) {
+                ('"' | '\'', None) if !is_literal => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                ('%', None) if text[pos + 1..].starts_with(is_name_char) => {
+                    let name = read_reference(&text[pos + 1..])?;
+                    let (replacement, entity_base_dir) =
+                        self.get_replacement(name, base_dir, depth)?;
+                    let replacement = self.expand_references(
+                        &replacement,
+                        is_literal,
+                        &entity_base_dir,
+                        depth + 1,
+                    )?;
+
+                    match is_literal {
+                        true => result.push_str(&replacement),
+                        false => {
+                            result.push(' ');
+                            result.push_str(&replacement);
+                            result.push(' ');
+                        }
+                    }
+
+                    pos += name.len() + 2;
+                    continue;
+                }
+                _ => {}
+            }
+
+            result.push(c);
+            pos += c.len_utf8();
+        }
+
+        Ok(result)
+    }
+}
+
+struct DtdScanner<'s> {
+    text: &'s str,
+    pos: usize,
+}
+
+impl<'s> DtdScanner<'s> {
+    fn new(text: &'s str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn is_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let is_next = self.peek() == Some(c);
+
+        if is_next {
+            self.pos += c.len_utf8();
+        }
+
+        is_next
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(format!(
+                "'{}' is expected at '{}'",
+                c,
+                get_excerpt(self.rest())
+            )),
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+
+        match self.is_end() {
+            true => Ok(()),
+            false => Err(format!(
+                "Unexpected '{}' in declaration",
+                get_excerpt(self.rest())
+            )),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches(is_whitespace).len();
+    }
+
+    // Reads a name or a name token
+    fn read_name(&mut self) -> Result<&'s str, String> {
+        let rest = self.rest();
+        let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+
+        if end == 0 {
+            return Err(format!("Name is expected at '{}'", get_excerpt(rest)));
+        }
+
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    fn read_literal(&mut self) -> Result<&'s str, String> {
+        let rest = self.rest();
+
+        let quote = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => {
+                return Err(format!(
+                    "Quoted value is expected at '{}'",
+                    get_excerpt(rest)
+                ))
+            }
+        };
+
+        let Some(end) = rest[1..].find(quote) else {
+            return Err(format!(
+                "Quoted value '{}' is not closed",
+                get_excerpt(rest)
+            ));
+        };
+
+        self.pos += end + 2;
+        Ok(&rest[1..end + 1])
+    }
+
+    // (a|b|c)
+    fn read_enumeration(&mut self) -> Result<Vec<String>, String> {
+        self.expect('(')?;
+
+        let mut result = vec![];
+
+        loop {
+            self.skip_whitespace();
+            result.push(self.read_name()?.to_string());
+            self.skip_whitespace();
+
+            if !self.eat('|') {
+                self.expect(')')?;
+                return Ok(result);
+            }
+        }
+    }
+
+    // Returns public and system identifiers of SYSTEM "uri" or
+    // PUBLIC "id" "uri", if there is one
+    fn read_external_id(&mut self) -> Result<(Option<String>, Option<String>), String> {
+        self.skip_whitespace();
+
+        let rest = self.rest();
+
+        let public_id = if let Some(after) = rest.strip_prefix("PUBLIC") {
+            self.pos += rest.len() - after.len();
+            self.skip_whitespace();
+            Some(self.read_literal()?.to_string())
+        } else if let Some(after) = rest.strip_prefix("SYSTEM") {
+            self.pos += rest.len() - after.len();
+            None
+        } else {
+            return Ok((None, None));
+        };
+
+        self.skip_whitespace();
+        let system_id = self.read_literal()?.to_string();
+
+        Ok((public_id, Some(system_id)))
+    }
+
+    fn read_occurrence(&mut self) -> (usize, Option<usize>) {
+        if self.eat('?') {
+            (0, Some(1))
+        } else if self.eat('*') {
+            (0, None)
+        } else if self.eat('+') {
+            (1, None)
+        } else {
+            (1, Some(1))
+        }
+    }
+}
+
+// EMPTY, ANY, (#PCDATA|a|b)* or element content such as (a,(b|c)*,d?)+
+fn parse_content_model(scanner: &mut DtdScanner) -> Result<XmlDtdContent, String> {
+    if !scanner.eat('(') {
+        return match scanner.read_name()? {
+            "EMPTY" => Ok(XmlDtdContent::Empty),
+            "ANY" => Ok(XmlDtdContent::Any),
+            name => Err(format!("'{}' is not expected", name)),
+        };
+    }
+
+    scanner.skip_whitespace();
+
+    if !scanner.eat('#') {
+        let term = parse_group(scanner)?;
+        let (min_occurs, max_occurs) = scanner.read_occurrence();
+
+        return Ok(XmlDtdContent::Children(XsdParticle {
+            term,
+            min_occurs,
+            max_occurs,
+        }));
+    }
+
+    if scanner.read_name()? != "PCDATA" {
+        return Err("#PCDATA is expected".to_string());
+    }
+
+    let mut names = vec![];
+
+    loop {
+        scanner.skip_whitespace();
+
+        if !scanner.eat('|') {
+            break;
+        }
+
+        scanner.skip_whitespace();
+        names.push(scanner.read_name()?.to_string());
+    }
+
+    scanner.expect(')')?;
+
+    // (#PCDATA) may go without '*'
+    if !scanner.eat('*') && !names.is_empty() {
+        return Err("'*' is expected after mixed content".to_string());
+    }
+
+    Ok(XmlDtdContent::Mixed(names))
+}
+
+// Reads a choice or a sequence after its '('
+fn parse_group(scanner: &mut DtdScanner) -> Result<XsdTerm, String> {
+    let mut particles = vec![parse_particle(scanner)?];
+    let mut separator = None;
+
+    loop {
+        scanner.skip_whitespace();
+
+        match scanner.peek() {
+            Some(')') => {
+                scanner.pos += 1;
+                break;
+            }
+            Some(c @ ('|' | ',')) if separator.is_none_or(|separator| separator == c) => {
+                scanner.pos += 1;
+                separator = Some(c);
+                particles.push(parse_particle(scanner)?);
+            }
+            _ => {
+                return Err(format!(
+                    "')' or a separator is expected at '{}'",
+                    get_excerpt(scanner.rest())
+                ))
+            }
+        }
+    }
+
+    match separator {
+        Some('|') => Ok(XsdTerm::Choice(particles)),
+        _ => Ok(XsdTerm::Sequence(particles)),
+    }
+}
+
+fn parse_particle(scanner: &mut DtdScanner) -> Result<XsdParticle, String> {
+    scanner.skip_whitespace();
+
+    let term = match scanner.eat('(') {
+        true => parse_group(scanner)?,
+        false => XsdTerm::Element(XsdElement {
+            name: scanner.read_name()?.to_string(),
+            type_ref: XsdTypeRef::Any,
+        }),
+    };
+
+    let (min_occurs, max_occurs) = scanner.read_occurrence();
+
+    Ok(XsdParticle {
+        term,
+        min_occurs,
+        max_occurs,
+    })
+}
+
+// Returns the name of "name;..." after '%'
+fn read_reference(text: &str) -> Result<&str, String> {
+    match text.split_once(';') {
+        Some((name, _)) if is_valid_name(name) => Ok(name),
+        _ => Err(format!(
+            "Parameter entity reference '%{}' is not valid",
+            get_excerpt(text)
+        )),
+    }
+}
+
+fn skip_past<'s>(text: &'s str, token: &str) -> Result<&'s str, String> {
+    match text.find(token) {
+        Some(pos) => Ok(&text[pos + token.len()..]),
+        None => Err(format!(
+            "Markup in DTD is not closed: '{}' is expected",
+            token
+        )),
+    }
+}
+
+fn find_declaration_end(declaration: &str) -> Option<usize> {
+    find_outside_quotes(declaration.as_bytes(), xml_utils::CLOSE_TAG_TOKEN, 0)
+}
+
+fn to_str<'b>(bytes: &'b [u8], name: &str) -> Result<&'b str, String> {
+    std::str::from_utf8(bytes).map_err(|err| format!("'{}' is not valid UTF-8: {}", name, err))
+}
+
+fn get_base_dir(system_id: &str) -> &str {
+    system_id.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+fn join_path(base_dir: &str, system_id: &str) -> String {
+    match base_dir.is_empty() {
+        true => system_id.to_string(),
+        false => format!("{}/{}", base_dir, system_id),
+    }
+}
+
+// The beginning of the text for error messages
+fn get_excerpt(text: &str) -> &str {
+    let end = text
+        .char_indices()
+        .find(|(pos, c)| *pos >= 32 || is_whitespace(*c))
+        .map_or(text.len(), |(pos, _)| pos);
+
+    &text[..end]
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_internal_subset() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE Feed [
+  <!-- <!ELEMENT Commented EMPTY> -->
+  <!ENTITY % inline "b | i">
+  <!ENTITY % draft "IGNORE">
+  <!ENTITY company "Acme">
+  <!ELEMENT Feed (Title, (Item | Ad)*, Footer?)+>
+  <!ELEMENT Title (#PCDATA)>
+  <!ELEMENT Item (#PCDATA | %inline;)*>
+  <!ELEMENT Ad EMPTY>
+  <!ELEMENT Footer ANY>
+  <![%draft;[ <!ELEMENT Draft EMPTY> ]]>
+  <![ INCLUDE [ <![IGNORE[ <!ELEMENT Nested EMPTY> ]]> <!ELEMENT Included EMPTY> ]]>
+  <!ATTLIST Item
+      id ID #REQUIRED
+      kind (news|sale) "news"
+      tags NMTOKENS #IMPLIED
+      source CDATA #FIXED "&company;">
+  <!ATTLIST Item kind CDATA "ignored" ref IDREF #IMPLIED>
+  <?processing instruction?>
+]>
+<Feed/>"#;
+
+        let dtd = XmlDtd::from_document(xml.as_bytes(), None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(Some("Feed"), dtd.root_name.as_deref());
+        assert_eq!(None, dtd.system_id);
+        assert_eq!(6, dtd.elements.len());
+        assert!(!dtd.elements.contains_key("Draft"));
+        assert!(!dtd.elements.contains_key("Nested"));
+        assert!(matches!(dtd.elements["Ad"], XmlDtdContent::Empty));
+        assert!(matches!(dtd.elements["Footer"], XmlDtdContent::Any));
+        assert!(matches!(&dtd.elements["Title"], XmlDtdContent::Mixed(names) if names.is_empty()));
+        assert!(
+            matches!(&dtd.elements["Item"], XmlDtdContent::Mixed(names) if names == &["b", "i"])
+        );
+
+        let XmlDtdContent::Children(feed) = &dtd.elements["Feed"] else {
+            panic!("Feed must have element content");
+        };
+
+        assert_eq!((1, None), (feed.min_occurs, feed.max_occurs));
+
+        let XsdTerm::Sequence(particles) = &feed.term else {
+            panic!("Feed must have a sequence");
+        };
+
+        assert_eq!(3, particles.len());
+        assert!(matches!(&particles[1].term, XsdTerm::Choice(choice) if choice.len() == 2));
+        assert_eq!(
+            (0, None),
+            (particles[1].min_occurs, particles[1].max_occurs)
+        );
+        assert_eq!(
+            (0, Some(1)),
+            (particles[2].min_occurs, particles[2].max_occurs)
+        );
+
+        let names: Vec<&str> = dtd.attributes["Item"]
+            .iter()
+            .map(|attribute| attribute.name.as_str())
+            .collect();
+
+        assert_eq!(vec!["id", "kind", "tags", "source", "ref"], names);
+
+        let kind = dtd.find_attribute("Item", "kind").unwrap();
+        assert_eq!(
+            XmlDtdAttributeType::Enumeration(vec!["news".to_string(), "sale".to_string()]),
+            kind.attribute_type
+        );
+        assert_eq!(Some("news"), kind.get_default_value());
+
+        let source = dtd.find_attribute("Item", "source").unwrap();
+        assert_eq!(
+            XmlDtdAttributeDefault::Fixed("&company;".to_string()),
+            source.default
+        );
+
+        assert_eq!(
+            XmlDtdAttributeDefault::Required,
+            dtd.find_attribute("Item", "id").unwrap().default
+        );
+    }
+
+    #[test]
+    fn test_attribute_types() {
+        let nmtokens = XmlDtdAttributeType::NmTokens;
+
+        assert_eq!("a b-1 2", nmtokens.normalize(" a\n b-1  2 "));
+        assert!(nmtokens.validate("a b-1 2").is_ok());
+        assert_eq!(
+            "'a,b' is not a valid NMTOKENS",
+            nmtokens.validate("a,b").unwrap_err()
+        );
+
+        assert_eq!(" a  b ", XmlDtdAttributeType::CData.normalize(" a\t\nb "));
+        assert!(XmlDtdAttributeType::Id.validate("1st").is_err());
+        assert!(XmlDtdAttributeType::IdRefs.validate("a b").is_ok());
+        assert!(XmlDtdAttributeType::IdRefs.validate("").is_err());
+    }
+
+    #[test]
+    fn test_external_subset() {
+        let dir = std::env::temp_dir().join(format!("my_xml_reader_dtd_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dtd/modules")).unwrap();
+
+        std::fs::write(
+            dir.join("dtd/feed.dtd"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!ENTITY % item SYSTEM "modules/item.mod">
+<!ELEMENT Feed (Item*)>
+%item;
+<!ATTLIST Feed version CDATA "1.0">"#,
+        )
+        .unwrap();
+
+        std::fs::write(
+            dir.join("dtd/modules/item.mod"),
+            r#"<!ELEMENT Item (#PCDATA)>"#,
+        )
+        .unwrap();
+
+        let resolver = XmlDtdFileResolver::new(&dir);
+
+        let xml = br#"<!DOCTYPE Feed PUBLIC "-//Acme//Feed//EN" "dtd/feed.dtd" [
+  <!ATTLIST Feed version CDATA "2.0">
+]><Feed/>"#;
+
+        let dtd = XmlDtd::from_document(xml, Some(&resolver))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(Some("-//Acme//Feed//EN"), dtd.public_id.as_deref());
+        assert_eq!(Some("dtd/feed.dtd"), dtd.system_id.as_deref());
+        assert!(dtd.elements.contains_key("Item"));
+        assert_eq!(
+            Some("2.0"),
+            dtd.find_attribute("Feed", "version")
+                .unwrap()
+                .get_default_value()
+        );
+
+        // Without a resolver only the internal subset is read
+        let dtd = XmlDtd::from_document(xml, None).unwrap().unwrap();
+        assert!(dtd.elements.is_empty());
+
+        for system_id in ["../feed.dtd", "/etc/passwd", "http://example.com/feed.dtd"] {
+            assert_eq!(
+                format!(
+                    "System identifier '{}' is not a relative local path",
+                    system_id
+                ),
+                resolver.resolve(system_id).unwrap_err()
+            );
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse = |dtd: &str| XmlDtd::parse(dtd.as_bytes(), None).unwrap_err();
+
+        assert_eq!(
+            "Element 'A' is declared more than once",
+            parse("<!ELEMENT A EMPTY><!ELEMENT A ANY>")
+        );
+
+        assert_eq!(
+            "Content model of element 'A' is not valid: ')' or a separator is expected at '|c)'",
+            parse("<!ELEMENT A (b,c|c)>")
+        );
+
+        assert_eq!(
+            "Content model of element 'A' is not valid: '*' is expected after mixed content",
+            parse("<!ELEMENT A (#PCDATA|b)>")
+        );
+
+        assert_eq!(
+            "Default value of attribute 'a' of element 'A' is not valid: 'c' is not one of the allowed values: b",
+            parse("<!ATTLIST A a (b) 'c'>")
+        );
+
+        assert_eq!(
+            "Parameter entity '%missing;' is not declared",
+            parse("<!ELEMENT A %missing;>")
+        );
+
+        assert_eq!(
+            "External parameter entity '%ext;' can not be read without a resolver",
+            parse("<!ENTITY % ext SYSTEM 'ext.dtd'>%ext;")
+        );
+
+        assert_eq!(
+            "Declaration <!DOCTYPE> is not supported",
+            parse("<!DOCTYPE A>")
+        );
+    }
+
+    #[test]
+    fn test_parameter_entity_expansion_is_limited() {
+        let mut dtd = String::from("<!ENTITY % a0 '<!-- -->'>\n");
+
+        for i in 1..=20 {
+            let references = format!("%a{};", i - 1).repeat(4);
+            dtd.push_str(&format!("<!ENTITY % a{} '{}'>\n", i, references));
+        }
+
+        dtd.push_str("%a20;");
+
+        let err = XmlDtd::parse(dtd.as_bytes(), None).unwrap_err();
+
+        assert_eq!(
+            format!(
+                "Parameter entities are expanded to more than {} bytes",
+                DEFAULT_MAX_ENTITY_EXPANSION_SIZE
+            ),
+            err
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::xml_to_json::append_text;
+use super::xsd_validator::ContentMatcher;
+use super::{AttributesIterator, MyXmlReader, XmlTagInfo, XmlTagType};
+use super::{XmlDtd, XmlDtdAttributeDefault, XmlDtdAttributeType, XmlDtdContent};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlDtdValidationError {
+    // Path of the element, "Feed/Item[2]/Title"
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for XmlDtdValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+// Validates the document against the DTD while reading it. Names are compared
+// as they are written, prefixes included. Errors of a document which is not
+// well-formed are returned as Err.
+pub fn validate_with_dtd(xml: &[u8], dtd: &XmlDtd) -> Result<Vec<XmlDtdValidationError>, String> {
+    let mut reader = MyXmlReader::from_slice(xml)?;
+    let mut validator = DtdValidator {
+        dtd,
+        opened: vec![],
+        ids: HashSet::new(),
+        id_references: vec![],
+        errors: vec![],
+    };
+
+    let mut content_start = 0;
+
+    while let Some(tag) = reader.read_next_tag()? {
+        if let Some(element) = validator.opened.last_mut() {
+            append_text(
+                &reader,
+                &reader.xml[content_start..tag.start_pos],
+                &mut element.text,
+            )?;
+        }
+
+        content_start = tag.end_pos + 1;
+
+        match tag.tag_type {
+            XmlTagType::Open => validator.open_element(&reader, &tag)?,
+            XmlTagType::OpenClose => {
+                validator.open_element(&reader, &tag)?;
+                validator.close_element();
+            }
+            XmlTagType::Close => validator.close_element(),
+        }
+    }
+
+    // IDREFs may point forward, so they are checked at the end
+    for (path, id) in std::mem::take(&mut validator.id_references) {
+        if !validator.ids.contains(&id) {
+            validator.add_error(path, format!("IDREF '{}' does not match any ID", id));
+        }
+    }
+
+    Ok(validator.errors)
+}
+
+struct OpenedElement<'t, 'd> {
+    name: &'t str,
+    path: String,
+    // None if the element is not declared
+    content: Option<&'d XmlDtdContent>,
+    // Amounts of the children of every name, for the paths of the next ones
+    child_counts: HashMap<&'t str, usize>,
+    // Set for element content until a child does not fit
+    matcher: Option<ContentMatcher<'d>>,
+    // Errors of the children which do not fit, reported when the element is
+    // closed
+    content_errors: Vec<XmlDtdValidationError>,
+    text: String,
+}
+
+impl<'t, 'd> OpenedElement<'t, 'd> {
+    fn match_child(&mut self, name: &str, path: &str) {
+        let message = match self.content {
+            Some(XmlDtdContent::Mixed(names)) if !names.iter().any(|n| n == name) => {
+                format!("Element <{}> is not expected in <{}>", name, self.name)
+            }
+            Some(XmlDtdContent::Children(_)) => {
+                match self.matcher.as_mut().map(|m| m.match_child(name)) {
+                    Some(Err(expected)) => {
+                        self.matcher = None;
+                        format!("Element <{}> is not expected here{}", name, expected)
+                    }
+                    _ => return,
+                }
+            }
+            _ => return,
+        };
+
+        self.content_errors.push(XmlDtdValidationError {
+            path: path.to_string(),
+            message,
+        });
+    }
+}
+
+struct DtdValidator<'d, 't> {
+    dtd: &'d XmlDtd,
+    opened: Vec<OpenedElement<'t, 'd>>,
+    ids: HashSet<String>,
+    // Paths of the elements and the IDs they refer to
+    id_references: Vec<(String, String)>,
+    errors: Vec<XmlDtdValidationError>,
+}
+
+impl<'d, 't> DtdValidator<'d, 't> {
+    fn open_element(
+        &mut self,
+        reader: &MyXmlReader<'t>,
+        tag: &XmlTagInfo<'t>,
+    ) -> Result<(), String> {
+        let path = match self.opened.last_mut() {
+            None => {
+                if let Some(root_name) = self.dtd.root_name.as_ref().filter(|n| *n != tag.name) {
+                    let message = format!("Root element must be <{}>", root_name);
+                    self.add_error(tag.name.to_string(), message);
+                }
+
+                tag.name.to_string()
+            }
+            Some(parent) => {
+                let position = parent.child_counts.entry(tag.name).or_default();
+                *position += 1;
+
+                let path = match *position {
+                    1 => format!("{}/{}", parent.path, tag.name),
+                    _ => format!("{}/{}[{}]", parent.path, tag.name, position),
+                };
+
+                parent.match_child(tag.name, &path);
+                path
+            }
+        };
+
+        let content = self.dtd.elements.get(tag.name);
+
+        match content {
+            Some(_) => self.validate_attributes(reader, tag, &path)?,
+            None => {
+                let message = format!("Element <{}> is not declared", tag.name);
+                self.add_error(path.clone(), message);
+            }
+        }
+
+        let matcher = match content {
+            Some(XmlDtdContent::Children(particle)) => Some(ContentMatcher::new(particle)),
+            _ => None,
+        };
+
+        self.opened.push(OpenedElement {
+            name: tag.name,
+            path,
+            content,
+            child_counts: HashMap::new(),
+            matcher,
+            content_errors: vec![],
+            text: String::new(),
+        });
+
+        Ok(())
+    }
+
+    fn validate_attributes(
+        &mut self,
+        reader: &MyXmlReader<'t>,
+        tag: &XmlTagInfo<'t>,
+        path: &str,
+    ) -> Result<(), String> {
+        let declared = match self.dtd.attributes.get(tag.name) {
+            Some(declared) => &declared[..],
+            None => &[],
+        };
+
+        let mut found = vec![];
+        let mut attributes = AttributesIterator::new(tag.raw);
+
+        while let Some((name, value)) = attributes.get_next()? {
+            let Some(attribute) = declared.iter().find(|a| a.name == name) else {
+                // Namespace declarations and xml:* are allowed unless the
+                // DTD declares them
+                if !(name == "xmlns" || name.starts_with("xmlns:") || name.starts_with("xml:")) {
+                    let message = format!("Attribute '{}' is not declared", name);
+                    self.add_error(path.to_string(), message);
+                }
+
+                continue;
+            };
+
+            found.push(name);
+
+            let value = reader.expand_entities(value)?;
+            let value = attribute.attribute_type.normalize(&value);
+
+            if let Err(err) = attribute.attribute_type.validate(&value) {
+                self.add_error(path.to_string(), format!("Attribute '{}': {}", name, err));
+                continue;
+            }
+
+            if let XmlDtdAttributeDefault::Fixed(fixed) = &attribute.default {
+                let fixed = reader.expand_entities(fixed)?;
+
+                if attribute.attribute_type.normalize(&fixed) != value {
+                    let message = format!("Attribute '{}' must be '{}'", name, fixed);
+                    self.add_error(path.to_string(), message);
+                }
+            }
+
+            match attribute.attribute_type {
+                XmlDtdAttributeType::Id if !self.ids.insert(value.to_string()) => {
+                    let message = format!("ID '{}' is already used", value);
+                    self.add_error(path.to_string(), message);
+                }
+                XmlDtdAttributeType::IdRef | XmlDtdAttributeType::IdRefs => {
+                    for id in value.split(' ') {
+                        self.id_references.push((path.to_string(), id.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for attribute in declared {
+            if attribute.default == XmlDtdAttributeDefault::Required
+                && !found.contains(&attribute.name.as_str())
+            {
+                let message = format!("Required attribute '{}' is missing", attribute.name);
+                self.add_error(path.to_string(), message);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn close_element(&mut self) {
+        let Some(element) = self.opened.pop() else {
+            return;
+        };
+
+        match element.content {
+            None | Some(XmlDtdContent::Any) | Some(XmlDtdContent::Mixed(_)) => {}
+            Some(XmlDtdContent::Empty) => {
+                if !element.child_counts.is_empty() || !element.text.is_empty() {
+                    self.add_error(element.path.clone(), "Element must be empty".to_string());
+                }
+            }
+            Some(XmlDtdContent::Children(_)) => {
+                if !is_whitespace_only(&element.text) {
+                    self.add_error(
+                        element.path.clone(),
+                        "Text is not allowed between child elements".to_string(),
+                    );
+                }
+
+                if let Some(Err(expected)) = element.matcher.as_ref().map(|m| m.match_end()) {
+                    self.add_error(
+                        element.path.clone(),
+                        format!("Content of <{}> is incomplete{}", element.name, expected),
+                    );
+                }
+            }
+        }
+
+        self.errors.extend(element.content_errors);
+    }
+
+    fn add_error(&mut self, path: String, message: String) {
+        self.errors.push(XmlDtdValidationError { path, message });
+    }
+}
+
+fn is_whitespace_only(text: &str) -> bool {
+    text.chars().all(|c| matches!(c, ' ' | '\t' | '\r' | '\n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED_DTD: &str = r#"<!DOCTYPE Feed [
+  <!ENTITY company "Acme &amp; Sons">
+  <!ELEMENT Feed (Title, Item+, Footer?)>
+  <!ATTLIST Feed version CDATA #FIXED "2" xmlns:x CDATA #IMPLIED>
+  <!ELEMENT Title (#PCDATA)>
+  <!ELEMENT Item (Name, (Price | Free), Related*)>
+  <!ATTLIST Item
+      id ID #REQUIRED
+      kind (news | sale) "news"
+      source CDATA #FIXED "&company;">
+  <!ELEMENT Name (#PCDATA | b)*>
+  <!ELEMENT b (#PCDATA)>
+  <!ELEMENT Price (#PCDATA)>
+  <!ELEMENT Free EMPTY>
+  <!ELEMENT Related EMPTY>
+  <!ATTLIST Related refs IDREFS #REQUIRED>
+  <!ELEMENT Footer ANY>
+]>"#;
+
+    fn validate(body: &str) -> Vec<String> {
+        let xml = format!("{}\n{}", FEED_DTD, body);
+        let dtd = XmlDtd::from_document(xml.as_bytes(), None)
+            .unwrap()
+            .unwrap();
+
+        validate_with_dtd(xml.as_bytes(), &dtd)
+            .unwrap()
+            .iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let xml = r#"<Feed version="2" xmlns="urn:feed" xml:lang="en">
+  <!-- generated -->
+  <Title>Deals &amp; news</Title>
+  <Item id="a1" kind=" sale " source="Acme &amp; Sons">
+    <Name>First <b>bold</b> item</Name>
+    <Price>10</Price>
+    <Related refs=" a2  a1 "/>
+  </Item>
+  <Item id="a2"><Name/><Free></Free></Item>
+  <Footer>Any <Title>content</Title></Footer>
+</Feed>"#;
+
+        assert_eq!(Vec::<String>::new(), validate(xml));
+    }
+
+    #[test]
+    fn test_invalid_attributes() {
+        let xml = r#"<Feed version="3" status="new">
+  <Title>T</Title>
+  <Item id="a1" kind="ad" source="Acme"><Name/><Free/></Item>
+  <Item id="a1"><Name/><Free/><Related refs="a1 missing"/></Item>
+  <Item kind="sale"><Name/><Free/><Related/></Item>
+</Feed>"#;
+
+        assert_eq!(
+            vec![
+                "Feed: Attribute 'version' must be '2'",
+                "Feed: Attribute 'status' is not declared",
+                "Feed/Item: Attribute 'kind': 'ad' is not one of the allowed values: news, sale",
+                "Feed/Item: Attribute 'source' must be 'Acme & Sons'",
+                "Feed/Item[2]: ID 'a1' is already used",
+                "Feed/Item[3]: Required attribute 'id' is missing",
+                "Feed/Item[3]/Related: Required attribute 'refs' is missing",
+                "Feed/Item[2]/Related: IDREF 'missing' does not match any ID",
+            ],
+            validate(xml)
+        );
+    }
+
+    #[test]
+    fn test_invalid_content() {
+        let xml = r#"<Feed>
+  <Title>T <b>bold</b></Title>
+  <Item id="a1"><Name>N <i>i</i></Name><Price>1</Price><Free/></Item>
+  <Item id="a2">text<Name/><Free>not empty</Free></Item>
+  <Item id="a3"><Name/></Item>
+  <Unknown><Title/></Unknown>
+</Feed>"#;
+
+        assert_eq!(
+            vec![
+                "Feed/Title/b: Element <b> is not expected in <Title>",
+                "Feed/Item/Name/i: Element <i> is not declared",
+                "Feed/Item/Name/i: Element <i> is not expected in <Name>",
+                "Feed/Item/Free: Element <Free> is not expected here. Expected <Related>",
+                "Feed/Item[2]/Free: Element must be empty",
+                "Feed/Item[2]: Text is not allowed between child elements",
+                "Feed/Item[3]: Content of <Item> is incomplete. Expected <Price> or <Free>",
+                "Feed/Unknown: Element <Unknown> is not declared",
+                "Feed/Unknown: Element <Unknown> is not expected here. Expected <Item> or <Footer>",
+            ],
+            validate(xml)
+        );
+
+        assert_eq!(
+            vec![
+                "Item: Root element must be <Feed>",
+                "Item: Required attribute 'id' is missing",
+                "Item: Content of <Item> is incomplete. Expected <Name>",
+            ],
+            validate("<Item/>")
+        );
+    }
+
+    #[test]
+    fn test_many_children() {
+        let mut xml = String::from("<Feed><Title>T</Title>");
+
+        for i in 0..10000 {
+            xml.push_str(&format!(r#"<Item id="i{}"><Name/><Free/></Item>"#, i));
+        }
+
+        xml.push_str(r#"<Item id="last"><Name/></Item><Title>T</Title></Feed>"#);
+
+        assert_eq!(
+            vec![
+                "Feed/Item[10001]: Content of <Item> is incomplete. Expected <Price> or <Free>",
+                "Feed/Title[2]: Element <Title> is not expected here. Expected <Item> or <Footer>",
+            ],
+            validate(&xml)
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+use super::XmlDeclaration;
+
+const ENCODING_ATTR: &str = "encoding";
+const UTF_8_LABEL: &str = "UTF-8";
+
+// Detects the encoding from the BOM first, then from the encoding attribute of
+// the XML declaration. UTF-8 is the default.
+pub fn detect_encoding(xml: &[u8]) -> Result<&'static Encoding, String> {
+    if let Some((encoding, _)) = Encoding::for_bom(xml) {
+        return Ok(encoding);
+    }
+
+    // UTF-16 without BOM starting with '<'
+    if xml.starts_with(&[b'<', 0]) {
+        return Ok(UTF_16LE);
+    }
+
+    if xml.starts_with(&[0, b'<']) {
+        return Ok(UTF_16BE);
+    }
+
+    let Some(label) = get_declared_encoding(xml) else {
+        return Ok(UTF_8);
+    };
+
+    let Some(encoding) = Encoding::for_label(label.as_bytes()) else {
+        return Err(format!(
+            "Unsupported encoding '{}' in the XML declaration",
+            label
+        ));
+    };
+
+    // Declaration can not say UTF-16 when the document is read as ASCII compatible bytes
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        return Err(format!(
+            "Encoding '{}' is declared but the document has no UTF-16 BOM",
+            encoding.name()
+        ));
+    }
+
+    Ok(encoding)
+}
+
+// The encoding declared in the XML declaration of the result is replaced with
+// UTF-8, so the result can be read or transcoded again as it is.
+pub fn transcode_to_utf8(xml: &[u8]) -> Result<Cow<'_, [u8]>, String> {
+    let encoding = detect_encoding(xml)?;
+
+    if encoding == UTF_8 {
+        return Ok(Cow::Borrowed(xml));
+    }
+
+    let bom_len = match Encoding::for_bom(xml) {
+        Some((_, bom_len)) => bom_len,
+        None => 0,
+    };
+
+    match encoding.decode_without_bom_handling_and_without_replacement(&xml[bom_len..]) {
+        Some(result) => Ok(Cow::Owned(
+            replace_declared_encoding(result.into_owned()).into_bytes(),
+        )),
+        None => Err(format!(
+            "Document contains bytes which are not valid {}",
+            encoding.name()
+        )),
+    }
+}
+
+fn get_declared_encoding(xml: &[u8]) -> Option<String> {
+    XmlDeclaration::parse(xml).ok()??.encoding
+}
+
+fn replace_declared_encoding(mut xml: String) -> String {
+    let Ok(Some(declaration)) = XmlDeclaration::parse(xml.as_bytes()) else {
+        return xml;
+    };
+
+    let Some(encoding) = declaration.encoding else {
+        return xml;
+    };
+
+    let header = &xml[declaration.start_pos..declaration.end_pos + 1];
+
+    // The version value goes first and can not contain the attribute name, so
+    // the first "encoding" is the name followed by '=' and the quoted value
+    let Some(name_pos) = header.find(ENCODING_ATTR) else {
+        return xml;
+    };
+
+    let after_name = &header[name_pos + ENCODING_ATTR.len()..];
+
+    let Some(quote_pos) = after_name.find(['"', '\'']) else {
+        return xml;
+    };
+
+    let value_start = declaration.start_pos + name_pos + ENCODING_ATTR.len() + quote_pos + 1;
+
+    xml.replace_range(value_start..value_start + encoding.len(), UTF_8_LABEL);
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::my_xml_reader::MyXmlReader;
+
+    fn encode_utf16(src: &str, big_endian: bool, with_bom: bool) -> Vec<u8> {
+        let bom = if with_bom { Some(0xFEFF) } else { None };
+
+        bom.into_iter()
+            .chain(src.encode_utf16())
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let test_cases: Vec<(Vec<u8>, &Encoding)> = vec![
+            (b"<R/>".to_vec(), UTF_8),
+            (b"\xEF\xBB\xBF<R/>".to_vec(), UTF_8),
+            (encode_utf16("<R/>", false, true), UTF_16LE),
+            (encode_utf16("<R/>", true, true), UTF_16BE),
+            (encode_utf16("<R/>", false, false), UTF_16LE),
+            (encode_utf16("<R/>", true, false), UTF_16BE),
+            (
+                br#"<?xml version="1.0" encoding="windows-1251"?><R/>"#.to_vec(),
+                encoding_rs::WINDOWS_1251,
+            ),
+            (
+                br#"<?xml version='1.0' encoding = 'ISO-8859-1'?><R/>"#.to_vec(),
+                encoding_rs::WINDOWS_1252,
+            ),
+        ];
+
+        for (xml, expected) in test_cases {
+            assert_eq!(expected, detect_encoding(&xml).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_unsupported_encoding() {
+        let err = detect_encoding(br#"<?xml version="1.0" encoding="klingon"?><R/>"#).unwrap_err();
+        assert_eq!("Unsupported encoding 'klingon' in the XML declaration", err);
+
+        assert!(detect_encoding(br#"<?xml version="1.0" encoding="UTF-16"?><R/>"#).is_err());
+    }
+
+    #[test]
+    fn test_read_utf16_document() {
+        let src =
+            r#"<?xml version="1.0" encoding="UTF-16"?><Payment><Payer>Иван</Payer></Payment>"#;
+
+        for big_endian in [false, true] {
+            let xml = encode_utf16(src, big_endian, true);
+            let xml = transcode_to_utf8(&xml).unwrap();
+
+            let mut reader = MyXmlReader::from_slice(&xml).unwrap();
+
+            let payment = reader.find_the_open_node("Payment/Payer").unwrap().unwrap();
+            let payer = reader.read_the_whole_node(payment).unwrap();
+
+            assert_eq!("Иван", payer.get_value().unwrap().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_read_windows_1251_document() {
+        let mut xml = br#"<?xml version="1.0" encoding="windows-1251"?><Payer>"#.to_vec();
+        xml.extend_from_slice(&[0xC8, 0xE2, 0xE0, 0xED]);
+        xml.extend_from_slice(b"</Payer>");
+
+        let xml = transcode_to_utf8(&xml).unwrap();
+
+        let mut reader = MyXmlReader::from_slice(&xml).unwrap();
+
+        let payer = reader.read_next_tag().unwrap().unwrap();
+        let payer = reader.read_the_whole_node(payer).unwrap();
+
+        assert_eq!("Иван", payer.get_value().unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_transcoded_document_declares_utf8() {
+        let src = r#"<?xml version="1.0" encoding = 'UTF-16' standalone="yes"?><R>Иван</R>"#;
+        let expected = r#"<?xml version="1.0" encoding = 'UTF-8' standalone="yes"?><R>Иван</R>"#;
+
+        let xml = encode_utf16(src, false, true);
+        let xml = transcode_to_utf8(&xml).unwrap();
+
+        assert_eq!(expected.as_bytes(), xml.as_ref());
+
+        let transcoded_again = transcode_to_utf8(&xml).unwrap();
+
+        assert!(matches!(transcoded_again, Cow::Borrowed(_)));
+        assert_eq!(expected.as_bytes(), transcoded_again.as_ref());
+
+        let mut xml = br#"<?xml version="1.0" encoding="windows-1251"?><R>"#.to_vec();
+        xml.extend_from_slice(&[0xC8, 0xE2, 0xE0, 0xED]);
+        xml.extend_from_slice(b"</R>");
+
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><R>Иван</R>"#.as_bytes(),
+            transcode_to_utf8(&xml).unwrap().as_ref()
+        );
+
+        let xml = encode_utf16(r#"<?xml version="1.0"?><R/>"#, true, true);
+
+        assert_eq!(
+            r#"<?xml version="1.0"?><R/>"#.as_bytes(),
+            transcode_to_utf8(&xml).unwrap().as_ref()
+        );
+    }
+
+    #[test]
+    fn test_utf8_is_not_copied() {
+        let xml = "<R>Иван</R>".as_bytes();
+
+        assert!(matches!(transcode_to_utf8(xml).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_invalid_bytes_for_encoding() {
+        let xml = encode_utf16("<R/>", false, true);
+
+        assert!(transcode_to_utf8(&xml[..xml.len() - 1]).is_err());
+    }
+}
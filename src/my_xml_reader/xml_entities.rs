@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use super::reader_options::check_limit;
+use super::xml_utils;
+use super::{XmlLimit, XmlLimitError};
+
+const DOCTYPE_TOKEN: &[u8] = "<!DOCTYPE".as_bytes();
+const ENTITY_TOKEN: &[u8] = "<!ENTITY".as_bytes();
+
+pub const DEFAULT_MAX_ENTITY_EXPANSION_SIZE: usize = 1024 * 1024;
+pub const DEFAULT_MAX_ENTITY_EXPANSION_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlEntities {
+    entities: HashMap<String, String>,
+}
+
+impl XmlEntities {
+    // Reads general entity declarations of the internal DTD subset which goes
+    // before the root element.
+    pub fn from_prolog(xml: &[u8], start_pos: usize) -> Result<Self, String> {
+        match find_doctype(xml, start_pos)? {
+            Some(doctype) => Self::from_doctype(doctype),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_doctype(doctype: &[u8]) -> Result<Self, String> {
+        let mut result = Self::default();
+
+        let Some(mut pos) = find_outside_quotes(doctype, b'[', 0) else {
+            return Ok(result);
+        };
+
+        pos += 1;
+
+        while pos < doctype.len() {
+            let subset = &doctype[pos..];
+
+            if subset[0] == b']' {
+                break;
+            }
+
+            if subset.starts_with(xml_utils::OPEN_COMMENT_TOKEN) {
+                pos = match xml_utils::find_next_token_ext(
+                    doctype,
+                    xml_utils::CLOSE_COMMENT_TOKEN,
+                    pos + xml_utils::OPEN_COMMENT_TOKEN.len(),
+                ) {
+                    Some(end) => end + xml_utils::CLOSE_COMMENT_TOKEN.len(),
+                    None => return Err("Comment inside DOCTYPE is not closed".to_string()),
+                };
+                continue;
+            }
+
+            if subset[0] != xml_utils::OPEN_TAG_TOKEN {
+                pos += 1;
+                continue;
+            }
+
+            let Some(declaration_end) =
+                find_outside_quotes(doctype, xml_utils::CLOSE_TAG_TOKEN, pos)
+            else {
+                return Err(format!(
+                    "Declaration inside DOCTYPE is not closed. Start pos is {}",
+                    pos
+                ));
+            };
+
+            if subset.starts_with(ENTITY_TOKEN) {
+                result.add_declaration(&doctype[pos + ENTITY_TOKEN.len()..declaration_end])?;
+            }
+
+            pos = declaration_end + 1;
+        }
+
+        Ok(result)
+    }
+
+    fn add_declaration(&mut self, declaration: &[u8]) -> Result<(), String> {
+        let declaration = std::str::from_utf8(declaration)
+            .map_err(|err| format!("Entity declaration is not valid UTF-8: {}", err))?
+            .trim_start();
+
+        // Parameter entities are only used inside the DTD itself.
+        if declaration.starts_with('%') {
+            return Ok(());
+        }
+
+        let name_end = declaration
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(declaration.len());
+
+        let name = &declaration[..name_end];
+        let definition = declaration[name_end..].trim();
+
+        let quote = match definition.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            // External entities (SYSTEM or PUBLIC) are never resolved.
+            _ => return Ok(()),
+        };
+
+        let Some(value_end) = definition[1..].find(quote) else {
+            return Err(format!("Value of entity '{}' is not closed", name));
+        };
+
+        // The first declaration is binding.
+        self.entities
+            .entry(name.to_string())
+            .or_insert_with(|| definition[1..value_end + 1].to_string());
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entities.get(name).map(|value| value.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    // Replaces predefined, character and declared entity references. Unknown
+    // references and bare '&' are kept as is.
+    pub fn expand(
+        &self,
+        text: &str,
+        max_size: usize,
+        max_depth: usize,
+    ) -> Result<String, XmlLimitError> {
+        let mut result = String::with_capacity(text.len());
+        self.expand_to(text, &mut result, 0, max_size, max_depth)?;
+        Ok(result)
+    }
+
+    fn expand_to(
+        &self,
+        text: &str,
+        result: &mut String,
+        depth: usize,
+        max_size: usize,
+        max_depth: usize,
+    ) -> Result<(), XmlLimitError> {
+        let mut rest = text;
+
+        while let Some(amp_pos) = rest.find('&') {
+            result.push_str(&rest[..amp_pos]);
+            rest = &rest[amp_pos..];
+
+            let reference_end = match xml_utils::find_reference_end(rest.as_bytes(), 0) {
+                Some(end) => end,
+                None => {
+                    result.push('&');
+                    rest = &rest[1..];
+                    continue;
+                }
+            };
+
+            let name = &rest[1..reference_end];
+
+            if let Some(c) = decode_predefined_or_char_reference(name) {
+                result.push(c);
+            } else if let Some(value) = self.entities.get(name) {
+                check_limit(
+                    XmlLimit::EntityExpansionDepth,
+                    Some(max_depth),
+                    depth + 1,
+                    text.len() - rest.len(),
+                )?;
+
+                self.expand_to(value, result, depth + 1, max_size, max_depth)?;
+            } else {
+                result.push_str(&rest[..reference_end + 1]);
+            }
+
+            check_limit(
+                XmlLimit::EntityExpansionSize,
+                Some(max_size),
+                result.len(),
+                text.len() - rest.len(),
+            )?;
+
+            rest = &rest[reference_end + 1..];
+        }
+
+        result.push_str(rest);
+
+        check_limit(
+            XmlLimit::EntityExpansionSize,
+            Some(max_size),
+            result.len(),
+            text.len(),
+        )
+    }
+}
+
+// Returns the <!DOCTYPE ...> declaration of the prolog which starts at the
+// given pos
+pub(super) fn find_doctype(xml: &[u8], start_pos: usize) -> Result<Option<&[u8]>, String> {
+    let mut pos = start_pos;
+
+    while let Some(markup_start) = xml_utils::find_next_token(xml, xml_utils::OPEN_TAG_TOKEN, pos) {
+        if xml_utils::is_element_tag(xml, markup_start) {
+            break;
+        }
+
+        let Some(markup_end) = xml_utils::find_markup_end(xml, markup_start) else {
+            return Err(format!(
+                "Can not find the end of the markup. Start pos is {}",
+                markup_start
+            ));
+        };
+
+        if xml[markup_start..].starts_with(DOCTYPE_TOKEN) {
+            return Ok(Some(&xml[markup_start..markup_end + 1]));
+        }
+
+        pos = markup_end + 1;
+    }
+
+    Ok(None)
+}
+
+fn decode_predefined_or_char_reference(name: &str) -> Option<char> {
+    match name {
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        _ => {
+            let code = name.strip_prefix('#')?;
+
+            let code = match code.strip_prefix('x') {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+
+            char::from_u32(code)
+        }
+    }
+}
+
+pub(super) fn find_outside_quotes(src: &[u8], token: u8, start_pos: usize) -> Option<usize> {
+    let mut pos = start_pos;
+
+    while pos < src.len() {
+        match src[pos] {
+            b'"' | b'\'' => pos = xml_utils::find_next_token(src, src[pos], pos + 1)?,
+            b if b == token => return Some(pos),
+            _ => {}
+        }
+
+        pos += 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_internal_subset() {
+        let xml_src = r#"<?xml version="1.0"?>
+<!DOCTYPE Feed [
+    <!-- <!ENTITY commented "no"> -->
+    <!ENTITY company "Acme &amp; Sons">
+    <!ENTITY quoted '"&company;" Ltd'>
+    <!ENTITY company "ignored">
+    <!ENTITY % param "skipped">
+    <!ENTITY logo SYSTEM "logo.png">
+    <!ELEMENT Feed (#PCDATA)>
+]>
+<Feed>&company;</Feed>"#;
+
+        let pos = xml_utils::init_pos_start(xml_src.as_bytes()).unwrap();
+        let entities = XmlEntities::from_prolog(xml_src.as_bytes(), pos).unwrap();
+
+        assert_eq!(2, entities.len());
+        assert_eq!(Some("Acme &amp; Sons"), entities.get("company"));
+        assert_eq!(Some("\"&company;\" Ltd"), entities.get("quoted"));
+        assert_eq!(None, entities.get("commented"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let entities = XmlEntities::from_doctype(
+            br#"<!DOCTYPE R [<!ENTITY a "A&amp;"><!ENTITY b "[&a;&a;]">]>"#,
+        )
+        .unwrap();
+
+        let result = entities
+            .expand("&b; &lt;&#65;&#x42;&gt; &unknown; & &amp;lt;", 1024, 16)
+            .unwrap();
+
+        assert_eq!("[A&A&] <AB> &unknown; & &lt;", result);
+    }
+
+    #[test]
+    fn test_expand_many_bare_ampersands() {
+        let entities = XmlEntities::from_doctype(br#"<!DOCTYPE R [<!ENTITY a "A">]>"#).unwrap();
+
+        // Every '&' used to make the search for ';' run to the end of the text
+        let text = format!("{}&a;", "& ".repeat(200_000));
+
+        let result = entities.expand(&text, usize::MAX, 16).unwrap();
+
+        assert_eq!(format!("{}A", "& ".repeat(200_000)), result);
+    }
+
+    #[test]
+    fn test_billion_laughs_is_stopped_by_size_limit() {
+        let entities = XmlEntities::from_doctype(
+            br#"<!DOCTYPE lolz [
+  <!ENTITY lol "lol">
+  <!ENTITY lol1 "&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;">
+  <!ENTITY lol2 "&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;">
+  <!ENTITY lol3 "&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;">
+  <!ENTITY lol4 "&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;">
+  <!ENTITY lol5 "&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;">
+  <!ENTITY lol6 "&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;">
+  <!ENTITY lol7 "&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;">
+  <!ENTITY lol8 "&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;">
+  <!ENTITY lol9 "&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;">
+]>"#,
+        )
+        .unwrap();
+
+        let err = entities.expand("&lol9;", 64 * 1024, 16).unwrap_err();
+
+        assert_eq!(XmlLimit::EntityExpansionSize, err.limit);
+    }
+
+    #[test]
+    fn test_recursive_entity_is_stopped_by_depth_limit() {
+        let entities =
+            XmlEntities::from_doctype(br#"<!DOCTYPE R [<!ENTITY a "x&b;"><!ENTITY b "y&a;">]>"#)
+                .unwrap();
+
+        let err = entities.expand("&a;", 1024, 8).unwrap_err();
+
+        assert_eq!(XmlLimit::EntityExpansionDepth, err.limit);
+    }
+}
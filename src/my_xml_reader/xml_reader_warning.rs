@@ -0,0 +1,50 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlReaderWarningKind {
+    ImplicitlyClosedElement(String),
+    CaseInsensitiveCloseTag { opened: String, found: String },
+    StrayCloseTag(String),
+    BareAmpersand,
+    InvalidXmlDeclaration(String),
+    InvalidXmlSpace(String),
+}
+
+impl fmt::Display for XmlReaderWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ImplicitlyClosedElement(name) => {
+                write!(f, "Element <{}> was closed implicitly", name)
+            }
+            Self::CaseInsensitiveCloseTag { opened, found } => write!(
+                f,
+                "Close tag </{}> matched opened tag <{}> ignoring case",
+                found, opened
+            ),
+            Self::StrayCloseTag(name) => {
+                write!(f, "Close tag </{}> has no opened tag and was ignored", name)
+            }
+            Self::BareAmpersand => write!(f, "Bare '&' is not a reference"),
+            Self::InvalidXmlDeclaration(err) => write!(f, "XML declaration is ignored: {}", err),
+            Self::InvalidXmlSpace(err) => write!(f, "xml:space is ignored: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlReaderWarning {
+    pub kind: XmlReaderWarningKind,
+    pub pos: usize,
+}
+
+impl XmlReaderWarning {
+    pub fn new(kind: XmlReaderWarningKind, pos: usize) -> Self {
+        Self { kind, pos }
+    }
+}
+
+impl fmt::Display for XmlReaderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at pos {}", self.kind, self.pos)
+    }
+}
@@ -1,6 +1,8 @@
+use std::borrow::Cow;
+
 use crate::my_xml_reader::AttributesIterator;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum XmlTagType {
     Open = 0,
     Close = 1,
@@ -18,8 +20,17 @@ pub struct XmlTagInfo<'t> {
 }
 
 impl<'t> XmlTagInfo<'t> {
-    pub fn raw_as_string(&self) -> &'t str {
-        std::str::from_utf8(&self.raw).unwrap()
+    pub fn raw_as_string(&self) -> Result<&'t str, String> {
+        std::str::from_utf8(self.raw).map_err(|err| {
+            format!(
+                "Tag <{}> at pos {} is not valid UTF-8: {}",
+                self.name, self.start_pos, err
+            )
+        })
+    }
+
+    pub fn raw_as_string_lossy(&self) -> Cow<'t, str> {
+        String::from_utf8_lossy(self.raw)
     }
 
     pub fn iterate_attributes(&'t self) -> AttributesIterator<'t> {
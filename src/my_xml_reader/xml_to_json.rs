@@ -0,0 +1,777 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::xml_utils::{find_markup_end, OPEN_CDATA_TOKEN};
+use super::{AttributesIterator, MyXmlReader, XmlTagInfo, XmlTagType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonAttributes {
+    // {"Item": {"@id": "1", "Name": "A"}}
+    #[default]
+    Prefixed,
+    // {"Item": {"@attributes": {"id": "1"}, "Name": "A"}}
+    Nested,
+}
+
+#[derive(Debug, Clone)]
+pub struct XmlToJsonOptions {
+    pub attributes: JsonAttributes,
+    pub attribute_prefix: String,
+    pub attributes_key: String,
+    pub text_key: String,
+    // Paths from the converted element such as "Feed/Item". Elements at these
+    // paths are always written as arrays, even if there is only one of them.
+    pub force_array_paths: Vec<String>,
+    pub coerce_numbers: bool,
+    pub coerce_booleans: bool,
+    pub indent: Option<String>,
+}
+
+impl Default for XmlToJsonOptions {
+    fn default() -> Self {
+        Self {
+            attributes: JsonAttributes::Prefixed,
+            attribute_prefix: "@".to_string(),
+            attributes_key: "@attributes".to_string(),
+            text_key: "#text".to_string(),
+            force_array_paths: vec![],
+            coerce_numbers: false,
+            coerce_booleans: false,
+            indent: None,
+        }
+    }
+}
+
+// Reads the next element from the reader and writes it as {"Name": value}.
+pub fn xml_to_json(
+    reader: &mut MyXmlReader,
+    options: &XmlToJsonOptions,
+    out: impl Write,
+) -> Result<(), String> {
+    loop {
+        let Some(tag) = reader.read_next_tag()? else {
+            return Err("There is no element to convert".to_string());
+        };
+
+        if !matches!(tag.tag_type, XmlTagType::Close) {
+            return element_to_json(reader, tag, options, out);
+        }
+    }
+}
+
+// Writes the element which has just been read, with all its content, as
+// {"Name": value}. The reader is left after the end of the element.
+//
+// Siblings with the same name are grouped into one array wherever they are,
+// so the element is read once into memory, grouping children by name as they
+// come, and then written.
+pub fn element_to_json<'t>(
+    reader: &mut MyXmlReader<'t>,
+    tag: XmlTagInfo<'t>,
+    options: &XmlToJsonOptions,
+    out: impl Write,
+) -> Result<(), String> {
+    if matches!(tag.tag_type, XmlTagType::Close) {
+        return Err(format!(
+            "Can not convert </{}>. Open tag is expected",
+            tag.name
+        ));
+    }
+
+    let mut converter = XmlToJsonConverter {
+        reader,
+        options,
+        json: JsonWriter::new(out, options.indent.as_deref()),
+        path: vec![],
+    };
+
+    let name = tag.name;
+    let element = converter.read_element(tag)?;
+
+    converter.json.begin_object()?;
+    converter.json.write_key(name)?;
+    converter.write_element(name, &element)?;
+    converter.json.end_object()?;
+
+    converter.json.finish()
+}
+
+struct JsonElement<'t> {
+    attributes: Vec<(&'t str, String)>,
+    text: String,
+    // Child elements grouped by name in the order of their first appearance
+    children: Vec<(&'t str, Vec<JsonElement<'t>>)>,
+}
+
+struct OpenedJsonElement<'t> {
+    name: &'t str,
+    element: JsonElement<'t>,
+    // Index of the group in element.children by the name of its elements
+    child_groups: HashMap<&'t str, usize>,
+    preserve_space: bool,
+    content_start: usize,
+}
+
+impl<'t> OpenedJsonElement<'t> {
+    fn add_child(&mut self, name: &'t str, child: JsonElement<'t>) {
+        let children = &mut self.element.children;
+
+        let index = *self.child_groups.entry(name).or_insert_with(|| {
+            children.push((name, vec![]));
+            children.len() - 1
+        });
+
+        children[index].1.push(child);
+    }
+}
+
+struct XmlToJsonConverter<'r, 't, W: Write> {
+    reader: &'r mut MyXmlReader<'t>,
+    options: &'r XmlToJsonOptions,
+    json: JsonWriter<'r, W>,
+    path: Vec<&'t str>,
+}
+
+impl<'r, 't, W: Write> XmlToJsonConverter<'r, 't, W> {
+    // Reads the element up to its close tag. Direct text is collected and
+    // children are added to their groups, so every tag is read once. Opened
+    // elements are kept on a stack rather than in recursive calls.
+    fn read_element(&mut self, tag: XmlTagInfo<'t>) -> Result<JsonElement<'t>, String> {
+        let element = self.read_attributes(&tag)?;
+
+        if !matches!(tag.tag_type, XmlTagType::Open) {
+            return Ok(element);
+        }
+
+        let mut opened = vec![OpenedJsonElement {
+            name: tag.name,
+            element,
+            child_groups: HashMap::new(),
+            preserve_space: self.reader.is_space_preserved(&tag)?,
+            content_start: tag.end_pos + 1,
+        }];
+
+        loop {
+            let Some(current) = opened.last_mut() else {
+                return Err(format!("Element <{}> is not opened", tag.name));
+            };
+
+            let Some(next_tag) = self.reader.read_next_tag()? else {
+                return Err(format!("Element <{}> is not closed", current.name));
+            };
+
+            let content = &self.reader.xml[current.content_start..next_tag.start_pos];
+            let is_closed = matches!(next_tag.tag_type, XmlTagType::Close);
+
+            // Whitespace between child elements is formatting, but the value
+            // of an element without children is kept as is
+            let keep_whitespace =
+                current.preserve_space || (is_closed && current.element.children.is_empty());
+
+            if keep_whitespace {
+                append_text(self.reader, content, &mut current.element.text)?;
+            } else if !content.iter().all(|b| is_xml_whitespace(*b)) {
+                let mut text = String::new();
+                append_text(self.reader, content, &mut text)?;
+
+                if !text.bytes().all(is_xml_whitespace) {
+                    current.element.text.push_str(&text);
+                }
+            }
+
+            current.content_start = next_tag.end_pos + 1;
+
+            match next_tag.tag_type {
+                XmlTagType::Open => {
+                    opened.push(OpenedJsonElement {
+                        name: next_tag.name,
+                        element: self.read_attributes(&next_tag)?,
+                        child_groups: HashMap::new(),
+                        preserve_space: self.reader.is_space_preserved(&next_tag)?,
+                        content_start: next_tag.end_pos + 1,
+                    });
+                }
+                XmlTagType::OpenClose => {
+                    let child = self.read_attributes(&next_tag)?;
+                    current.add_child(next_tag.name, child);
+                }
+                XmlTagType::Close => {
+                    let Some(mut closed) = opened.pop() else {
+                        return Err(format!("Element <{}> is not opened", next_tag.name));
+                    };
+
+                    if !closed.preserve_space {
+                        let handling = self.reader.get_options().value_whitespace;
+                        closed.element.text =
+                            super::normalize_whitespace(&closed.element.text, handling)
+                                .into_owned();
+                    }
+
+                    match opened.last_mut() {
+                        Some(parent) => {
+                            parent.content_start = next_tag.end_pos + 1;
+                            parent.add_child(closed.name, closed.element);
+                        }
+                        None => return Ok(closed.element),
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_attributes(&self, tag: &XmlTagInfo<'t>) -> Result<JsonElement<'t>, String> {
+        let mut result = JsonElement {
+            attributes: vec![],
+            text: String::new(),
+            children: vec![],
+        };
+
+        let mut iterator = AttributesIterator::new(tag.raw);
+
+        while let Some((name, value)) = iterator.get_next()? {
+            result
+                .attributes
+                .push((name, self.reader.expand_entities(value)?));
+        }
+
+        Ok(result)
+    }
+
+    fn write_element(&mut self, name: &'t str, element: &JsonElement<'t>) -> Result<(), String> {
+        if element.attributes.is_empty() && element.children.is_empty() {
+            return self.write_value(&element.text);
+        }
+
+        self.path.push(name);
+        self.json.begin_object()?;
+
+        match self.options.attributes {
+            JsonAttributes::Prefixed => {
+                for (name, value) in &element.attributes {
+                    self.json
+                        .write_key(&format!("{}{}", self.options.attribute_prefix, name))?;
+                    self.write_value(value)?;
+                }
+            }
+            JsonAttributes::Nested if !element.attributes.is_empty() => {
+                self.json.write_key(&self.options.attributes_key)?;
+                self.json.begin_object()?;
+
+                for (name, value) in &element.attributes {
+                    self.json.write_key(name)?;
+                    self.write_value(value)?;
+                }
+
+                self.json.end_object()?;
+            }
+            JsonAttributes::Nested => {}
+        }
+
+        if !element.text.is_empty() {
+            self.json.write_key(&self.options.text_key)?;
+            self.write_value(&element.text)?;
+        }
+
+        for (name, children) in &element.children {
+            self.json.write_key(name)?;
+
+            let is_array = children.len() > 1 || self.is_force_array_path(name);
+
+            if is_array {
+                self.json.begin_array()?;
+            }
+
+            for child in children {
+                self.write_element(name, child)?;
+            }
+
+            if is_array {
+                self.json.end_array()?;
+            }
+        }
+
+        self.json.end_object()?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn is_force_array_path(&self, name: &str) -> bool {
+        self.options.force_array_paths.iter().any(|path| {
+            let mut segments = path.split('/').filter(|s| !s.is_empty());
+
+            self.path
+                .iter()
+                .copied()
+                .chain(std::iter::once(name))
+                .all(|name| segments.next() == Some(name))
+                && segments.next().is_none()
+        })
+    }
+
+    fn write_value(&mut self, value: &str) -> Result<(), String> {
+        if self.options.coerce_booleans && (value == "true" || value == "false") {
+            return self.json.write_raw(value);
+        }
+
+        if self.options.coerce_numbers && is_json_number(value) {
+            return self.json.write_raw(value);
+        }
+
+        self.json.write_string(value)
+    }
+}
+
+fn is_xml_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+// Appends text of the content between two tags: references are expanded,
+// CDATA sections are unwrapped, comments and processing instructions are
+// dropped.
+pub(super) fn append_text(
+    reader: &MyXmlReader,
+    content: &[u8],
+    result: &mut String,
+) -> Result<(), String> {
+    let mut pos = 0;
+
+    while pos < content.len() {
+        let markup_start = content[pos..]
+            .iter()
+            .position(|b| *b == b'<')
+            .map_or(content.len(), |i| pos + i);
+
+        if markup_start > pos {
+            let text = std::str::from_utf8(&content[pos..markup_start])
+                .map_err(|err| format!("Text is not valid UTF-8: {}", err))?;
+            result.push_str(&reader.expand_entities(text)?);
+        }
+
+        if markup_start == content.len() {
+            break;
+        }
+
+        let Some(markup_end) = find_markup_end(content, markup_start) else {
+            return Err(format!(
+                "Markup is not closed: {}",
+                String::from_utf8_lossy(&content[markup_start..])
+            ));
+        };
+
+        let markup = &content[markup_start..=markup_end];
+
+        if markup.starts_with(OPEN_CDATA_TOKEN) {
+            let cdata = &markup[OPEN_CDATA_TOKEN.len()..markup.len() - 3];
+            result.push_str(
+                std::str::from_utf8(cdata)
+                    .map_err(|err| format!("CDATA is not valid UTF-8: {}", err))?,
+            );
+        }
+
+        pos = markup_end + 1;
+    }
+
+    Ok(())
+}
+
+// Only values written the way JSON writes numbers are coerced, so identifiers
+// such as "007" or "1e" stay strings.
+fn is_json_number(value: &str) -> bool {
+    let value = value.as_bytes();
+    let mut pos = 0;
+
+    let skip_digits = |pos: &mut usize| {
+        let start = *pos;
+
+        while *pos < value.len() && value[*pos].is_ascii_digit() {
+            *pos += 1;
+        }
+
+        *pos - start
+    };
+
+    if value.first() == Some(&b'-') {
+        pos += 1;
+    }
+
+    let int_start = pos;
+    let int_digits = skip_digits(&mut pos);
+
+    if int_digits == 0 || (int_digits > 1 && value[int_start] == b'0') {
+        return false;
+    }
+
+    if value.get(pos) == Some(&b'.') {
+        pos += 1;
+
+        if skip_digits(&mut pos) == 0 {
+            return false;
+        }
+    }
+
+    if matches!(value.get(pos), Some(b'e') | Some(b'E')) {
+        pos += 1;
+
+        if matches!(value.get(pos), Some(b'+') | Some(b'-')) {
+            pos += 1;
+        }
+
+        if skip_digits(&mut pos) == 0 {
+            return false;
+        }
+    }
+
+    pos == value.len()
+}
+
+struct JsonWriter<'i, W: Write> {
+    out: W,
+    indent: Option<&'i str>,
+    // Whether the current object or array already has a value
+    has_values: Vec<bool>,
+    after_key: bool,
+}
+
+impl<'i, W: Write> JsonWriter<'i, W> {
+    fn new(out: W, indent: Option<&'i str>) -> Self {
+        Self {
+            out,
+            indent,
+            has_values: vec![],
+            after_key: false,
+        }
+    }
+
+    fn begin_object(&mut self) -> Result<(), String> {
+        self.begin_value()?;
+        self.has_values.push(false);
+        self.write(b"{")
+    }
+
+    fn end_object(&mut self) -> Result<(), String> {
+        self.end_container(b"}")
+    }
+
+    fn begin_array(&mut self) -> Result<(), String> {
+        self.begin_value()?;
+        self.has_values.push(false);
+        self.write(b"[")
+    }
+
+    fn end_array(&mut self) -> Result<(), String> {
+        self.end_container(b"]")
+    }
+
+    fn write_key(&mut self, key: &str) -> Result<(), String> {
+        self.begin_value()?;
+        self.write_escaped(key)?;
+        self.write(if self.indent.is_some() { b": " } else { b":" })?;
+        self.after_key = true;
+        Ok(())
+    }
+
+    fn write_string(&mut self, value: &str) -> Result<(), String> {
+        self.begin_value()?;
+        self.write_escaped(value)
+    }
+
+    fn write_raw(&mut self, value: &str) -> Result<(), String> {
+        self.begin_value()?;
+        self.write(value.as_bytes())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        if self.indent.is_some() {
+            self.write(b"\n")?;
+        }
+
+        self.out.flush().map_err(|err| err.to_string())
+    }
+
+    fn begin_value(&mut self) -> Result<(), String> {
+        if self.after_key {
+            self.after_key = false;
+            return Ok(());
+        }
+
+        let Some(has_values) = self.has_values.last_mut() else {
+            return Ok(());
+        };
+
+        let need_comma = *has_values;
+        *has_values = true;
+
+        if need_comma {
+            self.write(b",")?;
+        }
+
+        self.write_indent(self.has_values.len())
+    }
+
+    fn end_container(&mut self, token: &[u8]) -> Result<(), String> {
+        if let Some(true) = self.has_values.pop() {
+            self.write_indent(self.has_values.len())?;
+        }
+
+        self.write(token)
+    }
+
+    fn write_indent(&mut self, level: usize) -> Result<(), String> {
+        let Some(indent) = self.indent else {
+            return Ok(());
+        };
+
+        let mut line = String::with_capacity(1 + indent.len() * level);
+        line.push('\n');
+
+        for _ in 0..level {
+            line.push_str(indent);
+        }
+
+        self.write(line.as_bytes())
+    }
+
+    fn write_escaped(&mut self, value: &str) -> Result<(), String> {
+        let mut result = String::with_capacity(value.len() + 2);
+        result.push('"');
+
+        for c in value.chars() {
+            match c {
+                '"' => result.push_str("\\\""),
+                '\\' => result.push_str("\\\\"),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+                c => result.push(c),
+            }
+        }
+
+        result.push('"');
+        self.write(result.as_bytes())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.out.write_all(data).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::my_xml_reader::{ReaderOptions, XmlWhitespaceHandling};
+
+    const FEED: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE Feed [<!ENTITY company "Acme &amp; Co">]>
+<Feed version="2" xmlns:x="urn:x">
+  <Title>&company; news</Title>
+  <Item id="1" featured="true">
+    <Name>First</Name>
+    <Price currency="EUR">10.50</Price>
+    <Tag>a</Tag>
+    <Tag>b</Tag>
+  </Item>
+  <!-- second item -->
+  <Item id="007" featured="false">
+    <Name><![CDATA[<Second>]]></Name>
+    <Price>1e3</Price>
+    <Empty/>
+  </Item>
+  <Note>Line "one"
+	tab</Note>
+  <Tag>top</Tag>
+</Feed>"#;
+
+    fn convert(xml: &str, options: &XmlToJsonOptions) -> String {
+        let mut reader = MyXmlReader::from_slice(xml.as_bytes()).unwrap();
+
+        let mut out = Vec::new();
+        xml_to_json(&mut reader, options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn pretty() -> XmlToJsonOptions {
+        XmlToJsonOptions {
+            indent: Some("  ".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_conventions() {
+        assert_eq!(
+            r##"{
+  "Feed": {
+    "@version": "2",
+    "@xmlns:x": "urn:x",
+    "Title": "Acme & Co news",
+    "Item": [
+      {
+        "@id": "1",
+        "@featured": "true",
+        "Name": "First",
+        "Price": {
+          "@currency": "EUR",
+          "#text": "10.50"
+        },
+        "Tag": [
+          "a",
+          "b"
+        ]
+      },
+      {
+        "@id": "007",
+        "@featured": "false",
+        "Name": "<Second>",
+        "Price": "1e3",
+        "Empty": ""
+      }
+    ],
+    "Note": "Line \"one\"\n\ttab",
+    "Tag": "top"
+  }
+}
+"##,
+            convert(FEED, &pretty())
+        );
+    }
+
+    #[test]
+    fn test_nested_attributes_and_coercion() {
+        let options = XmlToJsonOptions {
+            attributes: JsonAttributes::Nested,
+            text_key: "$".to_string(),
+            coerce_numbers: true,
+            coerce_booleans: true,
+            force_array_paths: vec!["Feed/Item/Name".to_string(), "Feed/Title".to_string()],
+            ..pretty()
+        };
+
+        assert_eq!(
+            r##"{
+  "Feed": {
+    "@attributes": {
+      "version": 2,
+      "xmlns:x": "urn:x"
+    },
+    "Title": [
+      "Acme & Co news"
+    ],
+    "Item": [
+      {
+        "@attributes": {
+          "id": 1,
+          "featured": true
+        },
+        "Name": [
+          "First"
+        ],
+        "Price": {
+          "@attributes": {
+            "currency": "EUR"
+          },
+          "$": 10.50
+        },
+        "Tag": [
+          "a",
+          "b"
+        ]
+      },
+      {
+        "@attributes": {
+          "id": "007",
+          "featured": false
+        },
+        "Name": [
+          "<Second>"
+        ],
+        "Price": 1e3,
+        "Empty": ""
+      }
+    ],
+    "Note": "Line \"one\"\n\ttab",
+    "Tag": "top"
+  }
+}
+"##,
+            convert(FEED, &options)
+        );
+    }
+
+    #[test]
+    fn test_compact_mixed_content_and_whitespace() {
+        let xml = r#"<P lang="en">
+  Hello <b>bold</b> and <i>italic</i>!
+  <Code xml:space="preserve">  x  <v>1</v></Code>
+  <Blank>   </Blank>
+</P>"#;
+
+        assert_eq!(
+            r##"{"P":{"@lang":"en","#text":"\n  Hello  and !\n  ","b":"bold","i":"italic","Code":{"@xml:space":"preserve","#text":"  x  ","v":"1"},"Blank":"   "}}"##,
+            convert(xml, &XmlToJsonOptions::default())
+        );
+
+        let options = ReaderOptions {
+            value_whitespace: XmlWhitespaceHandling::Collapse,
+            ..Default::default()
+        };
+
+        let mut reader = MyXmlReader::from_slice_with_options(xml.as_bytes(), options).unwrap();
+
+        let mut out = Vec::new();
+        xml_to_json(&mut reader, &XmlToJsonOptions::default(), &mut out).unwrap();
+
+        assert_eq!(
+            r##"{"P":{"@lang":"en","#text":"Hello and !","b":"bold","i":"italic","Code":{"@xml:space":"preserve","#text":"  x  ","v":"1"},"Blank":""}}"##,
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_convert_element_inside_document() {
+        let xml = "<Feed><Header><Id>1</Id></Header><Body><Item/></Body></Feed>";
+        let mut reader = MyXmlReader::from_slice(xml.as_bytes()).unwrap();
+
+        let header = reader.find_the_open_node("Feed/Header").unwrap().unwrap();
+
+        let mut out = Vec::new();
+        element_to_json(&mut reader, header, &XmlToJsonOptions::default(), &mut out).unwrap();
+
+        assert_eq!(r#"{"Header":{"Id":"1"}}"#, String::from_utf8(out).unwrap());
+
+        let body = reader.read_next_tag().unwrap().unwrap();
+        assert_eq!("Body", body.name);
+    }
+
+    #[test]
+    fn test_siblings_are_grouped_at_every_level() {
+        let xml = "<R><A>1</A><B><C>x</C><D/><C>y</C></B><A>2</A><B><C>z</C></B></R>";
+
+        assert_eq!(
+            r#"{"R":{"A":["1","2"],"B":[{"C":["x","y"],"D":""},{"C":"z"}]}}"#,
+            convert(xml, &XmlToJsonOptions::default())
+        );
+
+        let depth = 1000;
+        let xml = format!("{}v{}", "<A><B/>".repeat(depth), "</A>".repeat(depth));
+
+        let expected = format!(
+            r##"{{"A":{}{{"#text":"v","B":""}}{}}}"##,
+            r#"{"B":"","A":"#.repeat(depth - 1),
+            "}".repeat(depth - 1)
+        );
+
+        assert_eq!(expected, convert(&xml, &XmlToJsonOptions::default()));
+    }
+
+    #[test]
+    fn test_is_json_number() {
+        for value in ["0", "-1", "10.50", "1e3", "-0.5E-7", "123"] {
+            assert!(is_json_number(value), "{}", value);
+        }
+
+        for value in ["", "-", "007", "1.", ".5", "1e", "+1", "1 ", "0x10", "NaN"] {
+            assert!(!is_json_number(value), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,1053 @@
+use std::io::Write;
+
+use super::xml_to_json::append_text;
+use super::xml_utils::decode_xml_string;
+use super::{is_valid_name, AttributesIterator, MyXmlReader, MyXmlWriter, XmlTagInfo, XmlTagType};
+
+// Name of the template element which is replaced with the transformed content
+// of the matched element
+pub const XML_TEMPLATE_APPLY: &str = "xt:apply";
+
+#[derive(Debug, Clone, Default)]
+pub enum XmlTransformAction {
+    // Writes the element with its attributes changed by the attribute rules
+    #[default]
+    Copy,
+    Rename(String),
+    // Leaves out the element with its content
+    Remove,
+    // Writes the content without the element itself
+    Unwrap,
+    // Writes the template instead of the element
+    Template(XmlTemplate),
+}
+
+#[derive(Debug, Clone)]
+pub enum XmlAttributeAction {
+    Remove,
+    Rename(String),
+    // Replaces listed values, other values are kept
+    MapValues(Vec<(String, String)>),
+    // Adds the attribute or replaces its value with a value template such as
+    // "{@id}-{.}"
+    Set(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlTransformRule {
+    // Path as in find_the_open_node: names of the element and of some of its
+    // ancestors, so "Feed/Item" matches every Item inside Feed at any depth.
+    // "*" matches any name.
+    pub path: String,
+    pub action: XmlTransformAction,
+    // Attribute changes of Copy and Rename by attribute name
+    pub attributes: Vec<(String, XmlAttributeAction)>,
+    // Replaces the text of Copy and Rename elements without child elements
+    // if the trimmed text is one of the listed values, e.g. "Y" -> "true"
+    pub values: Vec<(String, String)>,
+}
+
+impl XmlTransformRule {
+    pub fn new(path: &str, action: XmlTransformAction) -> Self {
+        Self {
+            path: path.to_string(),
+            action,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlTransformOptions {
+    // The first rule matching an element is applied to it. Elements which no
+    // rule matches are copied.
+    pub rules: Vec<XmlTransformRule>,
+    // Drops whitespace-only text, e.g. to indent the output with
+    // MyXmlWriter::with_indent instead
+    pub strip_whitespace: bool,
+}
+
+#[derive(Debug, Clone)]
+enum ValuePart {
+    Literal(String),
+    // {@name}: value of an attribute of the matched element
+    Attribute(String),
+    // {.}: trimmed text of the matched element without its child elements
+    Text,
+}
+
+#[derive(Debug, Clone)]
+enum TemplateOp {
+    StartElement(String, Vec<(String, Vec<ValuePart>)>),
+    Text(Vec<ValuePart>),
+    EndElement,
+    Apply,
+}
+
+// Output of a Template action: elements, attributes and text as XML with
+// "{@name}" and "{.}" in attribute values and text, and at most one
+// <xt:apply/> where the content of the matched element goes. Whitespace-only
+// text of the template is dropped.
+#[derive(Debug, Clone)]
+pub struct XmlTemplate {
+    pub source: String,
+    ops: Vec<TemplateOp>,
+}
+
+impl XmlTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        // Wrapped, so the template may have several top-level nodes
+        let wrapped = format!("<t>{}</t>", template);
+        let mut reader = MyXmlReader::from_slice(wrapped.as_bytes())?;
+        let mut ops = vec![];
+        let mut content_start = 0;
+
+        while let Some(tag) = reader.read_next_tag()? {
+            if tag.level > 0 || tag.tag_type == XmlTagType::Close {
+                let text =
+                    String::from_utf8_lossy(&wrapped.as_bytes()[content_start..tag.start_pos]);
+
+                if text.contains("<!--") || text.contains("<?") || text.contains("<![CDATA[") {
+                    return Err(format!(
+                        "Template '{}' can contain only elements and text",
+                        template
+                    ));
+                }
+
+                if !text.trim_matches(is_whitespace).is_empty() {
+                    ops.push(TemplateOp::Text(parse_value(&decode_xml_string(&text))?));
+                }
+            }
+
+            content_start = tag.end_pos + 1;
+
+            if tag.level == 0 {
+                continue;
+            }
+
+            match tag.tag_type {
+                XmlTagType::Close => ops.push(TemplateOp::EndElement),
+                _ if tag.name == XML_TEMPLATE_APPLY => {
+                    if ops.iter().any(|op| matches!(op, TemplateOp::Apply)) {
+                        return Err(format!(
+                            "Template '{}' can contain only one <{}/>",
+                            template, XML_TEMPLATE_APPLY
+                        ));
+                    }
+
+                    ops.push(TemplateOp::Apply);
+
+                    if let XmlTagType::Open = tag.tag_type {
+                        content_start = reader.skip_node(&tag)?.end;
+                    }
+                }
+                _ => {
+                    let mut attributes = vec![];
+                    let mut iterator = AttributesIterator::new(tag.raw);
+
+                    while let Some((name, value)) = iterator.get_next()? {
+                        let value = parse_value(&decode_xml_string(value))?;
+                        attributes.push((name.to_string(), value));
+                    }
+
+                    ops.push(TemplateOp::StartElement(tag.name.to_string(), attributes));
+
+                    if let XmlTagType::OpenClose = tag.tag_type {
+                        ops.push(TemplateOp::EndElement);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            source: template.to_string(),
+            ops,
+        })
+    }
+
+    // Tells whether the template is a single element, so it can replace the
+    // root element of the output
+    fn has_single_root(&self) -> bool {
+        let mut depth = 0;
+        let mut roots = 0;
+
+        for op in &self.ops {
+            match op {
+                TemplateOp::StartElement(..) => {
+                    if depth == 0 {
+                        roots += 1;
+                    }
+
+                    depth += 1;
+                }
+                TemplateOp::EndElement => depth -= 1,
+                TemplateOp::Text(_) | TemplateOp::Apply if depth == 0 => return false,
+                TemplateOp::Text(_) | TemplateOp::Apply => {}
+            }
+        }
+
+        roots == 1
+    }
+}
+
+// Reads the document and writes it through the rules without building a
+// tree. Only the root element is written: the caller writes the declaration
+// and finishes the writer.
+pub fn transform_xml<W: Write>(
+    xml: &[u8],
+    options: &XmlTransformOptions,
+    writer: &mut MyXmlWriter<W>,
+) -> Result<(), String> {
+    let rules = options
+        .rules
+        .iter()
+        .map(CompiledRule::new)
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut transformer = XmlTransformer {
+        reader: MyXmlReader::from_slice(xml)?,
+        writer,
+        rules: &rules,
+        strip_whitespace: options.strip_whitespace,
+        frames: vec![],
+    };
+
+    transformer.transform()
+}
+
+struct CompiledRule<'r> {
+    segments: Vec<&'r str>,
+    rule: &'r XmlTransformRule,
+    // Values of XmlAttributeAction::Set
+    set_values: Vec<(&'r str, Vec<ValuePart>)>,
+}
+
+impl<'r> CompiledRule<'r> {
+    fn new(rule: &'r XmlTransformRule) -> Result<Self, String> {
+        let segments: Vec<&str> = rule.path.split('/').collect();
+
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(format!("Path '{}' of a rule is not valid", rule.path));
+        }
+
+        if let XmlTransformAction::Rename(new_name) = &rule.action {
+            if !is_valid_name(new_name) {
+                return Err(format!(
+                    "Rule '{}' renames elements to invalid name '{}'",
+                    rule.path, new_name
+                ));
+            }
+        }
+
+        let mut set_values = vec![];
+
+        for (name, action) in &rule.attributes {
+            match action {
+                XmlAttributeAction::Rename(new_name) if !is_valid_name(new_name) => {
+                    return Err(format!(
+                        "Rule '{}' renames attribute '{}' to invalid name '{}'",
+                        rule.path, name, new_name
+                    ));
+                }
+                XmlAttributeAction::Set(_) if !is_valid_name(name) => {
+                    return Err(format!(
+                        "Rule '{}' sets attribute with invalid name '{}'",
+                        rule.path, name
+                    ));
+                }
+                XmlAttributeAction::Set(value) => {
+                    set_values.push((name.as_str(), parse_value(value)?));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            segments,
+            rule,
+            set_values,
+        })
+    }
+
+    // The last segment is the element itself, the others are its ancestors in
+    // the same order but not necessarily adjacent
+    fn is_match(&self, ancestors: &[&str], name: &str) -> bool {
+        let Some((last, segments)) = self.segments.split_last() else {
+            return false;
+        };
+
+        let mut ancestors = ancestors.iter();
+
+        is_name_match(last, name)
+            && segments
+                .iter()
+                .all(|segment| ancestors.any(|ancestor| is_name_match(segment, ancestor)))
+    }
+}
+
+// What is written when an element of the input is closed
+struct Frame {
+    after_content: Vec<RenderedOp>,
+    end_elements: usize,
+}
+
+#[derive(Debug, Clone)]
+enum RenderedOp {
+    StartElement(String, Vec<(String, String)>),
+    Text(String),
+    EndElement,
+    Apply,
+}
+
+struct XmlTransformer<'r, 't, 'w, W: Write> {
+    reader: MyXmlReader<'t>,
+    writer: &'w mut MyXmlWriter<W>,
+    rules: &'r [CompiledRule<'r>],
+    strip_whitespace: bool,
+    frames: Vec<Frame>,
+}
+
+impl<'r, 't, 'w, W: Write> XmlTransformer<'r, 't, 'w, W> {
+    fn transform(&mut self) -> Result<(), String> {
+        let xml = self.reader.xml;
+        let mut content_start = 0;
+
+        while let Some(tag) = self.reader.read_next_tag()? {
+            if !self.frames.is_empty() {
+                self.write_text(&xml[content_start..tag.start_pos])?;
+            }
+
+            content_start = tag.end_pos + 1;
+
+            match tag.tag_type {
+                XmlTagType::Close => self.close_element()?,
+                _ => {
+                    if let Some(content_end) = self.open_element(&tag)? {
+                        content_start = content_end;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the end of the element if its content is skipped
+    fn open_element(&mut self, tag: &XmlTagInfo<'t>) -> Result<Option<usize>, String> {
+        let rules = self.rules;
+
+        let ancestors: Vec<&str> = self.reader.opened_nodes[..tag.level]
+            .iter()
+            .map(|node| node.name)
+            .collect();
+
+        let rule = rules
+            .iter()
+            .find(|rule| rule.is_match(&ancestors, tag.name));
+        let action = rule.map_or(&XmlTransformAction::Copy, |rule| &rule.rule.action);
+        let mut own_text = None;
+
+        if let (Some(rule), 0, 0) = (rule, tag.level, self.writer.get_level()) {
+            check_root_rule(rule, tag.name)?;
+        }
+
+        let name = match action {
+            XmlTransformAction::Remove => return self.skip_content(tag),
+            XmlTransformAction::Unwrap => {
+                self.push_frame(tag, vec![], 0)?;
+                return Ok(None);
+            }
+            XmlTransformAction::Template(template) => {
+                let mut ops = vec![];
+
+                for op in &template.ops {
+                    ops.push(self.render_op(op, tag, &mut own_text)?);
+                }
+
+                let apply = ops.iter().position(|op| matches!(op, RenderedOp::Apply));
+                let after_content = match apply {
+                    Some(index) => ops.split_off(index + 1),
+                    None => vec![],
+                };
+
+                self.write_ops(&ops)?;
+
+                if apply.is_none() {
+                    return self.skip_content(tag);
+                }
+
+                self.push_frame(tag, after_content, 0)?;
+                return Ok(None);
+            }
+            XmlTransformAction::Copy => tag.name,
+            XmlTransformAction::Rename(name) => name.as_str(),
+        };
+
+        self.writer.start_element(name)?;
+
+        let Some(rule) = rule else {
+            self.write_raw_attributes(tag)?;
+            self.push_frame(tag, vec![], 1)?;
+            return Ok(None);
+        };
+
+        self.write_attributes(tag, rule, &mut own_text)?;
+
+        if !rule.rule.values.is_empty() {
+            let (text, has_child_elements) = self.read_own_text(tag)?;
+            let text = text.trim_matches(is_whitespace);
+
+            let mapped = rule.rule.values.iter().find(|(from, _)| from == text);
+
+            if let (Some((_, to)), false) = (mapped, has_child_elements) {
+                self.writer.write_text(to)?;
+                self.writer.end_element()?;
+                return self.skip_content(tag);
+            }
+        }
+
+        self.push_frame(tag, vec![], 1)?;
+        Ok(None)
+    }
+
+    fn push_frame(
+        &mut self,
+        tag: &XmlTagInfo<'t>,
+        after_content: Vec<RenderedOp>,
+        end_elements: usize,
+    ) -> Result<(), String> {
+        self.frames.push(Frame {
+            after_content,
+            end_elements,
+        });
+
+        // An empty element has no close tag, so it is closed right away
+        match tag.tag_type {
+            XmlTagType::OpenClose => self.close_element(),
+            _ => Ok(()),
+        }
+    }
+
+    fn skip_content(&mut self, tag: &XmlTagInfo<'t>) -> Result<Option<usize>, String> {
+        match tag.tag_type {
+            XmlTagType::Open => Ok(Some(self.reader.skip_node(tag)?.end)),
+            _ => Ok(None),
+        }
+    }
+
+    fn close_element(&mut self) -> Result<(), String> {
+        let Some(frame) = self.frames.pop() else {
+            return Ok(());
+        };
+
+        self.write_ops(&frame.after_content)?;
+
+        for _ in 0..frame.end_elements {
+            self.writer.end_element()?;
+        }
+
+        Ok(())
+    }
+
+    // Writes content between tags. Comments and processing instructions are
+    // written as markup, so whitespace around them can be dropped.
+    fn write_text(&mut self, raw: &[u8]) -> Result<(), String> {
+        let text =
+            std::str::from_utf8(raw).map_err(|err| format!("Text is not valid UTF-8: {}", err))?;
+
+        let mut pos = 0;
+        let mut text_start = 0;
+
+        while let Some(markup_start) = text[pos..].find('<').map(|start| pos + start) {
+            let markup = &text[markup_start..];
+
+            let close_token = if markup.starts_with("<![CDATA[") {
+                "]]>"
+            } else if markup.starts_with("<!--") {
+                "-->"
+            } else if markup.starts_with("<?") {
+                "?>"
+            } else {
+                pos = markup_start + 1;
+                continue;
+            };
+
+            let markup_end = markup
+                .find(close_token)
+                .map_or(text.len(), |end| markup_start + end + close_token.len());
+
+            pos = markup_end;
+
+            // CDATA sections are text
+            if close_token != "]]>" {
+                self.write_text_part(&text[text_start..markup_start])?;
+                self.writer
+                    .write_raw_markup(&text[markup_start..markup_end])?;
+                text_start = markup_end;
+            }
+        }
+
+        self.write_text_part(&text[text_start..])
+    }
+
+    fn write_text_part(&mut self, text: &str) -> Result<(), String> {
+        let is_whitespace_only = text.trim_matches(is_whitespace).is_empty();
+
+        if is_whitespace_only && (self.strip_whitespace || self.writer.get_level() == 0) {
+            return Ok(());
+        }
+
+        self.writer.write_raw_text(text)
+    }
+
+    fn write_raw_attributes(&mut self, tag: &XmlTagInfo<'t>) -> Result<(), String> {
+        let mut attributes = AttributesIterator::new(tag.raw);
+
+        while let Some((name, raw_value)) = attributes.get_next()? {
+            self.writer.write_raw_attribute(name, raw_value)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_attributes(
+        &mut self,
+        tag: &XmlTagInfo<'t>,
+        rule: &CompiledRule,
+        own_text: &mut Option<String>,
+    ) -> Result<(), String> {
+        let mut attributes = AttributesIterator::new(tag.raw);
+
+        while let Some((name, raw_value)) = attributes.get_next()? {
+            let action = rule
+                .rule
+                .attributes
+                .iter()
+                .find(|(attribute, _)| attribute == name)
+                .map(|(_, action)| action);
+
+            match action {
+                None => self.writer.write_raw_attribute(name, raw_value)?,
+                Some(XmlAttributeAction::Remove | XmlAttributeAction::Set(_)) => {}
+                Some(XmlAttributeAction::Rename(new_name)) => {
+                    self.writer.write_raw_attribute(new_name, raw_value)?
+                }
+                Some(XmlAttributeAction::MapValues(values)) => {
+                    let value = self.reader.expand_entities(raw_value)?;
+
+                    match values.iter().find(|(from, _)| *from == value) {
+                        Some((_, to)) => self.writer.write_attribute(name, to)?,
+                        None => self.writer.write_raw_attribute(name, raw_value)?,
+                    }
+                }
+            }
+        }
+
+        for (name, value) in &rule.set_values {
+            let value = self.render_value(value, tag, own_text)?;
+            self.writer.write_attribute(name, &value)?;
+        }
+
+        Ok(())
+    }
+
+    fn render_op(
+        &mut self,
+        op: &TemplateOp,
+        tag: &XmlTagInfo<'t>,
+        own_text: &mut Option<String>,
+    ) -> Result<RenderedOp, String> {
+        Ok(match op {
+            TemplateOp::StartElement(name, attributes) => {
+                let mut rendered = vec![];
+
+                for (attribute, value) in attributes {
+                    rendered.push((attribute.clone(), self.render_value(value, tag, own_text)?));
+                }
+
+                RenderedOp::StartElement(name.clone(), rendered)
+            }
+            TemplateOp::Text(value) => RenderedOp::Text(self.render_value(value, tag, own_text)?),
+            TemplateOp::EndElement => RenderedOp::EndElement,
+            TemplateOp::Apply => RenderedOp::Apply,
+        })
+    }
+
+    fn render_value(
+        &mut self,
+        parts: &[ValuePart],
+        tag: &XmlTagInfo<'t>,
+        own_text: &mut Option<String>,
+    ) -> Result<String, String> {
+        let mut result = String::new();
+
+        for part in parts {
+            match part {
+                ValuePart::Literal(literal) => result.push_str(literal),
+                ValuePart::Attribute(name) => {
+                    if let Some(value) = self.reader.get_attribute_value(tag, name)? {
+                        result.push_str(&value);
+                    }
+                }
+                ValuePart::Text => {
+                    if own_text.is_none() {
+                        let (text, _) = self.read_own_text(tag)?;
+                        *own_text = Some(text.trim_matches(is_whitespace).to_string());
+                    }
+
+                    result.push_str(own_text.as_deref().unwrap_or_default());
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn write_ops(&mut self, ops: &[RenderedOp]) -> Result<(), String> {
+        for op in ops {
+            match op {
+                RenderedOp::StartElement(name, attributes) => {
+                    self.writer.start_element(name)?;
+
+                    for (attribute, value) in attributes {
+                        self.writer.write_attribute(attribute, value)?;
+                    }
+                }
+                RenderedOp::Text(text) => self.writer.write_text(text)?,
+                RenderedOp::EndElement => self.writer.end_element()?,
+                RenderedOp::Apply => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reads ahead the text of the element which was just opened, without the
+    // content of its children, and tells whether it has child elements
+    fn read_own_text(&mut self, tag: &XmlTagInfo<'t>) -> Result<(String, bool), String> {
+        if tag.tag_type == XmlTagType::OpenClose {
+            return Ok((String::new(), false));
+        }
+
+        let checkpoint = self.reader.save_checkpoint();
+        let xml = self.reader.xml;
+
+        let mut text = String::new();
+        let mut has_child_elements = false;
+        let mut depth = 0;
+        let mut content_start = tag.end_pos + 1;
+
+        while let Some(next) = self.reader.read_next_tag()? {
+            if depth == 0 {
+                append_text(&self.reader, &xml[content_start..next.start_pos], &mut text)?;
+            }
+
+            content_start = next.end_pos + 1;
+
+            match next.tag_type {
+                XmlTagType::Open => {
+                    has_child_elements = true;
+                    depth += 1;
+                }
+                XmlTagType::OpenClose => has_child_elements = true,
+                XmlTagType::Close if depth == 0 => break,
+                XmlTagType::Close => depth -= 1,
+            }
+        }
+
+        self.reader.restore_checkpoint(&checkpoint);
+        Ok((text, has_child_elements))
+    }
+}
+
+// The output must have a single root element, so the root element of the
+// input can only be replaced with one element. It is checked before anything
+// is written.
+fn check_root_rule(rule: &CompiledRule, name: &str) -> Result<(), String> {
+    match &rule.rule.action {
+        XmlTransformAction::Remove => Err(format!(
+            "Rule '{}' can not remove the root element <{}>",
+            rule.rule.path, name
+        )),
+        XmlTransformAction::Unwrap => Err(format!(
+            "Rule '{}' can not unwrap the root element <{}>",
+            rule.rule.path, name
+        )),
+        XmlTransformAction::Template(template) if !template.has_single_root() => Err(format!(
+            "Template of rule '{}' must be a single element to replace the root element <{}>",
+            rule.rule.path, name
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Splits "id-{@id}: {.}" into literals and expressions. "{{" and "}}" are
+// literal braces.
+fn parse_value(value: &str) -> Result<Vec<ValuePart>, String> {
+    let mut result = vec![];
+    let mut literal = String::new();
+    let mut rest = value;
+
+    while let Some(pos) = rest.find(['{', '}']) {
+        literal.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(after) = rest.strip_prefix("{{") {
+            literal.push('{');
+            rest = after;
+            continue;
+        }
+
+        if let Some(after) = rest.strip_prefix("}}") {
+            literal.push('}');
+            rest = after;
+            continue;
+        }
+
+        let Some(end) = rest.find('}').filter(|_| rest.starts_with('{')) else {
+            return Err(format!(
+                "Braces of template value '{}' are not balanced",
+                value
+            ));
+        };
+
+        if !literal.is_empty() {
+            result.push(ValuePart::Literal(std::mem::take(&mut literal)));
+        }
+
+        let expression = rest[1..end].trim();
+
+        result.push(match expression.strip_prefix('@') {
+            Some(name) if is_valid_name(name) => ValuePart::Attribute(name.to_string()),
+            _ if expression == "." => ValuePart::Text,
+            _ => {
+                return Err(format!(
+                    "Expression '{{{}}}' of template value '{}' is not supported",
+                    expression, value
+                ))
+            }
+        });
+
+        rest = &rest[end + 1..];
+    }
+
+    literal.push_str(rest);
+
+    if !literal.is_empty() {
+        result.push(ValuePart::Literal(literal));
+    }
+
+    Ok(result)
+}
+
+fn is_name_match(segment: &str, name: &str) -> bool {
+    segment == "*" || segment == name
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(xml: &str, options: &XmlTransformOptions) -> String {
+        let mut writer = MyXmlWriter::new(vec![]);
+        transform_xml(xml.as_bytes(), options, &mut writer).unwrap();
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_elements_and_attributes() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE Feed [<!ENTITY yes "Y">]>
+<Feed version="1" internal="x">
+  <!-- items -->
+  <Item id="1" status="A" note='say "hi"'><Name>First &amp; best</Name><Active>&yes;</Active><Debug>a<b/></Debug></Item>
+  <Group><Item id="2" status="B"><Name><![CDATA[<Second>]]></Name><Active>N</Active></Item></Group>
+  <Active>Y</Active>
+</Feed>"#;
+
+        let options = XmlTransformOptions {
+            rules: vec![
+                XmlTransformRule {
+                    attributes: vec![
+                        ("internal".to_string(), XmlAttributeAction::Remove),
+                        (
+                            "version".to_string(),
+                            XmlAttributeAction::Set("2".to_string()),
+                        ),
+                    ],
+                    ..XmlTransformRule::new(
+                        "Feed",
+                        XmlTransformAction::Rename("Products".to_string()),
+                    )
+                },
+                XmlTransformRule::new("Debug", XmlTransformAction::Remove),
+                XmlTransformRule::new("Feed/Group", XmlTransformAction::Unwrap),
+                XmlTransformRule {
+                    attributes: vec![
+                        (
+                            "id".to_string(),
+                            XmlAttributeAction::Rename("sku".to_string()),
+                        ),
+                        (
+                            "status".to_string(),
+                            XmlAttributeAction::MapValues(vec![(
+                                "A".to_string(),
+                                "active".to_string(),
+                            )]),
+                        ),
+                        (
+                            "key".to_string(),
+                            XmlAttributeAction::Set("p-{@id}".to_string()),
+                        ),
+                    ],
+                    ..XmlTransformRule::new(
+                        "Item",
+                        XmlTransformAction::Rename("Product".to_string()),
+                    )
+                },
+                XmlTransformRule {
+                    values: vec![
+                        ("Y".to_string(), "true".to_string()),
+                        ("N".to_string(), "false".to_string()),
+                    ],
+                    ..XmlTransformRule::new("Item/Active", XmlTransformAction::Copy)
+                },
+            ],
+            strip_whitespace: true,
+        };
+
+        assert_eq!(
+            concat!(
+                r#"<Products version="2"><!-- items -->"#,
+                r#"<Product sku="1" status="active" note='say "hi"' key="p-1"><Name>First &amp; best</Name><Active>true</Active></Product>"#,
+                r#"<Product sku="2" status="B" key="p-2"><Name><![CDATA[<Second>]]></Name><Active>false</Active></Product>"#,
+                r#"<Active>Y</Active></Products>"#,
+            ),
+            transform(xml, &options)
+        );
+    }
+
+    #[test]
+    fn test_templates() {
+        let xml = r#"<Feed>
+  <Item id="7" kind="a&amp;b">  Widget <Tag>x</Tag><Tag>y</Tag>
+  </Item>
+  <Item id="8"/>
+  <Tag>z</Tag>
+</Feed>"#;
+
+        let options = XmlTransformOptions {
+            rules: vec![
+                XmlTransformRule::new(
+                    "Item",
+                    XmlTransformAction::Template(
+                        XmlTemplate::parse(
+                            r#"<Product code="{@id}{{x}}" kind="{@kind}{@missing}">
+                                 <Title>{.} &lt;{@id}&gt;</Title>
+                                 <Tags><xt:apply/></Tags>
+                               </Product>"#,
+                        )
+                        .unwrap(),
+                    ),
+                ),
+                XmlTransformRule::new(
+                    "Item/Tag",
+                    XmlTransformAction::Template(XmlTemplate::parse("<Label>{.}</Label>").unwrap()),
+                ),
+                XmlTransformRule::new(
+                    "*",
+                    XmlTransformAction::Template(
+                        XmlTemplate::parse("<Unknown name='{.}'/>text").unwrap(),
+                    ),
+                ),
+            ],
+            strip_whitespace: false,
+        };
+
+        let mut writer = MyXmlWriter::new(vec![]);
+        writer.start_element("Root").unwrap();
+        transform_xml(xml.as_bytes(), &options, &mut writer).unwrap();
+        writer.end_element().unwrap();
+
+        assert_eq!(
+            r#"<Root><Unknown name=""/>text</Root>"#,
+            String::from_utf8(writer.finish().unwrap()).unwrap()
+        );
+
+        let options = XmlTransformOptions {
+            rules: options.rules[..2].to_vec(),
+            strip_whitespace: false,
+        };
+
+        assert_eq!(
+            concat!(
+                "<Feed>\n  ",
+                r#"<Product code="7{x}" kind="a&amp;b"><Title>Widget &lt;7&gt;</Title><Tags>  Widget <Label>x</Label><Label>y</Label>"#,
+                "\n  </Tags></Product>\n  ",
+                r#"<Product code="8{x}" kind=""><Title> &lt;8&gt;</Title><Tags/></Product>"#,
+                "\n  <Tag>z</Tag>\n</Feed>",
+            ),
+            transform(xml, &options)
+        );
+    }
+
+    #[test]
+    fn test_indented_output() {
+        let xml = "<A>\n\t<B c='1'>text</B>\n\t<C/>\n</A>";
+
+        let options = XmlTransformOptions {
+            rules: vec![XmlTransformRule::new(
+                "C",
+                XmlTransformAction::Rename("D".to_string()),
+            )],
+            strip_whitespace: true,
+        };
+
+        let mut writer = MyXmlWriter::with_indent(vec![], "  ");
+        transform_xml(xml.as_bytes(), &options, &mut writer).unwrap();
+
+        assert_eq!(
+            "<A>\n  <B c=\"1\">text</B>\n  <D/>\n</A>\n",
+            String::from_utf8(writer.finish().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            "Expression '{Name}' of template value '{Name}' is not supported",
+            XmlTemplate::parse("<A>{Name}</A>").unwrap_err()
+        );
+
+        assert_eq!(
+            "Braces of template value 'a}b' are not balanced",
+            XmlTemplate::parse("<A b='a}b'/>").unwrap_err()
+        );
+
+        assert_eq!(
+            "Template '<A><xt:apply/><xt:apply/></A>' can contain only one <xt:apply/>",
+            XmlTemplate::parse("<A><xt:apply/><xt:apply/></A>").unwrap_err()
+        );
+
+        let options = XmlTransformOptions {
+            rules: vec![XmlTransformRule::new("A//B", XmlTransformAction::Remove)],
+            strip_whitespace: false,
+        };
+
+        let mut writer = MyXmlWriter::new(vec![]);
+
+        assert_eq!(
+            "Path 'A//B' of a rule is not valid",
+            transform_xml(b"<A/>", &options, &mut writer).unwrap_err()
+        );
+
+        let options = XmlTransformOptions {
+            rules: vec![XmlTransformRule::new("A", XmlTransformAction::Unwrap)],
+            strip_whitespace: false,
+        };
+
+        assert_eq!(
+            "Rule 'A' can not unwrap the root element <A>",
+            transform_xml(b"<A><B/><C/></A>", &options, &mut writer).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_names_in_rules() {
+        let test_cases = [
+            (
+                XmlTransformRule::new("A", XmlTransformAction::Rename("1B".to_string())),
+                "Rule 'A' renames elements to invalid name '1B'",
+            ),
+            (
+                XmlTransformRule {
+                    attributes: vec![(
+                        "a".to_string(),
+                        XmlAttributeAction::Rename("b c".to_string()),
+                    )],
+                    ..XmlTransformRule::new("A", XmlTransformAction::Copy)
+                },
+                "Rule 'A' renames attribute 'a' to invalid name 'b c'",
+            ),
+            (
+                XmlTransformRule {
+                    attributes: vec![("d>e".to_string(), XmlAttributeAction::Set("1".to_string()))],
+                    ..XmlTransformRule::new("A", XmlTransformAction::Copy)
+                },
+                "Rule 'A' sets attribute with invalid name 'd>e'",
+            ),
+        ];
+
+        for (rule, expected) in test_cases {
+            let options = XmlTransformOptions {
+                rules: vec![rule],
+                strip_whitespace: false,
+            };
+
+            let mut writer = MyXmlWriter::new(vec![]);
+
+            assert_eq!(
+                expected,
+                transform_xml(b"<R><A a='1'/></R>", &options, &mut writer).unwrap_err()
+            );
+            assert!(writer.finish().unwrap_err().contains("not written"));
+        }
+    }
+
+    #[test]
+    fn test_root_must_stay_a_single_element() {
+        let test_cases = [
+            (
+                XmlTransformAction::Remove,
+                "Rule 'R' can not remove the root element <R>",
+            ),
+            (
+                XmlTransformAction::Unwrap,
+                "Rule 'R' can not unwrap the root element <R>",
+            ),
+            (
+                XmlTransformAction::Template(XmlTemplate::parse("<A/><B/>").unwrap()),
+                "Template of rule 'R' must be a single element to replace the root element <R>",
+            ),
+            (
+                XmlTransformAction::Template(XmlTemplate::parse("<A/>text").unwrap()),
+                "Template of rule 'R' must be a single element to replace the root element <R>",
+            ),
+            (
+                XmlTransformAction::Template(XmlTemplate::parse("<xt:apply/>").unwrap()),
+                "Template of rule 'R' must be a single element to replace the root element <R>",
+            ),
+        ];
+
+        for (action, expected) in test_cases {
+            let options = XmlTransformOptions {
+                rules: vec![XmlTransformRule::new("R", action)],
+                strip_whitespace: false,
+            };
+
+            let mut writer = MyXmlWriter::new(vec![]);
+
+            assert_eq!(
+                expected,
+                transform_xml(b"<!-- c --><R><A/></R>", &options, &mut writer).unwrap_err()
+            );
+
+            // Nothing is written
+            assert!(writer.finish().unwrap_err().contains("not written"));
+        }
+
+        let options = XmlTransformOptions {
+            rules: vec![XmlTransformRule::new(
+                "R",
+                XmlTransformAction::Template(XmlTemplate::parse("<New><xt:apply/></New>").unwrap()),
+            )],
+            strip_whitespace: false,
+        };
+
+        assert_eq!("<New><A/></New>", transform("<R><A/></R>", &options));
+    }
+}
@@ -2,10 +2,18 @@ pub const OPEN_TAG_TOKEN: u8 = b'<';
 pub const CLOSE_TAG_TOKEN: u8 = b'>';
 pub const OPEN_HEADER_TOKEN: &'static [u8] = "<?".as_bytes();
 pub const CLOSE_HEADER_TOKEN: &[u8] = "?>".as_bytes();
+pub const OPEN_COMMENT_TOKEN: &[u8] = "<!--".as_bytes();
+pub const CLOSE_COMMENT_TOKEN: &[u8] = "-->".as_bytes();
+pub const OPEN_CDATA_TOKEN: &[u8] = "<![CDATA[".as_bytes();
+pub const CLOSE_CDATA_TOKEN: &[u8] = "]]>".as_bytes();
+pub const OPEN_DECLARATION_TOKEN: &[u8] = "<!".as_bytes();
 use std::collections::HashMap;
+use std::ops::Range;
 
 use lazy_static::lazy_static;
 
+use super::XmlTagType;
+
 lazy_static! {
     static ref XML_ESC: HashMap<&'static str, &'static str> = [
         ("&quot;", "\""),
@@ -79,60 +87,287 @@ pub fn init_pos_start<'t>(xml: &'t [u8]) -> Result<usize, String> {
     };
 }
 
-pub fn find_next_token<'t>(xml: &'t [u8], token_to_find: u8, start_pos: usize) -> Option<usize> {
-    for pos in start_pos..xml.len() {
-        if xml[pos] == token_to_find {
-            return Some(pos);
+pub fn find_next_token(xml: &[u8], token_to_find: u8, start_pos: usize) -> Option<usize> {
+    let pos = super::byte_scan::find_byte(xml.get(start_pos..)?, token_to_find)?;
+    Some(start_pos + pos)
+}
+
+// A backslash escapes the next byte, so \" does not end a value. The tag
+// scanners and the attributes iterator share the rule to agree on where a
+// value ends.
+pub fn find_value_end(xml: &[u8], quote: u8, start_pos: usize) -> Option<usize> {
+    let mut pos = start_pos;
+
+    while pos < xml.len() {
+        match xml[pos] {
+            b'\\' => pos += 1,
+            b if b == quote => return Some(pos),
+            _ => {}
         }
+
+        pos += 1;
     }
 
     None
 }
 
-pub fn extract_tag_name<'t>(node_tag: &'t [u8]) -> &'t [u8] {
-    let mut loop_start: usize = 1;
+// '>' is allowed inside attribute values, so quoted values are skipped
+pub fn find_tag_end_outside_quotes(xml: &[u8], start_pos: usize) -> Option<usize> {
+    let mut pos = start_pos;
 
-    if node_tag[1] == b'/' {
-        loop_start = 2;
-    }
+    loop {
+        let found = super::byte_scan::find_any_of_three_bytes(
+            xml.get(pos..)?,
+            CLOSE_TAG_TOKEN,
+            b'"',
+            b'\'',
+        )?;
 
-    for i in loop_start..node_tag.len() - 1 {
-        if node_tag[i] <= 32 || node_tag[i] == b'/' {
-            return &node_tag[loop_start..i];
+        let found = pos + found;
+
+        if xml[found] == CLOSE_TAG_TOKEN {
+            return Some(found);
         }
+
+        pos = find_value_end(xml, xml[found], found + 1)? + 1;
     }
+}
 
-    return &node_tag[loop_start..node_tag.len() - 1];
+pub fn is_element_tag(xml: &[u8], start_pos: usize) -> bool {
+    !matches!(xml.get(start_pos + 1), Some(b'!') | Some(b'?'))
 }
 
-fn find_next_token_ext<'t>(xml: &'t [u8], token_to_find: &[u8], start_pos: usize) -> Option<usize> {
-    if token_to_find.len() == 2 {
-        for pos in start_pos..xml.len() - 1 {
-            if xml[pos] == token_to_find[0] && xml[pos + 1] == token_to_find[1] {
-                return Some(pos);
+// Returns the position of the last byte of a comment, CDATA section, processing
+// instruction or declaration which starts at start_pos.
+pub fn find_markup_end(xml: &[u8], start_pos: usize) -> Option<usize> {
+    let markup = &xml[start_pos..];
+
+    let (close_token, content_start) = if markup.starts_with(OPEN_COMMENT_TOKEN) {
+        (CLOSE_COMMENT_TOKEN, OPEN_COMMENT_TOKEN.len())
+    } else if markup.starts_with(OPEN_CDATA_TOKEN) {
+        (CLOSE_CDATA_TOKEN, OPEN_CDATA_TOKEN.len())
+    } else if markup.starts_with(OPEN_HEADER_TOKEN) {
+        (CLOSE_HEADER_TOKEN, OPEN_HEADER_TOKEN.len())
+    } else {
+        return find_declaration_end(xml, start_pos);
+    };
+
+    let pos = find_next_token_ext(xml, close_token, start_pos + content_start)?;
+    Some(pos + close_token.len() - 1)
+}
+
+fn find_declaration_end(xml: &[u8], start_pos: usize) -> Option<usize> {
+    let mut brackets = 0;
+    let mut pos = start_pos + OPEN_DECLARATION_TOKEN.len();
+
+    while pos < xml.len() {
+        match xml[pos] {
+            b'"' | b'\'' => {
+                pos = find_next_token(xml, xml[pos], pos + 1)?;
             }
+            b'[' => brackets += 1,
+            b']' => brackets -= 1,
+            CLOSE_TAG_TOKEN if brackets <= 0 => return Some(pos),
+            _ => {}
         }
-        return None;
+
+        pos += 1;
     }
 
-    for pos in start_pos..xml.len() {
-        let mut all_match = true;
+    None
+}
+
+// Finds the next element tag skipping comments, CDATA sections, processing
+// instructions and declarations. Returns the positions of '<' and '>'.
+pub fn find_next_tag(xml: &[u8], start_pos: usize) -> Result<Option<(usize, usize)>, String> {
+    let mut pos = start_pos;
+
+    while let Some(tag_start) = find_next_token(xml, OPEN_TAG_TOKEN, pos) {
+        if is_element_tag(xml, tag_start) {
+            return match find_tag_end_outside_quotes(xml, tag_start) {
+                Some(tag_end) => Ok(Some((tag_start, tag_end))),
+                None => Err(format!(
+                    "Can not find the close. Start pos is {}",
+                    tag_start
+                )),
+            };
+        }
 
-        for sub_pos in 0..token_to_find.len() {
-            if xml[pos + sub_pos] != token_to_find[sub_pos] {
-                all_match = false;
-                break;
+        if super::is_xml_declaration_at(xml, tag_start) {
+            return Err(format!(
+                "XML declaration is allowed only at the start of the document. Pos is {}",
+                tag_start
+            ));
+        }
+
+        match find_markup_end(xml, tag_start) {
+            Some(markup_end) => pos = markup_end + 1,
+            None => {
+                return Err(format!(
+                    "Can not find the end of the markup. Start pos is {}",
+                    tag_start
+                ))
             }
         }
+    }
 
-        if all_match {
-            return Some(pos);
+    Ok(None)
+}
+
+// Checks if the '&' at pos starts a character or entity reference such as
+// "&amp;" or "&#x41;".
+pub fn is_reference_at(text: &[u8], pos: usize) -> bool {
+    find_reference_end(text, pos).is_some()
+}
+
+// Returns the position of the ';' which ends the reference started by the '&'
+// at pos. Only the name is scanned, so a bare '&' costs a few bytes at most.
+pub fn find_reference_end(text: &[u8], pos: usize) -> Option<usize> {
+    for (len, b) in text[pos + 1..].iter().enumerate() {
+        match b {
+            b';' if len > 0 => return Some(pos + 1 + len),
+            b'#' | b'_' | b':' | b'.' | b'-' => {}
+            b if b.is_ascii_alphanumeric() || *b >= 0x80 => {}
+            _ => return None,
         }
     }
 
     None
 }
 
+// Returns the amount of attributes of the tag and the length of the longest
+// attribute name.
+pub fn get_attributes_stats(raw: &[u8]) -> (usize, usize) {
+    let mut amount = 0;
+    let mut max_name_len = 0;
+    let mut name_start = None;
+    let mut name_len = None;
+    let mut pos = 1;
+
+    while pos < raw.len() {
+        match raw[pos] {
+            b'"' | b'\'' => {
+                match find_value_end(raw, raw[pos], pos + 1) {
+                    Some(quote_end) => pos = quote_end,
+                    None => break,
+                }
+
+                name_start = None;
+            }
+            b'=' => {
+                if let Some(start) = name_start.take() {
+                    amount += 1;
+                    max_name_len = max_name_len.max(name_len.unwrap_or(pos - start));
+                }
+            }
+            b'/' | b'>' => name_start = None,
+            b if b.is_ascii_whitespace() => {
+                if let Some(start) = name_start {
+                    name_len.get_or_insert(pos - start);
+                }
+            }
+            _ => {
+                if raw[pos - 1].is_ascii_whitespace() {
+                    name_start = Some(pos);
+                    name_len = None;
+                }
+            }
+        }
+
+        pos += 1;
+    }
+
+    (amount, max_name_len)
+}
+
+fn is_xml_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+// Classifies the tag and finds the range of its name inside raw. Whitespace is
+// allowed after the name and before ">" or "/>", but not right after "<" or
+// "</". Close tags can not have attributes and "/" must go right before ">".
+pub fn parse_tag(raw: &[u8], start_pos: usize) -> Result<(XmlTagType, Range<usize>), String> {
+    let content_end = raw.len() - 1;
+
+    let name_start = if raw.get(1) == Some(&b'/') { 2 } else { 1 };
+
+    let name_end = raw[name_start..content_end]
+        .iter()
+        .position(|b| is_xml_whitespace(*b) || *b == b'/')
+        .map_or(content_end, |pos| name_start + pos);
+
+    if name_end == name_start {
+        return Err(format!(
+            "Tag name is expected right after '{}'. Pos is {}",
+            String::from_utf8_lossy(&raw[..name_start]),
+            start_pos
+        ));
+    }
+
+    let name = name_start..name_end;
+
+    if name_start == 2 {
+        if raw[name_end..content_end]
+            .iter()
+            .all(|b| is_xml_whitespace(*b))
+        {
+            return Ok((XmlTagType::Close, name));
+        }
+
+        return Err(format!(
+            "Close tag </{}> can have only whitespace after the name. Pos is {}",
+            String::from_utf8_lossy(&raw[name]),
+            start_pos
+        ));
+    }
+
+    let mut pos = name_end;
+
+    while pos < content_end {
+        match raw[pos] {
+            b'"' | b'\'' => match find_value_end(&raw[..content_end], raw[pos], pos + 1) {
+                Some(quote_end) => pos = quote_end,
+                None => break,
+            },
+            b'/' if pos + 1 == content_end => return Ok((XmlTagType::OpenClose, name)),
+            b'/' => {
+                return Err(format!(
+                    "'/' must go right before '>' in tag <{}>. Pos is {}",
+                    String::from_utf8_lossy(&raw[name]),
+                    start_pos + pos
+                ))
+            }
+            _ => {}
+        }
+
+        pos += 1;
+    }
+
+    Ok((XmlTagType::Open, name))
+}
+
+pub fn extract_tag_name(node_tag: &[u8]) -> &[u8] {
+    let mut loop_start: usize = 1;
+
+    if node_tag[1] == b'/' {
+        loop_start = 2;
+    }
+
+    for i in loop_start..node_tag.len() - 1 {
+        if node_tag[i] <= 32 || node_tag[i] == b'/' {
+            return &node_tag[loop_start..i];
+        }
+    }
+
+    &node_tag[loop_start..node_tag.len() - 1]
+}
+
+pub fn find_next_token_ext(xml: &[u8], token_to_find: &[u8], start_pos: usize) -> Option<usize> {
+    let pos = super::byte_scan::find_bytes(xml.get(start_pos..)?, token_to_find)?;
+    Some(start_pos + pos)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,6 +408,65 @@ mod tests {
         assert_eq!("RootNode", std::str::from_utf8(node_name).unwrap());
     }
 
+    #[test]
+    fn test_find_tag_end_outside_quotes() {
+        let xml_src = r#"<A b="1>2" c='3>4'>text</A>"#;
+
+        let end = find_tag_end_outside_quotes(xml_src.as_bytes(), 0).unwrap();
+        assert_eq!(r#"<A b="1>2" c='3>4'>"#, &xml_src[..end + 1]);
+
+        let (start, end) = find_next_tag(xml_src.as_bytes(), 0).unwrap().unwrap();
+        assert_eq!(r#"<A b="1>2" c='3>4'>"#, &xml_src[start..end + 1]);
+    }
+
+    #[test]
+    fn test_find_next_tag_skips_markup() {
+        let xml_src =
+            r#"<!DOCTYPE R [<!ENTITY a "<b>">]><!-- <c> --><?pi <d>?><![CDATA[<e>]]><R/>"#;
+
+        let (start, end) = find_next_tag(xml_src.as_bytes(), 0).unwrap().unwrap();
+
+        assert_eq!("<R/>", &xml_src[start..end + 1]);
+    }
+
+    #[test]
+    fn test_find_next_tag_with_unclosed_comment() {
+        let xml_src = "<!-- <R/>";
+
+        assert!(find_next_tag(xml_src.as_bytes(), 0).is_err());
+    }
+
+    #[test]
+    fn test_is_reference_at() {
+        let text = "a &amp; b &#x41; c & d &; e &f".as_bytes();
+
+        assert!(is_reference_at(text, 2));
+        assert!(is_reference_at(text, 10));
+        assert!(!is_reference_at(text, 19));
+        assert!(!is_reference_at(text, 23));
+        assert!(!is_reference_at(text, 28));
+    }
+
+    #[test]
+    fn test_get_attributes_stats() {
+        assert_eq!((0, 0), get_attributes_stats(b"<Root>"));
+        assert_eq!((0, 0), get_attributes_stats(b"<Root/>"));
+        assert_eq!(
+            (3, 6),
+            get_attributes_stats(br#"<Root a="1" longer = '2 b="3"' c="x=y"/>"#)
+        );
+    }
+
+    #[test]
+    fn test_find_next_token_ext() {
+        let xml_src = "<A><!-- comment --></A>";
+
+        assert_eq!(Some(3), find_next_token_ext(xml_src.as_bytes(), b"<!--", 0));
+        assert_eq!(Some(16), find_next_token_ext(xml_src.as_bytes(), b"-->", 4));
+        assert_eq!(None, find_next_token_ext(xml_src.as_bytes(), b"-->", 18));
+        assert_eq!(None, find_next_token_ext(xml_src.as_bytes(), b"-->", 100));
+    }
+
     #[test]
     fn test_init_pos_start_with_header_and_leading_space() {
         let xml_src = "   <?xml version=\"1.0\"?><Root/>";
@@ -207,4 +501,73 @@ mod tests {
 
         assert_eq!("<Root></Root>", &xml_src[pos..]);
     }
+
+    #[test]
+    fn test_parse_tag_with_whitespace() {
+        let test_cases = [
+            ("<Root>", XmlTagType::Open, "Root"),
+            ("<Root\n>", XmlTagType::Open, "Root"),
+            ("<Root\r\n\tattr=\"1\"\n>", XmlTagType::Open, "Root"),
+            ("<Root attr=\"a/b\">", XmlTagType::Open, "Root"),
+            ("<Root attr='/'>", XmlTagType::Open, "Root"),
+            ("<Root/>", XmlTagType::OpenClose, "Root"),
+            ("<Root />", XmlTagType::OpenClose, "Root"),
+            ("<Root\n/>", XmlTagType::OpenClose, "Root"),
+            ("<Root attr=\"1\"\t/>", XmlTagType::OpenClose, "Root"),
+            ("<Root attr=\"/\"/>", XmlTagType::OpenClose, "Root"),
+            ("</Root>", XmlTagType::Close, "Root"),
+            ("</Root >", XmlTagType::Close, "Root"),
+            ("</Root\r\n>", XmlTagType::Close, "Root"),
+            ("<ns:Root>", XmlTagType::Open, "ns:Root"),
+        ];
+
+        for (raw, expected_type, expected_name) in test_cases {
+            let (tag_type, name) = parse_tag(raw.as_bytes(), 0).unwrap();
+
+            assert_eq!(expected_type, tag_type, "{}", raw);
+            assert_eq!(expected_name, &raw[name], "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_parse_tag_rejects_illegal_whitespace() {
+        let test_cases = [
+            ("< Root>", "Tag name is expected right after '<'. Pos is 10"),
+            (
+                "<\nRoot/>",
+                "Tag name is expected right after '<'. Pos is 10",
+            ),
+            (
+                "</ Root>",
+                "Tag name is expected right after '</'. Pos is 10",
+            ),
+            ("<>", "Tag name is expected right after '<'. Pos is 10"),
+            ("</>", "Tag name is expected right after '</'. Pos is 10"),
+            (
+                "<Root / >",
+                "'/' must go right before '>' in tag <Root>. Pos is 16",
+            ),
+            (
+                "<Root/ >",
+                "'/' must go right before '>' in tag <Root>. Pos is 15",
+            ),
+            (
+                "</Root/>",
+                "Close tag </Root> can have only whitespace after the name. Pos is 10",
+            ),
+            (
+                "</Root a=\"1\">",
+                "Close tag </Root> can have only whitespace after the name. Pos is 10",
+            ),
+        ];
+
+        for (raw, expected) in test_cases {
+            assert_eq!(
+                expected,
+                parse_tag(raw.as_bytes(), 10).unwrap_err(),
+                "{}",
+                raw
+            );
+        }
+    }
 }
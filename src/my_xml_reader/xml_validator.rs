@@ -0,0 +1,921 @@
+use std::fmt;
+
+use super::xml_utils;
+use super::XmlDeclaration;
+
+const XML_DECLARATION_TOKEN: &[u8] = "<?xml".as_bytes();
+const DOCTYPE_TOKEN: &[u8] = "<!DOCTYPE".as_bytes();
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const PREDEFINED_ENTITIES: [&str; 5] = ["quot", "apos", "lt", "gt", "amp"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlValidationErrorKind {
+    XmlDeclarationNotAtStart,
+    InvalidXmlDeclaration(String),
+    TextOutsideRootElement,
+    NoRootElement,
+    MultipleRootElements,
+    InvalidName(String),
+    InvalidTagSyntax,
+    InvalidMarkup,
+    UnclosedMarkup,
+    DuplicateAttribute(String),
+    LessThanInAttributeValue(String),
+    InvalidReference(String),
+    UndeclaredEntity(String),
+    CDataEndInContent,
+    DoubleHyphenInComment,
+    MisplacedCData,
+    MisplacedDoctype,
+    UnexpectedCloseTag(String),
+    MismatchedCloseTag { expected: String, found: String },
+    UnclosedElements(Vec<XmlUnclosedElement>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlUnclosedElement {
+    pub name: String,
+    pub start_pos: usize,
+}
+
+impl fmt::Display for XmlValidationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::XmlDeclarationNotAtStart => {
+                write!(
+                    f,
+                    "XML declaration is allowed only at the start of the document"
+                )
+            }
+            Self::InvalidXmlDeclaration(reason) => {
+                write!(f, "Invalid XML declaration: {}", reason)
+            }
+            Self::TextOutsideRootElement => {
+                write!(f, "Text is not allowed outside the root element")
+            }
+            Self::NoRootElement => write!(f, "Document has no root element"),
+            Self::MultipleRootElements => write!(f, "Document has more than one root element"),
+            Self::InvalidName(name) => write!(f, "Invalid name '{}'", name),
+            Self::InvalidTagSyntax => write!(f, "Invalid tag syntax"),
+            Self::InvalidMarkup => write!(f, "Invalid markup declaration"),
+            Self::UnclosedMarkup => write!(f, "Markup is not closed"),
+            Self::DuplicateAttribute(name) => write!(f, "Duplicate attribute '{}'", name),
+            Self::LessThanInAttributeValue(name) => {
+                write!(f, "Value of attribute '{}' contains '<'", name)
+            }
+            Self::InvalidReference(reference) => write!(f, "Invalid reference '{}'", reference),
+            Self::UndeclaredEntity(name) => write!(f, "Entity '{}' is not declared", name),
+            Self::CDataEndInContent => write!(f, "']]>' is not allowed in content"),
+            Self::DoubleHyphenInComment => write!(f, "'--' is not allowed inside a comment"),
+            Self::MisplacedCData => write!(f, "CDATA section is allowed only inside an element"),
+            Self::MisplacedDoctype => {
+                write!(f, "DOCTYPE is allowed only once before the root element")
+            }
+            Self::UnexpectedCloseTag(name) => {
+                write!(
+                    f,
+                    "Attempt to close tag </{}>. There are no opened tags",
+                    name
+                )
+            }
+            Self::MismatchedCloseTag { expected, found } => write!(
+                f,
+                "Attempt to close tag </{}>. But last opened tag has the name <{}>",
+                found, expected
+            ),
+            Self::UnclosedElements(elements) => {
+                write!(f, "Document ended while elements are still opened:")?;
+
+                for (i, element) in elements.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(
+                        f,
+                        "{}<{}> (pos {})",
+                        separator, element.name, element.start_pos
+                    )?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlValidationError {
+    pub kind: XmlValidationErrorKind,
+    pub pos: usize,
+}
+
+impl XmlValidationError {
+    pub fn new(kind: XmlValidationErrorKind, pos: usize) -> Self {
+        Self { kind, pos }
+    }
+}
+
+impl fmt::Display for XmlValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at pos {}", self.kind, self.pos)
+    }
+}
+
+impl std::error::Error for XmlValidationError {}
+
+pub fn validate(xml: &[u8]) -> Result<(), Vec<XmlValidationError>> {
+    let mut errors = Vec::new();
+    let mut checker = XmlWellFormednessChecker::default();
+
+    let mut pos = checker.check_prolog(xml, &mut errors);
+    let mut content_start = pos;
+
+    while let Some(tag_start) = xml_utils::find_next_token(xml, xml_utils::OPEN_TAG_TOKEN, pos) {
+        let tag_end = if xml_utils::is_element_tag(xml, tag_start) {
+            xml_utils::find_tag_end_outside_quotes(xml, tag_start)
+        } else {
+            xml_utils::find_markup_end(xml, tag_start)
+        };
+
+        let Some(tag_end) = tag_end else {
+            checker.check_content(xml, content_start, tag_start, &mut errors);
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::UnclosedMarkup,
+                tag_start,
+            ));
+            content_start = xml.len();
+            break;
+        };
+
+        if xml_utils::is_element_tag(xml, tag_start) {
+            checker.check_content(xml, content_start, tag_start, &mut errors);
+            checker.check_tag(xml, tag_start, tag_end, &mut errors);
+            content_start = tag_end + 1;
+        }
+
+        pos = tag_end + 1;
+    }
+
+    checker.check_content(xml, content_start, xml.len(), &mut errors);
+    checker.check_end(xml.len(), &mut errors);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XmlWellFormednessChecker<'t> {
+    opened_nodes: Vec<(&'t str, usize)>,
+    root_found: bool,
+    doctype_found: bool,
+}
+
+impl<'t> XmlWellFormednessChecker<'t> {
+    // References are checked as in a document with a DOCTYPE, which may declare
+    // any entity
+    pub(super) fn set_doctype_found(&mut self) {
+        self.doctype_found = true;
+    }
+
+    // Checks the optional BOM and XML declaration. Returns the position where
+    // the rest of the document starts.
+    pub fn check_prolog(&mut self, xml: &'t [u8], errors: &mut Vec<XmlValidationError>) -> usize {
+        let start_pos = if xml.starts_with(UTF8_BOM) {
+            UTF8_BOM.len()
+        } else {
+            0
+        };
+
+        if !is_processing_instruction_with_target(&xml[start_pos..], XML_DECLARATION_TOKEN) {
+            return start_pos;
+        }
+
+        match XmlDeclaration::parse_at(xml, start_pos) {
+            Ok(declaration) => declaration.end_pos + 1,
+            Err(err) => {
+                errors.push(err);
+
+                match xml_utils::find_markup_end(xml, start_pos) {
+                    Some(end_pos) => end_pos + 1,
+                    None => xml.len(),
+                }
+            }
+        }
+    }
+
+    // Checks text, comments, CDATA sections, processing instructions and
+    // declarations between two element tags.
+    pub fn check_content(
+        &mut self,
+        xml: &'t [u8],
+        start_pos: usize,
+        end_pos: usize,
+        errors: &mut Vec<XmlValidationError>,
+    ) {
+        let mut pos = start_pos;
+
+        while pos < end_pos {
+            let markup_start = xml_utils::find_next_token(xml, xml_utils::OPEN_TAG_TOKEN, pos)
+                .filter(|markup_start| *markup_start < end_pos)
+                .unwrap_or(end_pos);
+
+            if markup_start > pos {
+                self.check_text(xml, pos, markup_start, errors);
+            }
+
+            if markup_start == end_pos {
+                break;
+            }
+
+            let Some(markup_end) = xml_utils::find_markup_end(xml, markup_start) else {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::UnclosedMarkup,
+                    markup_start,
+                ));
+                break;
+            };
+
+            self.check_markup(xml, markup_start, markup_end, errors);
+            pos = markup_end + 1;
+        }
+    }
+
+    pub fn check_tag(
+        &mut self,
+        xml: &'t [u8],
+        start_pos: usize,
+        end_pos: usize,
+        errors: &mut Vec<XmlValidationError>,
+    ) {
+        let raw = &xml[start_pos..end_pos + 1];
+
+        if raw.get(1) == Some(&b'/') {
+            self.check_close_tag(raw, start_pos, errors);
+            return;
+        }
+
+        let Some((name, is_open_close)) = self.check_open_tag(raw, start_pos, errors) else {
+            return;
+        };
+
+        if self.opened_nodes.is_empty() {
+            if self.root_found {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::MultipleRootElements,
+                    start_pos,
+                ));
+            }
+
+            self.root_found = true;
+        }
+
+        if !is_open_close {
+            self.opened_nodes.push((name, start_pos));
+        }
+    }
+
+    pub fn check_end(&mut self, xml_len: usize, errors: &mut Vec<XmlValidationError>) {
+        if !self.root_found {
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::NoRootElement,
+                xml_len,
+            ));
+        }
+
+        if !self.opened_nodes.is_empty() {
+            let unclosed_elements = self
+                .opened_nodes
+                .iter()
+                .map(|(name, start_pos)| XmlUnclosedElement {
+                    name: name.to_string(),
+                    start_pos: *start_pos,
+                })
+                .collect();
+
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::UnclosedElements(unclosed_elements),
+                xml_len,
+            ));
+        }
+    }
+
+    pub fn rewind_to_level(&mut self, level: usize) {
+        self.opened_nodes.truncate(level);
+    }
+
+    fn check_open_tag(
+        &mut self,
+        raw: &'t [u8],
+        start_pos: usize,
+        errors: &mut Vec<XmlValidationError>,
+    ) -> Option<(&'t str, bool)> {
+        let name_end = find_name_end(raw, 1);
+
+        if name_end == 1 {
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::InvalidTagSyntax,
+                start_pos,
+            ));
+            return None;
+        }
+
+        let name = check_name(&raw[1..name_end], start_pos + 1, errors);
+
+        // Used to keep tracking the nesting when the tag syntax is broken.
+        let is_open_close = raw[raw.len() - 2] == b'/';
+
+        let mut attributes: Vec<&[u8]> = Vec::new();
+        let mut pos = name_end;
+
+        loop {
+            let whitespace_start = pos;
+            pos = skip_whitespace(raw, pos);
+
+            match raw[pos] {
+                b'>' if pos == raw.len() - 1 => return Some((name, false)),
+                b'/' if pos == raw.len() - 2 => return Some((name, true)),
+                _ if pos == whitespace_start => {
+                    errors.push(XmlValidationError::new(
+                        XmlValidationErrorKind::InvalidTagSyntax,
+                        start_pos + pos,
+                    ));
+                    return Some((name, is_open_close));
+                }
+                _ => {}
+            }
+
+            let attr_start = pos;
+            pos = find_name_end(raw, pos);
+            let attr_name = &raw[attr_start..pos];
+
+            if attr_name.is_empty() {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::InvalidTagSyntax,
+                    start_pos + pos,
+                ));
+                return Some((name, is_open_close));
+            }
+
+            let attr_name_str = check_name(attr_name, start_pos + attr_start, errors);
+
+            if attributes.contains(&attr_name) {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::DuplicateAttribute(attr_name_str.to_string()),
+                    start_pos + attr_start,
+                ));
+            } else {
+                attributes.push(attr_name);
+            }
+
+            pos = skip_whitespace(raw, pos);
+
+            if raw[pos] != b'=' {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::InvalidTagSyntax,
+                    start_pos + pos,
+                ));
+                return Some((name, is_open_close));
+            }
+
+            pos = skip_whitespace(raw, pos + 1);
+
+            let quote = raw[pos];
+
+            if quote != b'"' && quote != b'\'' {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::InvalidTagSyntax,
+                    start_pos + pos,
+                ));
+                return Some((name, is_open_close));
+            }
+
+            let value_start = pos + 1;
+            let value_end = match xml_utils::find_value_end(raw, quote, value_start) {
+                Some(value_end) => value_end,
+                None => raw.len() - 1,
+            };
+
+            let value = &raw[value_start..value_end];
+
+            if value.contains(&xml_utils::OPEN_TAG_TOKEN) {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::LessThanInAttributeValue(attr_name_str.to_string()),
+                    start_pos + value_start,
+                ));
+            }
+
+            self.check_references(value, start_pos + value_start, errors);
+
+            pos = value_end + 1;
+        }
+    }
+
+    fn check_close_tag(
+        &mut self,
+        raw: &'t [u8],
+        start_pos: usize,
+        errors: &mut Vec<XmlValidationError>,
+    ) {
+        let name_end = find_name_end(raw, 2);
+        let name = check_name(&raw[2..name_end], start_pos + 2, errors);
+
+        if skip_whitespace(raw, name_end) != raw.len() - 1 {
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::InvalidTagSyntax,
+                start_pos,
+            ));
+        }
+
+        let Some((last_opened, _)) = self.opened_nodes.last() else {
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::UnexpectedCloseTag(name.to_string()),
+                start_pos,
+            ));
+            return;
+        };
+
+        if *last_opened == name {
+            self.opened_nodes.pop();
+            return;
+        }
+
+        errors.push(XmlValidationError::new(
+            XmlValidationErrorKind::MismatchedCloseTag {
+                expected: last_opened.to_string(),
+                found: name.to_string(),
+            },
+            start_pos,
+        ));
+
+        // Keep validating the rest of the document as if the close tag ended
+        // the nearest opened node with the same name.
+        if let Some(index) = self
+            .opened_nodes
+            .iter()
+            .rposition(|(opened, _)| *opened == name)
+        {
+            self.opened_nodes.truncate(index);
+        }
+    }
+
+    fn check_text(
+        &mut self,
+        xml: &'t [u8],
+        start_pos: usize,
+        end_pos: usize,
+        errors: &mut Vec<XmlValidationError>,
+    ) {
+        let text = &xml[start_pos..end_pos];
+
+        if self.opened_nodes.is_empty() {
+            if let Some(pos) = text.iter().position(|b| !b.is_ascii_whitespace()) {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::TextOutsideRootElement,
+                    start_pos + pos,
+                ));
+            }
+
+            return;
+        }
+
+        if let Some(pos) = xml_utils::find_next_token_ext(text, xml_utils::CLOSE_CDATA_TOKEN, 0) {
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::CDataEndInContent,
+                start_pos + pos,
+            ));
+        }
+
+        self.check_references(text, start_pos, errors);
+    }
+
+    fn check_markup(
+        &mut self,
+        xml: &'t [u8],
+        start_pos: usize,
+        end_pos: usize,
+        errors: &mut Vec<XmlValidationError>,
+    ) {
+        let markup = &xml[start_pos..end_pos + 1];
+
+        if markup.starts_with(xml_utils::OPEN_COMMENT_TOKEN) {
+            let content_start = xml_utils::OPEN_COMMENT_TOKEN.len();
+            let content_end = markup.len() - xml_utils::CLOSE_COMMENT_TOKEN.len();
+
+            // The end of the comment may overlap with its start as in "<!-->".
+            let content = markup.get(content_start..content_end).unwrap_or_default();
+
+            if let Some(pos) = xml_utils::find_next_token_ext(content, b"--", 0) {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::DoubleHyphenInComment,
+                    start_pos + content_start + pos,
+                ));
+            } else if content.ends_with(b"-") || content_end < content_start {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::DoubleHyphenInComment,
+                    end_pos - 2,
+                ));
+            }
+        } else if markup.starts_with(xml_utils::OPEN_CDATA_TOKEN) {
+            if self.opened_nodes.is_empty() {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::MisplacedCData,
+                    start_pos,
+                ));
+            }
+        } else if markup.starts_with(xml_utils::OPEN_HEADER_TOKEN) {
+            let target_end = find_name_end(markup, 2);
+            let target = &markup[2..target_end];
+
+            if target.eq_ignore_ascii_case(b"xml") {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::XmlDeclarationNotAtStart,
+                    start_pos,
+                ));
+            } else {
+                check_name(target, start_pos + 2, errors);
+            }
+        } else if markup.starts_with(DOCTYPE_TOKEN) {
+            if self.root_found || self.doctype_found {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::MisplacedDoctype,
+                    start_pos,
+                ));
+            }
+
+            self.doctype_found = true;
+        } else {
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::InvalidMarkup,
+                start_pos,
+            ));
+        }
+    }
+
+    fn check_references(
+        &self,
+        text: &[u8],
+        start_pos: usize,
+        errors: &mut Vec<XmlValidationError>,
+    ) {
+        let mut pos = 0;
+
+        while let Some(amp_pos) = xml_utils::find_next_token(text, b'&', pos) {
+            let reference_end = text[amp_pos + 1..]
+                .iter()
+                .position(|b| *b == b';' || *b == b'&' || *b == b'<' || b.is_ascii_whitespace())
+                .map(|len| amp_pos + 1 + len)
+                .filter(|end| text[*end] == b';');
+
+            let Some(reference_end) = reference_end else {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::InvalidReference("&".to_string()),
+                    start_pos + amp_pos,
+                ));
+                pos = amp_pos + 1;
+                continue;
+            };
+
+            let reference = &text[amp_pos + 1..reference_end];
+
+            if let Some(kind) = self.check_reference(reference) {
+                errors.push(XmlValidationError::new(kind, start_pos + amp_pos));
+            }
+
+            pos = reference_end + 1;
+        }
+    }
+
+    fn check_reference(&self, reference: &[u8]) -> Option<XmlValidationErrorKind> {
+        let reference_str = String::from_utf8_lossy(reference);
+
+        if let Some(char_ref) = reference.strip_prefix(b"#") {
+            let code = match char_ref.strip_prefix(b"x") {
+                Some(hex) => parse_digits(hex, 16),
+                None => parse_digits(char_ref, 10),
+            };
+
+            return match code {
+                Some(code) if is_xml_char(code) => None,
+                _ => Some(XmlValidationErrorKind::InvalidReference(format!(
+                    "&{};",
+                    reference_str
+                ))),
+            };
+        }
+
+        let Ok(name) = std::str::from_utf8(reference) else {
+            return Some(XmlValidationErrorKind::InvalidReference(format!(
+                "&{};",
+                reference_str
+            )));
+        };
+
+        if !is_valid_name(name) {
+            return Some(XmlValidationErrorKind::InvalidReference(format!(
+                "&{};",
+                name
+            )));
+        }
+
+        if PREDEFINED_ENTITIES.contains(&name) || self.doctype_found {
+            return None;
+        }
+
+        Some(XmlValidationErrorKind::UndeclaredEntity(name.to_string()))
+    }
+}
+
+fn is_processing_instruction_with_target(xml: &[u8], token: &[u8]) -> bool {
+    if !xml.starts_with(token) {
+        return false;
+    }
+
+    match xml.get(token.len()) {
+        Some(b) => b.is_ascii_whitespace() || *b == b'?',
+        None => false,
+    }
+}
+
+fn check_name<'t>(name: &'t [u8], pos: usize, errors: &mut Vec<XmlValidationError>) -> &'t str {
+    match std::str::from_utf8(name) {
+        Ok(name) => {
+            if !is_valid_name(name) {
+                errors.push(XmlValidationError::new(
+                    XmlValidationErrorKind::InvalidName(name.to_string()),
+                    pos,
+                ));
+            }
+
+            name
+        }
+        Err(_) => {
+            errors.push(XmlValidationError::new(
+                XmlValidationErrorKind::InvalidName(String::from_utf8_lossy(name).to_string()),
+                pos,
+            ));
+
+            ""
+        }
+    }
+}
+
+fn find_name_end(raw: &[u8], start_pos: usize) -> usize {
+    let mut pos = start_pos;
+
+    while pos < raw.len() - 1 {
+        match raw[pos] {
+            b'/' | b'>' | b'=' | b'?' => break,
+            b if b.is_ascii_whitespace() => break,
+            _ => pos += 1,
+        }
+    }
+
+    pos
+}
+
+fn skip_whitespace(raw: &[u8], start_pos: usize) -> usize {
+    let mut pos = start_pos;
+
+    while pos < raw.len() - 1 && raw[pos].is_ascii_whitespace() {
+        pos += 1;
+    }
+
+    pos
+}
+
+fn parse_digits(digits: &[u8], radix: u32) -> Option<u32> {
+    if digits.is_empty() {
+        return None;
+    }
+
+    let digits = std::str::from_utf8(digits).ok()?;
+    u32::from_str_radix(digits, radix).ok()
+}
+
+fn is_xml_char(code: u32) -> bool {
+    matches!(code, 0x9 | 0xA | 0xD | 0x20..=0xD7FF | 0xE000..=0xFFFD | 0x10000..=0x10FFFF)
+}
+
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}'
+        | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}'
+        | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}'
+        | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}'
+        | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}')
+}
+
+pub(super) fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9'
+            | '\u{B7}'
+            | '\u{300}'..='\u{36F}'
+            | '\u{203F}'..='\u{2040}')
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if is_name_start_char(c) => chars.all(is_name_char),
+        _ => false,
+    }
+}
+
+pub fn is_valid_name_token(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(is_name_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_error_kinds(xml: &str) -> Vec<XmlValidationErrorKind> {
+        match validate(xml.as_bytes()) {
+            Ok(()) => vec![],
+            Err(errors) => errors.into_iter().map(|err| err.kind).collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE R>
+<!-- comment -->
+<R a="1" b='2 &amp; &#x41; &#65;'>
+    <?pi data?>
+    <A>Text &lt; &company;</A><![CDATA[<not a tag> & ]]>
+    <B/>
+</R>
+<!-- trailing comment -->
+"#;
+
+        assert_eq!(Ok(()), validate(xml.as_bytes()));
+    }
+
+    #[test]
+    fn test_greater_than_in_attribute_values() {
+        let xml = r#"<R a="1>2" b='3>'><A c="x>"/></R>"#;
+
+        assert_eq!(Ok(()), validate(xml.as_bytes()));
+    }
+
+    #[test]
+    fn test_escaped_quote_in_attribute_values() {
+        assert_eq!(Ok(()), validate(r#"<r><a c="x\"y">t</a></r>"#.as_bytes()));
+
+        assert_eq!(
+            vec![XmlValidationErrorKind::LessThanInAttributeValue(
+                "c".to_string()
+            )],
+            get_error_kinds(r#"<r><a c='\'<'/></r>"#)
+        );
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        assert_eq!(
+            vec![
+                XmlValidationErrorKind::InvalidName("1R".to_string()),
+                XmlValidationErrorKind::InvalidName("1R".to_string()),
+            ],
+            get_error_kinds("<1R></1R>")
+        );
+
+        assert_eq!(
+            vec![XmlValidationErrorKind::InvalidName("-a".to_string())],
+            get_error_kinds(r#"<R -a="1"/>"#)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_attribute() {
+        let errors = validate(r#"<R a="1" b="2" a="3"/>"#.as_bytes()).unwrap_err();
+
+        assert_eq!(
+            vec![XmlValidationError::new(
+                XmlValidationErrorKind::DuplicateAttribute("a".to_string()),
+                15
+            )],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_text_and_elements_after_root() {
+        assert_eq!(
+            vec![
+                XmlValidationErrorKind::TextOutsideRootElement,
+                XmlValidationErrorKind::MultipleRootElements,
+            ],
+            get_error_kinds("<R/>text<R2/>")
+        );
+    }
+
+    #[test]
+    fn test_unclosed_elements_at_the_end() {
+        assert_eq!(
+            vec![XmlValidationErrorKind::UnclosedElements(vec![
+                XmlUnclosedElement {
+                    name: "R".to_string(),
+                    start_pos: 0
+                },
+                XmlUnclosedElement {
+                    name: "A".to_string(),
+                    start_pos: 3
+                },
+            ])],
+            get_error_kinds("<R><A>")
+        );
+
+        assert_eq!(
+            vec![XmlValidationErrorKind::NoRootElement],
+            get_error_kinds("<!-- only comment -->")
+        );
+    }
+
+    #[test]
+    fn test_mismatched_close_tags_reports_every_violation() {
+        assert_eq!(
+            vec![
+                XmlValidationErrorKind::MismatchedCloseTag {
+                    expected: "B".to_string(),
+                    found: "A".to_string()
+                },
+                XmlValidationErrorKind::UnexpectedCloseTag("C".to_string()),
+            ],
+            get_error_kinds("<R><A><B></A></R></C>")
+        );
+    }
+
+    #[test]
+    fn test_references() {
+        assert_eq!(
+            vec![
+                XmlValidationErrorKind::InvalidReference("&".to_string()),
+                XmlValidationErrorKind::UndeclaredEntity("nbsp".to_string()),
+                XmlValidationErrorKind::InvalidReference("&#0;".to_string()),
+            ],
+            get_error_kinds("<R>A & B &nbsp; &#0;</R>")
+        );
+    }
+
+    #[test]
+    fn test_invalid_tag_syntax() {
+        assert_eq!(
+            vec![XmlValidationErrorKind::InvalidTagSyntax],
+            get_error_kinds(r#"<R a="1"b="2"/>"#)
+        );
+
+        assert_eq!(
+            vec![XmlValidationErrorKind::InvalidTagSyntax],
+            get_error_kinds(r#"<R a=1/>"#)
+        );
+
+        assert_eq!(
+            vec![XmlValidationErrorKind::LessThanInAttributeValue(
+                "a".to_string()
+            )],
+            get_error_kinds(r#"<R a="<"/>"#)
+        );
+    }
+
+    #[test]
+    fn test_misplaced_markup() {
+        assert_eq!(
+            vec![
+                XmlValidationErrorKind::XmlDeclarationNotAtStart,
+                XmlValidationErrorKind::MisplacedCData,
+                XmlValidationErrorKind::DoubleHyphenInComment,
+                XmlValidationErrorKind::MisplacedDoctype,
+            ],
+            get_error_kinds(
+                r#" <?xml version="1.0"?><![CDATA[x]]><R><!-- a -- b --></R><!DOCTYPE R>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_unclosed_markup() {
+        assert_eq!(
+            vec![
+                XmlValidationErrorKind::UnclosedMarkup,
+                XmlValidationErrorKind::UnclosedElements(vec![XmlUnclosedElement {
+                    name: "R".to_string(),
+                    start_pos: 0
+                }]),
+            ],
+            get_error_kinds("<R><!-- </R>")
+        );
+    }
+}
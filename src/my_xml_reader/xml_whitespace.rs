@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+
+use super::xml_utils;
+
+const XML_SPACE_ATTR: &[u8] = "xml:space".as_bytes();
+
+// Names follow the whiteSpace facet of XML Schema, which uses the attribute
+// value normalization rules of XML 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum XmlWhitespaceHandling {
+    #[default]
+    Preserve,
+    // Removes leading and trailing whitespace
+    Trim,
+    // Replaces every tab and line end with a space. CR LF is one line end.
+    Replace,
+    // Replaces, then collapses runs of spaces into one and trims
+    Collapse,
+}
+
+fn is_xml_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+pub fn normalize_whitespace(value: &str, handling: XmlWhitespaceHandling) -> Cow<'_, str> {
+    match handling {
+        XmlWhitespaceHandling::Preserve => Cow::Borrowed(value),
+        XmlWhitespaceHandling::Trim => Cow::Borrowed(value.trim_matches(is_xml_whitespace)),
+        XmlWhitespaceHandling::Replace => {
+            if value.contains(['\t', '\r', '\n']) {
+                // Line ends are normalized before the replacement
+                let value = value.replace("\r\n", "\n");
+                Cow::Owned(value.replace(['\t', '\r', '\n'], " "))
+            } else {
+                Cow::Borrowed(value)
+            }
+        }
+        XmlWhitespaceHandling::Collapse => {
+            let trimmed = value.trim_matches(is_xml_whitespace);
+
+            if !trimmed.contains(['\t', '\r', '\n']) && !trimmed.contains("  ") {
+                return Cow::Borrowed(trimmed);
+            }
+
+            let mut result = String::with_capacity(trimmed.len());
+
+            for word in trimmed
+                .split(is_xml_whitespace)
+                .filter(|word| !word.is_empty())
+            {
+                if !result.is_empty() {
+                    result.push(' ');
+                }
+
+                result.push_str(word);
+            }
+
+            Cow::Owned(result)
+        }
+    }
+}
+
+// Reads xml:space of the tag: Some(true) for "preserve", Some(false) for
+// "default" and None if the attribute is absent.
+pub fn get_xml_space(raw: &[u8]) -> Result<Option<bool>, String> {
+    if xml_utils::find_next_token_ext(raw, XML_SPACE_ATTR, 0).is_none() {
+        return Ok(None);
+    }
+
+    let mut attributes = super::AttributesIterator::new(raw);
+
+    while let Some((name, value)) = attributes.get_next()? {
+        if name.as_bytes() == XML_SPACE_ATTR {
+            return match value {
+                "preserve" => Ok(Some(true)),
+                "default" => Ok(Some(false)),
+                _ => Err(format!(
+                    "xml:space must be 'preserve' or 'default' but it is '{}'",
+                    value
+                )),
+            };
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_whitespace() {
+        let value = "\n    Line 1\t\tand\r\n  line 2  \n";
+
+        let test_cases = [
+            (XmlWhitespaceHandling::Preserve, value),
+            (XmlWhitespaceHandling::Trim, "Line 1\t\tand\r\n  line 2"),
+            (
+                XmlWhitespaceHandling::Replace,
+                "     Line 1  and   line 2   ",
+            ),
+            (XmlWhitespaceHandling::Collapse, "Line 1 and line 2"),
+        ];
+
+        for (handling, expected) in test_cases {
+            assert_eq!(
+                expected,
+                normalize_whitespace(value, handling),
+                "{:?}",
+                handling
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalized_value_is_not_copied() {
+        for handling in [
+            XmlWhitespaceHandling::Trim,
+            XmlWhitespaceHandling::Replace,
+            XmlWhitespaceHandling::Collapse,
+        ] {
+            assert!(matches!(
+                normalize_whitespace("a b", handling),
+                Cow::Borrowed("a b")
+            ));
+        }
+    }
+
+    #[test]
+    fn test_get_xml_space() {
+        let test_cases = [
+            ("<A>", None),
+            ("<A id=\"1\">", None),
+            ("<A xml:space=\"preserve\">", Some(true)),
+            ("<A id=\"1\" xml:space=\"default\"/>", Some(false)),
+        ];
+
+        for (raw, expected) in test_cases {
+            assert_eq!(expected, get_xml_space(raw.as_bytes()).unwrap(), "{}", raw);
+        }
+
+        assert!(get_xml_space("<A xml:space=\"keep\">".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,436 @@
+use std::collections::BTreeSet;
+
+// Regular expression of the XML Schema pattern facet. A pattern always
+// matches the whole value, so ^ and $ are ordinary characters. Character
+// class subtraction and the \s, \d, \w, \i and \c escapes (with their
+// negations) are supported, while Unicode categories and blocks (\p{...}) are
+// not.
+#[derive(Debug, Clone)]
+pub struct XsdPattern {
+    pub source: String,
+    branches: Vec<Vec<Piece>>,
+}
+
+#[derive(Debug, Clone)]
+struct Piece {
+    atom: Atom,
+    min: usize,
+    max: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Atom {
+    Char(char),
+    Class(CharClass),
+    Group(Vec<Vec<Piece>>),
+}
+
+#[derive(Debug, Clone)]
+struct CharClass {
+    negated: bool,
+    items: Vec<ClassItem>,
+    subtracted: Option<Box<CharClass>>,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    // \s, \d, \w, \i, \c or their negations
+    Escape(char),
+}
+
+impl XsdPattern {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = PatternParser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+
+        let branches = parser
+            .parse_branches()
+            .map_err(|err| format!("Pattern '{}' is not valid: {}", source, err))?;
+
+        if parser.pos < parser.chars.len() {
+            return Err(format!("Pattern '{}' is not valid: unexpected ')'", source));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            branches,
+        })
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        let chars: Vec<char> = value.chars().collect();
+        match_branches(&self.branches, &chars, 0).contains(&chars.len())
+    }
+}
+
+struct PatternParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl PatternParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("unexpected end")?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn parse_branches(&mut self) -> Result<Vec<Vec<Piece>>, String> {
+        let mut branches = vec![self.parse_branch()?];
+
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.parse_branch()?);
+        }
+
+        Ok(branches)
+    }
+
+    fn parse_branch(&mut self) -> Result<Vec<Piece>, String> {
+        let mut pieces = vec![];
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+
+            let atom = self.parse_atom()?;
+            let (min, max) = self.parse_quantifier()?;
+            pieces.push(Piece { atom, min, max });
+        }
+
+        Ok(pieces)
+    }
+
+    fn parse_atom(&mut self) -> Result<Atom, String> {
+        match self.next()? {
+            '(' => {
+                let branches = self.parse_branches()?;
+
+                if self.next()? != ')' {
+                    return Err("')' is expected".to_string());
+                }
+
+                Ok(Atom::Group(branches))
+            }
+            '[' => Ok(Atom::Class(self.parse_class()?)),
+            '.' => Ok(Atom::Class(CharClass {
+                negated: true,
+                items: vec![ClassItem::Range('\n', '\n'), ClassItem::Range('\r', '\r')],
+                subtracted: None,
+            })),
+            '\\' => match self.parse_escape()? {
+                ClassItem::Range(c, _) => Ok(Atom::Char(c)),
+                item => Ok(Atom::Class(CharClass {
+                    negated: false,
+                    items: vec![item],
+                    subtracted: None,
+                })),
+            },
+            c @ ('?' | '*' | '+' | '{' | '}' | ']') => Err(format!("'{}' must be escaped", c)),
+            c => Ok(Atom::Char(c)),
+        }
+    }
+
+    fn parse_quantifier(&mut self) -> Result<(usize, Option<usize>), String> {
+        let quantifier = match self.peek() {
+            Some('?') => (0, Some(1)),
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('{') => {
+                self.pos += 1;
+
+                let min = self.parse_number()?;
+
+                let max = match self.next()? {
+                    '}' => return Ok((min, Some(min))),
+                    ',' if self.peek() == Some('}') => None,
+                    ',' => Some(self.parse_number()?),
+                    c => return Err(format!("unexpected '{}' in a quantifier", c)),
+                };
+
+                if self.next()? != '}' {
+                    return Err("'}' is expected".to_string());
+                }
+
+                if max.is_some_and(|max| max < min) {
+                    return Err("quantifier max is less than min".to_string());
+                }
+
+                return Ok((min, max));
+            }
+            _ => return Ok((1, Some(1))),
+        };
+
+        self.pos += 1;
+        Ok(quantifier)
+    }
+
+    fn parse_number(&mut self) -> Result<usize, String> {
+        let start = self.pos;
+
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map_err(|_| "number is expected in a quantifier".to_string())
+    }
+
+    // Is called after '['
+    fn parse_class(&mut self) -> Result<CharClass, String> {
+        let mut result = CharClass {
+            negated: self.peek() == Some('^'),
+            items: vec![],
+            subtracted: None,
+        };
+
+        if result.negated {
+            self.pos += 1;
+        }
+
+        loop {
+            let c = self.next()?;
+
+            match c {
+                ']' if !result.items.is_empty() => return Ok(result),
+                '-' if self.peek() == Some('[') && !result.items.is_empty() => {
+                    self.pos += 1;
+                    result.subtracted = Some(Box::new(self.parse_class()?));
+
+                    if self.next()? != ']' {
+                        return Err("']' is expected after a subtraction".to_string());
+                    }
+
+                    return Ok(result);
+                }
+                '[' => return Err("'[' must be escaped in a character class".to_string()),
+                _ => {
+                    let item = if c == '\\' {
+                        self.parse_escape()?
+                    } else {
+                        ClassItem::Range(c, c)
+                    };
+
+                    let ClassItem::Range(from, _) = item else {
+                        result.items.push(item);
+                        continue;
+                    };
+
+                    let is_range = self.peek() == Some('-')
+                        && !matches!(self.chars.get(self.pos + 1), Some(']' | '[') | None);
+
+                    if !is_range {
+                        result.items.push(item);
+                        continue;
+                    }
+
+                    self.pos += 1;
+
+                    let to = match self.next()? {
+                        '\\' => match self.parse_escape()? {
+                            ClassItem::Range(to, _) => to,
+                            ClassItem::Escape(_) => {
+                                return Err("range can not end with a class escape".to_string())
+                            }
+                        },
+                        to => to,
+                    };
+
+                    if to < from {
+                        return Err(format!("range {}-{} is out of order", from, to));
+                    }
+
+                    result.items.push(ClassItem::Range(from, to));
+                }
+            }
+        }
+    }
+
+    // Is called after '\'
+    fn parse_escape(&mut self) -> Result<ClassItem, String> {
+        let c = self.next()?;
+
+        let c = match c {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            's' | 'S' | 'd' | 'D' | 'w' | 'W' | 'i' | 'I' | 'c' | 'C' => {
+                return Ok(ClassItem::Escape(c))
+            }
+            'p' | 'P' => return Err("Unicode categories and blocks are not supported".to_string()),
+            '\\' | '|' | '.' | '-' | '^' | '?' | '*' | '+' | '{' | '}' | '(' | ')' | '[' | ']' => c,
+            c => return Err(format!("unknown escape '\\{}'", c)),
+        };
+
+        Ok(ClassItem::Range(c, c))
+    }
+}
+
+impl CharClass {
+    fn contains(&self, c: char) -> bool {
+        let is_listed = self.items.iter().any(|item| match item {
+            ClassItem::Range(from, to) => (*from..=*to).contains(&c),
+            ClassItem::Escape(escape) => is_escape_match(*escape, c),
+        });
+
+        if is_listed == self.negated {
+            return false;
+        }
+
+        !self
+            .subtracted
+            .as_ref()
+            .is_some_and(|subtracted| subtracted.contains(c))
+    }
+}
+
+fn is_escape_match(escape: char, c: char) -> bool {
+    let is_match = match escape.to_ascii_lowercase() {
+        's' => matches!(c, ' ' | '\t' | '\n' | '\r'),
+        'd' => c.is_numeric(),
+        // All characters except punctuation, separators and other characters
+        'w' => !(c.is_ascii_punctuation() || c.is_whitespace() || c.is_control()),
+        'i' => c.is_alphabetic() || c == '_' || c == ':',
+        _ => c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.' | '\u{B7}'),
+    };
+
+    is_match == escape.is_ascii_lowercase()
+}
+
+// Every function returns all the positions where a match starting at the
+// given one may end
+fn match_branches(branches: &[Vec<Piece>], chars: &[char], start: usize) -> BTreeSet<usize> {
+    let mut result = BTreeSet::new();
+
+    for branch in branches {
+        let mut positions = BTreeSet::from([start]);
+
+        for piece in branch {
+            positions = positions
+                .into_iter()
+                .flat_map(|pos| match_piece(piece, chars, pos))
+                .collect();
+
+            if positions.is_empty() {
+                break;
+            }
+        }
+
+        result.extend(positions);
+    }
+
+    result
+}
+
+fn match_piece(piece: &Piece, chars: &[char], start: usize) -> BTreeSet<usize> {
+    let mut result = BTreeSet::new();
+
+    if piece.min == 0 {
+        result.insert(start);
+    }
+
+    let mut current = BTreeSet::from([start]);
+    let mut count = 0;
+
+    while piece.max.is_none_or(|max| count < max) {
+        count += 1;
+
+        let next: BTreeSet<usize> = current
+            .iter()
+            .flat_map(|pos| match_atom(&piece.atom, chars, *pos))
+            .collect();
+
+        // An unbounded repetition stops when it reaches no new positions
+        let is_new = next.iter().any(|pos| !result.contains(pos));
+
+        if count >= piece.min {
+            result.extend(next.iter().copied());
+        }
+
+        if next.is_empty() || (count > piece.min && !is_new) {
+            break;
+        }
+
+        current = next;
+    }
+
+    result
+}
+
+fn match_atom(atom: &Atom, chars: &[char], pos: usize) -> BTreeSet<usize> {
+    match atom {
+        Atom::Char(c) if chars.get(pos) == Some(c) => BTreeSet::from([pos + 1]),
+        Atom::Class(class) if chars.get(pos).is_some_and(|c| class.contains(*c)) => {
+            BTreeSet::from([pos + 1])
+        }
+        Atom::Group(branches) => match_branches(branches, chars, pos),
+        _ => BTreeSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, value: &str) -> bool {
+        XsdPattern::parse(pattern).unwrap().is_match(value)
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(is_match("[A-Z]{2}-\\d{3}", "AB-123"));
+        assert!(!is_match("[A-Z]{2}-\\d{3}", "AB-1234"));
+        assert!(!is_match("[A-Z]{2}-\\d{3}", "xAB-123"));
+
+        assert!(is_match("(ab|c)*d?", ""));
+        assert!(is_match("(ab|c)*d?", "abccabd"));
+        assert!(!is_match("(ab|c)*d?", "abca"));
+
+        assert!(is_match("a{2,}b{0,1}", "aaab"));
+        assert!(!is_match("a{2,}", "a"));
+        assert!(is_match("\\d+(\\.\\d{1,2})?", "10.50"));
+        assert!(!is_match("\\d+(\\.\\d{1,2})?", "10.505"));
+
+        // ^ and $ are ordinary characters
+        assert!(is_match("^a$", "^a$"));
+        assert!(!is_match("^a$", "a"));
+
+        assert!(is_match(".+", "any thing"));
+        assert!(!is_match(".", "\n"));
+        assert!(is_match("\\s*\\S+", "  x"));
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert!(is_match("[^0-9]+", "abc"));
+        assert!(!is_match("[^0-9]+", "a1"));
+        assert!(is_match("[a-z-[aeiou]]+", "xyz"));
+        assert!(!is_match("[a-z-[aeiou]]+", "xay"));
+        assert!(is_match("[-+]?[0-9]+", "-12"));
+        assert!(is_match("[\\d\\-]+", "1-2"));
+        assert!(is_match("[a\\]]", "]"));
+        assert!(is_match("\\i\\c*", "ns:name-1"));
+        assert!(!is_match("\\i\\c*", "1name"));
+        assert!(is_match("\\w+", "Zürich"));
+        assert!(!is_match("\\w", "!"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for pattern in [
+            "(a", "a)", "[a", "a{2", "a{3,1}", "*a", "[z-a]", "\\p{L}", "\\q",
+        ] {
+            assert!(XsdPattern::parse(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,1257 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::{normalize_whitespace, XmlWhitespaceHandling, XsdPattern};
+use super::{AttributesIterator, MyXmlReader, XmlTagInfo, XmlTagType};
+
+pub const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XsdBuiltinType {
+    String,
+    NormalizedString,
+    Token,
+    AnyUri,
+    Boolean,
+    Decimal,
+    Integer,
+    Long,
+    Int,
+    Short,
+    Byte,
+    NonNegativeInteger,
+    PositiveInteger,
+    Date,
+    DateTime,
+    Time,
+}
+
+impl XsdBuiltinType {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "string" | "anySimpleType" => Self::String,
+            "normalizedString" => Self::NormalizedString,
+            "token" => Self::Token,
+            "anyURI" => Self::AnyUri,
+            "boolean" => Self::Boolean,
+            "decimal" => Self::Decimal,
+            "integer" => Self::Integer,
+            "long" => Self::Long,
+            "int" => Self::Int,
+            "short" => Self::Short,
+            "byte" => Self::Byte,
+            "nonNegativeInteger" => Self::NonNegativeInteger,
+            "positiveInteger" => Self::PositiveInteger,
+            "date" => Self::Date,
+            "dateTime" => Self::DateTime,
+            "time" => Self::Time,
+            _ => return None,
+        })
+    }
+
+    fn get_whitespace(&self) -> XmlWhitespaceHandling {
+        match self {
+            Self::String => XmlWhitespaceHandling::Preserve,
+            Self::NormalizedString => XmlWhitespaceHandling::Replace,
+            _ => XmlWhitespaceHandling::Collapse,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Self::Decimal
+                | Self::Integer
+                | Self::Long
+                | Self::Int
+                | Self::Short
+                | Self::Byte
+                | Self::NonNegativeInteger
+                | Self::PositiveInteger
+        )
+    }
+
+    fn is_valid(&self, value: &str) -> bool {
+        let integer_range = match self {
+            Self::Long => Some((i64::MIN as i128, i64::MAX as i128)),
+            Self::Int => Some((i32::MIN as i128, i32::MAX as i128)),
+            Self::Short => Some((i16::MIN as i128, i16::MAX as i128)),
+            Self::Byte => Some((i8::MIN as i128, i8::MAX as i128)),
+            Self::NonNegativeInteger => Some((0, i128::MAX)),
+            Self::PositiveInteger => Some((1, i128::MAX)),
+            _ => None,
+        };
+
+        if let Some((min, max)) = integer_range {
+            return is_integer(value)
+                && value
+                    .parse::<i128>()
+                    .is_ok_and(|value| (min..=max).contains(&value));
+        }
+
+        match self {
+            Self::String | Self::NormalizedString | Self::Token | Self::AnyUri => true,
+            Self::Boolean => matches!(value, "true" | "false" | "1" | "0"),
+            Self::Decimal => is_decimal(value),
+            Self::Integer => is_integer(value),
+            Self::Date => strip_timezone(value).is_some_and(is_date),
+            Self::DateTime => strip_timezone(value).is_some_and(|value| {
+                value
+                    .split_once('T')
+                    .is_some_and(|(date, time)| is_date(date) && is_time(time))
+            }),
+            Self::Time => strip_timezone(value).is_some_and(is_time),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XsdFacets {
+    pub whitespace: Option<XmlWhitespaceHandling>,
+    pub enumeration: Vec<String>,
+    // Patterns of every derivation step. A value must match one pattern of
+    // each step.
+    pub patterns: Vec<Vec<XsdPattern>>,
+    pub length: Option<usize>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub min_inclusive: Option<String>,
+    pub max_inclusive: Option<String>,
+    pub min_exclusive: Option<String>,
+    pub max_exclusive: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XsdSimpleType {
+    pub name: Option<String>,
+    pub builtin: XsdBuiltinType,
+    pub facets: XsdFacets,
+}
+
+impl XsdSimpleType {
+    fn new(name: Option<String>, builtin: XsdBuiltinType) -> Self {
+        Self {
+            name,
+            builtin,
+            facets: XsdFacets::default(),
+        }
+    }
+
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        let handling = self
+            .facets
+            .whitespace
+            .unwrap_or(self.builtin.get_whitespace());
+        let value = normalize_whitespace(value, handling);
+        let value = value.as_ref();
+
+        if !self.builtin.is_valid(value) {
+            return Err(format!(
+                "'{}' is not a valid {}",
+                value,
+                self.get_type_name()
+            ));
+        }
+
+        let facets = &self.facets;
+
+        if !facets.enumeration.is_empty() && !facets.enumeration.iter().any(|v| v == value) {
+            return Err(format!(
+                "'{}' is not one of the allowed values: {}",
+                value,
+                facets.enumeration.join(", ")
+            ));
+        }
+
+        for patterns in &facets.patterns {
+            if !patterns.iter().any(|pattern| pattern.is_match(value)) {
+                let sources: Vec<&str> = patterns.iter().map(|p| p.source.as_str()).collect();
+
+                return Err(format!(
+                    "'{}' does not match pattern '{}'",
+                    value,
+                    sources.join("|")
+                ));
+            }
+        }
+
+        let length = value.chars().count();
+
+        if facets.length.is_some_and(|expected| length != expected)
+            || facets.min_length.is_some_and(|min| length < min)
+            || facets.max_length.is_some_and(|max| length > max)
+        {
+            return Err(format!(
+                "Length of '{}' is {} which is not allowed",
+                value, length
+            ));
+        }
+
+        let check_bound = |bound: &Option<String>,
+                           relation: &str,
+                           is_allowed: fn(Ordering) -> bool| {
+            match bound {
+                Some(bound) if !is_allowed(self.compare(value, bound)) => {
+                    Err(format!("'{}' is {} {}", value, relation, bound))
+                }
+                _ => Ok(()),
+            }
+        };
+
+        check_bound(&facets.min_inclusive, "less than", |o| o != Ordering::Less)?;
+        check_bound(&facets.max_inclusive, "greater than", |o| {
+            o != Ordering::Greater
+        })?;
+        check_bound(&facets.min_exclusive, "less than or equal to", |o| {
+            o == Ordering::Greater
+        })?;
+        check_bound(&facets.max_exclusive, "greater than or equal to", |o| {
+            o == Ordering::Less
+        })?;
+
+        Ok(())
+    }
+
+    fn get_type_name(&self) -> &str {
+        match &self.name {
+            Some(name) => name,
+            None => "value",
+        }
+    }
+
+    // Numbers are compared as numbers, dates and times as strings, which is
+    // right for values written in the same timezone.
+    fn compare(&self, value: &str, bound: &str) -> Ordering {
+        if self.builtin.is_numeric() {
+            let value: f64 = value.parse().unwrap_or(f64::NAN);
+            let bound: f64 = bound.parse().unwrap_or(f64::NAN);
+            value.total_cmp(&bound)
+        } else {
+            value.cmp(bound)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct XsdAttribute {
+    pub name: String,
+    pub simple_type: usize,
+    pub required: bool,
+    pub default: Option<String>,
+    pub fixed: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XsdTypeRef {
+    // xs:anyType, which allows any attributes and content
+    Any,
+    Simple(usize),
+    Complex(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct XsdElement {
+    pub name: String,
+    pub type_ref: XsdTypeRef,
+}
+
+#[derive(Debug, Clone)]
+pub enum XsdTerm {
+    Element(XsdElement),
+    Sequence(Vec<XsdParticle>),
+    Choice(Vec<XsdParticle>),
+    All(Vec<XsdParticle>),
+}
+
+#[derive(Debug, Clone)]
+pub struct XsdParticle {
+    pub term: XsdTerm,
+    pub min_occurs: usize,
+    // None is unbounded
+    pub max_occurs: Option<usize>,
+}
+
+impl XsdParticle {
+    pub fn find_element(&self, name: &str) -> Option<&XsdElement> {
+        match &self.term {
+            XsdTerm::Element(element) if element.name == name => Some(element),
+            XsdTerm::Element(_) => None,
+            XsdTerm::Sequence(particles) | XsdTerm::Choice(particles) | XsdTerm::All(particles) => {
+                particles
+                    .iter()
+                    .find_map(|particle| particle.find_element(name))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum XsdContent {
+    #[default]
+    Empty,
+    Simple(usize),
+    Elements(XsdParticle),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XsdComplexType {
+    pub name: Option<String>,
+    pub mixed: bool,
+    pub attributes: Vec<XsdAttribute>,
+    pub content: XsdContent,
+}
+
+// Types refer to each other by their index in complex_types and simple_types.
+// Namespaces of the validated documents are not checked: elements and
+// attributes are matched by their local names.
+#[derive(Debug, Clone, Default)]
+pub struct XsdSchema {
+    pub target_namespace: Option<String>,
+    pub elements: Vec<XsdElement>,
+    pub complex_types: Vec<XsdComplexType>,
+    pub simple_types: Vec<XsdSimpleType>,
+}
+
+impl XsdSchema {
+    // Compiles a schema from a single XSD document. Includes, imports, groups,
+    // wildcards, lists, unions and derivation of complex types by restriction
+    // are not supported and are reported as errors.
+    pub fn parse(xsd: &[u8]) -> Result<Self, String> {
+        let mut reader = MyXmlReader::from_slice(xsd)?;
+
+        let root = loop {
+            let Some(tag) = reader.read_next_tag()? else {
+                return Err("Schema document has no root element".to_string());
+            };
+
+            if !matches!(tag.tag_type, XmlTagType::Close) {
+                break tag;
+            }
+        };
+
+        let root = read_schema_node(&mut reader, root, &[])?;
+
+        if root.name != "schema" || root.namespace != XSD_NAMESPACE {
+            return Err(format!(
+                "Root element must be <schema> of namespace {}",
+                XSD_NAMESPACE
+            ));
+        }
+
+        let mut compiler = SchemaCompiler {
+            schema: XsdSchema {
+                target_namespace: root.get_attribute("targetNamespace").map(|v| v.to_string()),
+                ..Default::default()
+            },
+            complex_type_names: HashMap::new(),
+            simple_type_names: HashMap::new(),
+            builtin_types: HashMap::new(),
+            element_nodes: HashMap::new(),
+            pending_complex_types: vec![],
+            pending_simple_types: vec![],
+        };
+
+        compiler.compile(&root)?;
+
+        Ok(compiler.schema)
+    }
+
+    pub fn find_element(&self, name: &str) -> Option<&XsdElement> {
+        self.elements.iter().find(|element| element.name == name)
+    }
+}
+
+// The schema document is small, so it is read into a tree first
+struct SchemaNode {
+    name: String,
+    namespace: String,
+    attributes: Vec<(String, String)>,
+    // Namespace declarations in scope as (prefix, URI)
+    namespaces: Vec<(String, String)>,
+    children: Vec<SchemaNode>,
+    pos: usize,
+}
+
+impl SchemaNode {
+    fn get_attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn get_required_attribute(&self, name: &str) -> Result<&str, String> {
+        self.get_attribute(name).ok_or_else(|| {
+            format!(
+                "<xs:{}> at pos {} has no {} attribute",
+                self.name, self.pos, name
+            )
+        })
+    }
+
+    fn get_children(&self) -> impl Iterator<Item = &SchemaNode> {
+        self.children
+            .iter()
+            .filter(|child| child.name != "annotation")
+    }
+
+    // Returns (namespace URI, local name) of a QName attribute value
+    fn resolve_qname<'a>(&self, qname: &'a str) -> Result<(&str, &'a str), String> {
+        let (prefix, local_name) = qname.split_once(':').unwrap_or(("", qname));
+
+        match self.namespaces.iter().rev().find(|(p, _)| p == prefix) {
+            Some((_, uri)) => Ok((uri, local_name)),
+            None if prefix.is_empty() => Ok(("", local_name)),
+            None => Err(format!(
+                "Namespace prefix '{}' of '{}' is not declared",
+                prefix, qname
+            )),
+        }
+    }
+
+    fn unsupported(&self) -> String {
+        format!("<xs:{}> at pos {} is not supported", self.name, self.pos)
+    }
+}
+
+fn read_schema_node<'t>(
+    reader: &mut MyXmlReader<'t>,
+    tag: XmlTagInfo<'t>,
+    parent_namespaces: &[(String, String)],
+) -> Result<SchemaNode, String> {
+    let mut node = SchemaNode {
+        name: String::new(),
+        namespace: String::new(),
+        attributes: vec![],
+        namespaces: parent_namespaces.to_vec(),
+        children: vec![],
+        pos: tag.start_pos,
+    };
+
+    let mut attributes = AttributesIterator::new(tag.raw);
+
+    while let Some((name, value)) = attributes.get_next()? {
+        let value = reader.expand_entities(value)?;
+
+        if name == "xmlns" {
+            node.namespaces.push((String::new(), value));
+        } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+            node.namespaces.push((prefix.to_string(), value));
+        } else {
+            node.attributes.push((name.to_string(), value));
+        }
+    }
+
+    let (namespace, local_name) = node.resolve_qname(tag.name)?;
+    node.namespace = namespace.to_string();
+    node.name = local_name.to_string();
+
+    if let XmlTagType::OpenClose = tag.tag_type {
+        return Ok(node);
+    }
+
+    loop {
+        let Some(child) = reader.read_next_tag()? else {
+            return Err(format!("Element <{}> is not closed", tag.name));
+        };
+
+        match child.tag_type {
+            XmlTagType::Close => return Ok(node),
+            _ => {
+                let child = read_schema_node(reader, child, &node.namespaces)?;
+
+                if child.namespace == XSD_NAMESPACE {
+                    node.children.push(child);
+                }
+            }
+        }
+    }
+}
+
+struct SchemaCompiler<'n> {
+    schema: XsdSchema,
+    complex_type_names: HashMap<String, usize>,
+    simple_type_names: HashMap<String, usize>,
+    // Derived anonymous types keep the name of their built-in base for error
+    // messages, so the built-in types are looked up by index
+    builtin_types: HashMap<String, usize>,
+    element_nodes: HashMap<String, &'n SchemaNode>,
+    // Nodes of the types which are not compiled yet, by type index. A type is
+    // compiled when it is referenced or at the end.
+    pending_complex_types: Vec<Option<&'n SchemaNode>>,
+    pending_simple_types: Vec<Option<&'n SchemaNode>>,
+}
+
+impl<'n> SchemaCompiler<'n> {
+    fn compile(&mut self, root: &'n SchemaNode) -> Result<(), String> {
+        for node in root.get_children() {
+            match node.name.as_str() {
+                "complexType" => {
+                    let name = node.get_required_attribute("name")?;
+                    let index = self.add_complex_type(Some(name.to_string()), node);
+                    self.complex_type_names.insert(name.to_string(), index);
+                }
+                "simpleType" => {
+                    let name = node.get_required_attribute("name")?;
+                    let index = self.add_simple_type(Some(name.to_string()), node);
+                    self.simple_type_names.insert(name.to_string(), index);
+                }
+                "element" => {
+                    let name = node.get_required_attribute("name")?;
+                    self.element_nodes.insert(name.to_string(), node);
+                }
+                _ => return Err(node.unsupported()),
+            }
+        }
+
+        for node in root.get_children() {
+            if node.name == "element" {
+                let element = self.compile_element_declaration(node)?;
+                self.schema.elements.push(element);
+            }
+        }
+
+        // Compiling a type can add anonymous types, so the lengths are
+        // checked on every iteration
+        let mut index = 0;
+        while index < self.pending_complex_types.len() {
+            self.compile_complex_type(index)?;
+            index += 1;
+        }
+
+        let mut index = 0;
+        while index < self.pending_simple_types.len() {
+            self.compile_simple_type(index)?;
+            index += 1;
+        }
+
+        Ok(())
+    }
+
+    fn add_complex_type(&mut self, name: Option<String>, node: &'n SchemaNode) -> usize {
+        self.schema.complex_types.push(XsdComplexType {
+            name,
+            ..Default::default()
+        });
+        self.pending_complex_types.push(Some(node));
+        self.schema.complex_types.len() - 1
+    }
+
+    fn add_simple_type(&mut self, name: Option<String>, node: &'n SchemaNode) -> usize {
+        self.schema
+            .simple_types
+            .push(XsdSimpleType::new(name, XsdBuiltinType::String));
+        self.pending_simple_types.push(Some(node));
+        self.schema.simple_types.len() - 1
+    }
+
+    fn compile_element_declaration(&mut self, node: &'n SchemaNode) -> Result<XsdElement, String> {
+        if let Some(name) = node.get_attribute("ref") {
+            let (_, name) = node.resolve_qname(name)?;
+
+            let Some(referenced) = self.element_nodes.get(name).copied() else {
+                return Err(format!("Element '{}' is not declared", name));
+            };
+
+            if let Some(element) = self.schema.find_element(name) {
+                return Ok(element.clone());
+            }
+
+            return self.compile_element_declaration(referenced);
+        }
+
+        let name = node.get_required_attribute("name")?.to_string();
+
+        if let Some(type_name) = node.get_attribute("type") {
+            return Ok(XsdElement {
+                name,
+                type_ref: self.resolve_type(node, type_name)?,
+            });
+        }
+
+        let type_ref = match node.get_children().next() {
+            Some(child) if child.name == "complexType" => {
+                XsdTypeRef::Complex(self.add_complex_type(None, child))
+            }
+            Some(child) if child.name == "simpleType" => {
+                XsdTypeRef::Simple(self.add_simple_type(None, child))
+            }
+            Some(child) => return Err(child.unsupported()),
+            None => XsdTypeRef::Any,
+        };
+
+        Ok(XsdElement { name, type_ref })
+    }
+
+    fn resolve_type(&mut self, node: &SchemaNode, qname: &str) -> Result<XsdTypeRef, String> {
+        let (namespace, name) = node.resolve_qname(qname)?;
+
+        if namespace == XSD_NAMESPACE {
+            if name == "anyType" {
+                return Ok(XsdTypeRef::Any);
+            }
+
+            let Some(builtin) = XsdBuiltinType::from_name(name) else {
+                return Err(format!("Built-in type '{}' is not supported", qname));
+            };
+
+            return Ok(XsdTypeRef::Simple(self.get_builtin_type(qname, builtin)));
+        }
+
+        if let Some(index) = self.complex_type_names.get(name) {
+            return Ok(XsdTypeRef::Complex(*index));
+        }
+
+        match self.simple_type_names.get(name) {
+            Some(index) => Ok(XsdTypeRef::Simple(*index)),
+            None => Err(format!("Type '{}' is not defined", qname)),
+        }
+    }
+
+    fn get_builtin_type(&mut self, name: &str, builtin: XsdBuiltinType) -> usize {
+        if let Some(index) = self.builtin_types.get(name) {
+            return *index;
+        }
+
+        self.schema
+            .simple_types
+            .push(XsdSimpleType::new(Some(name.to_string()), builtin));
+        self.pending_simple_types.push(None);
+
+        let index = self.schema.simple_types.len() - 1;
+        self.builtin_types.insert(name.to_string(), index);
+        index
+    }
+
+    fn resolve_simple_type(&mut self, node: &SchemaNode, qname: &str) -> Result<usize, String> {
+        match self.resolve_type(node, qname)? {
+            XsdTypeRef::Simple(index) => {
+                self.compile_simple_type(index)?;
+                Ok(index)
+            }
+            _ => Err(format!("Type '{}' is not a simple type", qname)),
+        }
+    }
+
+    fn compile_complex_type(&mut self, index: usize) -> Result<(), String> {
+        let Some(node) = self.pending_complex_types[index].take() else {
+            return Ok(());
+        };
+
+        let mut result = XsdComplexType {
+            name: self.schema.complex_types[index].name.clone(),
+            mixed: node.get_attribute("mixed") == Some("true"),
+            ..Default::default()
+        };
+
+        for child in node.get_children() {
+            match child.name.as_str() {
+                "sequence" | "choice" | "all" => {
+                    result.content = XsdContent::Elements(self.compile_particle(child)?)
+                }
+                "attribute" => result.attributes.push(self.compile_attribute(child)?),
+                "simpleContent" => self.compile_simple_content(child, &mut result)?,
+                "complexContent" => self.compile_complex_content(child, &mut result)?,
+                _ => return Err(child.unsupported()),
+            }
+        }
+
+        self.schema.complex_types[index] = result;
+        Ok(())
+    }
+
+    fn compile_simple_content(
+        &mut self,
+        node: &'n SchemaNode,
+        result: &mut XsdComplexType,
+    ) -> Result<(), String> {
+        let Some(extension) = node.get_children().find(|child| child.name == "extension") else {
+            return Err(node.unsupported());
+        };
+
+        let base = extension.get_required_attribute("base")?;
+
+        match self.resolve_type(extension, base)? {
+            XsdTypeRef::Simple(index) => {
+                self.compile_simple_type(index)?;
+                result.content = XsdContent::Simple(index);
+            }
+            XsdTypeRef::Complex(index) => {
+                self.compile_complex_type(index)?;
+                let base = &self.schema.complex_types[index];
+
+                let XsdContent::Simple(_) = base.content else {
+                    return Err(format!(
+                        "Type '{}' has no simple content to extend",
+                        base_name(base)
+                    ));
+                };
+
+                result.content = base.content.clone();
+                result.attributes = base.attributes.clone();
+            }
+            XsdTypeRef::Any => return Err(format!("Simple content can not extend '{}'", base)),
+        }
+
+        for child in extension.get_children() {
+            match child.name.as_str() {
+                "attribute" => result.attributes.push(self.compile_attribute(child)?),
+                _ => return Err(child.unsupported()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_complex_content(
+        &mut self,
+        node: &'n SchemaNode,
+        result: &mut XsdComplexType,
+    ) -> Result<(), String> {
+        let Some(extension) = node.get_children().find(|child| child.name == "extension") else {
+            return Err(node.unsupported());
+        };
+
+        let base = extension.get_required_attribute("base")?;
+
+        let XsdTypeRef::Complex(index) = self.resolve_type(extension, base)? else {
+            return Err(format!("Complex content can not extend '{}'", base));
+        };
+
+        self.compile_complex_type(index)?;
+        let base = self.schema.complex_types[index].clone();
+
+        result.mixed |= node.get_attribute("mixed") == Some("true") || base.mixed;
+        result.attributes = base.attributes;
+        result.content = base.content;
+
+        for child in extension.get_children() {
+            match child.name.as_str() {
+                "sequence" | "choice" | "all" => {
+                    let particle = self.compile_particle(child)?;
+
+                    // Particles of the extension follow the ones of the base
+                    result.content = match std::mem::take(&mut result.content) {
+                        XsdContent::Elements(base) => XsdContent::Elements(XsdParticle {
+                            term: XsdTerm::Sequence(vec![base, particle]),
+                            min_occurs: 1,
+                            max_occurs: Some(1),
+                        }),
+                        _ => XsdContent::Elements(particle),
+                    };
+                }
+                "attribute" => result.attributes.push(self.compile_attribute(child)?),
+                _ => return Err(child.unsupported()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_particle(&mut self, node: &'n SchemaNode) -> Result<XsdParticle, String> {
+        let min_occurs = match node.get_attribute("minOccurs") {
+            Some(value) => parse_occurs(node, value)?,
+            None => 1,
+        };
+
+        let max_occurs = match node.get_attribute("maxOccurs") {
+            Some("unbounded") => None,
+            Some(value) => Some(parse_occurs(node, value)?),
+            None => Some(1),
+        };
+
+        if max_occurs.is_some_and(|max| max < min_occurs) {
+            return Err(format!(
+                "maxOccurs of <xs:{}> at pos {} is less than minOccurs",
+                node.name, node.pos
+            ));
+        }
+
+        let term = match node.name.as_str() {
+            "element" => XsdTerm::Element(self.compile_element_declaration(node)?),
+            "sequence" | "choice" | "all" => {
+                let particles = node
+                    .get_children()
+                    .map(|child| self.compile_particle(child))
+                    .collect::<Result<Vec<_>, String>>()?;
+
+                match node.name.as_str() {
+                    "sequence" => XsdTerm::Sequence(particles),
+                    "choice" => XsdTerm::Choice(particles),
+                    _ => {
+                        let is_valid_all = particles.iter().all(|particle| {
+                            matches!(particle.term, XsdTerm::Element(_))
+                                && particle.max_occurs == Some(1)
+                        });
+
+                        if !is_valid_all {
+                            return Err(format!(
+                                "<xs:all> at pos {} may contain only elements which occur once",
+                                node.pos
+                            ));
+                        }
+
+                        XsdTerm::All(particles)
+                    }
+                }
+            }
+            _ => return Err(node.unsupported()),
+        };
+
+        Ok(XsdParticle {
+            term,
+            min_occurs,
+            max_occurs,
+        })
+    }
+
+    fn compile_attribute(&mut self, node: &'n SchemaNode) -> Result<XsdAttribute, String> {
+        if node.get_attribute("ref").is_some() {
+            return Err(format!(
+                "Attribute reference at pos {} is not supported",
+                node.pos
+            ));
+        }
+
+        let simple_type = match (node.get_attribute("type"), node.get_children().next()) {
+            (Some(type_name), _) => self.resolve_simple_type(node, type_name)?,
+            (None, Some(child)) if child.name == "simpleType" => {
+                let index = self.add_simple_type(None, child);
+                self.compile_simple_type(index)?;
+                index
+            }
+            (None, Some(child)) => return Err(child.unsupported()),
+            (None, None) => self.get_builtin_type("xs:string", XsdBuiltinType::String),
+        };
+
+        let result = XsdAttribute {
+            name: node.get_required_attribute("name")?.to_string(),
+            simple_type,
+            required: node.get_attribute("use") == Some("required"),
+            default: node.get_attribute("default").map(|v| v.to_string()),
+            fixed: node.get_attribute("fixed").map(|v| v.to_string()),
+        };
+
+        for value in [&result.default, &result.fixed].into_iter().flatten() {
+            self.schema.simple_types[simple_type]
+                .validate(value)
+                .map_err(|err| {
+                    format!("Value of attribute '{}' is not valid: {}", result.name, err)
+                })?;
+        }
+
+        Ok(result)
+    }
+
+    fn compile_simple_type(&mut self, index: usize) -> Result<(), String> {
+        let Some(node) = self.pending_simple_types[index].take() else {
+            return Ok(());
+        };
+
+        let restriction = match node.get_children().next() {
+            Some(child) if child.name == "restriction" => child,
+            Some(child) => return Err(child.unsupported()),
+            None => return Err(node.unsupported()),
+        };
+
+        let base = match (
+            restriction.get_attribute("base"),
+            restriction.get_children().next(),
+        ) {
+            (Some(base), _) => self.resolve_simple_type(restriction, base)?,
+            (None, Some(child)) if child.name == "simpleType" => {
+                let base = self.add_simple_type(None, child);
+                self.compile_simple_type(base)?;
+                base
+            }
+            _ => return Err(restriction.unsupported()),
+        };
+
+        let base = self.schema.simple_types[base].clone();
+        let name = self.schema.simple_types[index].name.clone();
+
+        let mut result = XsdSimpleType {
+            name: name.or(base.name),
+            builtin: base.builtin,
+            facets: base.facets,
+        };
+
+        let mut enumeration = vec![];
+        let mut patterns = vec![];
+
+        for facet in restriction.get_children() {
+            if facet.name == "simpleType" {
+                continue;
+            }
+
+            let value = facet.get_required_attribute("value")?;
+            let facets = &mut result.facets;
+
+            match facet.name.as_str() {
+                "enumeration" => enumeration.push(value.to_string()),
+                "pattern" => patterns.push(XsdPattern::parse(value)?),
+                "whiteSpace" => {
+                    facets.whitespace = Some(match value {
+                        "preserve" => XmlWhitespaceHandling::Preserve,
+                        "replace" => XmlWhitespaceHandling::Replace,
+                        "collapse" => XmlWhitespaceHandling::Collapse,
+                        _ => return Err(format!("whiteSpace '{}' is not valid", value)),
+                    })
+                }
+                "length" => facets.length = Some(parse_occurs(facet, value)?),
+                "minLength" => facets.min_length = Some(parse_occurs(facet, value)?),
+                "maxLength" => facets.max_length = Some(parse_occurs(facet, value)?),
+                "minInclusive" | "maxInclusive" | "minExclusive" | "maxExclusive" => {
+                    if !result.builtin.is_valid(value) {
+                        return Err(format!(
+                            "Value '{}' of <xs:{}> at pos {} is not valid for its base type",
+                            value, facet.name, facet.pos
+                        ));
+                    }
+
+                    let bound = match facet.name.as_str() {
+                        "minInclusive" => &mut facets.min_inclusive,
+                        "maxInclusive" => &mut facets.max_inclusive,
+                        "minExclusive" => &mut facets.min_exclusive,
+                        _ => &mut facets.max_exclusive,
+                    };
+
+                    *bound = Some(value.to_string());
+                }
+                _ => return Err(facet.unsupported()),
+            }
+        }
+
+        if !enumeration.is_empty() {
+            result.facets.enumeration = enumeration;
+        }
+
+        if !patterns.is_empty() {
+            result.facets.patterns.push(patterns);
+        }
+
+        self.schema.simple_types[index] = result;
+        Ok(())
+    }
+}
+
+fn base_name(complex_type: &XsdComplexType) -> &str {
+    complex_type.name.as_deref().unwrap_or("anonymous")
+}
+
+fn parse_occurs(node: &SchemaNode, value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| {
+        format!(
+            "'{}' at <xs:{}> at pos {} is not a non-negative integer",
+            value, node.name, node.pos
+        )
+    })
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_decimal(value: &str) -> bool {
+    let number = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+    (!integer.is_empty() || !fraction.is_empty())
+        && integer.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+}
+
+// Returns the value without "Z" or "+hh:mm"/"-hh:mm", or None if the
+// timezone is not valid
+fn strip_timezone(value: &str) -> Option<&str> {
+    if let Some(value) = value.strip_suffix('Z') {
+        return Some(value);
+    }
+
+    let split_pos = value.len().checked_sub(6)?;
+
+    let (Some(rest), Some(zone)) = (value.get(..split_pos), value.get(split_pos..)) else {
+        return Some(value);
+    };
+
+    let zone = zone.as_bytes();
+
+    if !matches!(zone[0], b'+' | b'-') || zone[3] != b':' {
+        return Some(value);
+    }
+
+    let hours = get_number(std::str::from_utf8(&zone[1..3]).ok()?)?;
+    let minutes = get_number(std::str::from_utf8(&zone[4..]).ok()?)?;
+
+    (hours < 14 && minutes < 60 || hours == 14 && minutes == 0).then_some(rest)
+}
+
+fn get_number(digits: &str) -> Option<u32> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    digits.parse().ok()
+}
+
+// YYYY-MM-DD
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.rsplitn(3, '-').collect();
+
+    let [day, month, year] = parts[..] else {
+        return false;
+    };
+
+    if year.len() < 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+
+    let (Some(year), Some(month), Some(day)) =
+        (get_number(year), get_number(month), get_number(day))
+    else {
+        return false;
+    };
+
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year => 29,
+        2 => 28,
+        _ => return false,
+    };
+
+    year > 0 && (1..=days_in_month).contains(&day)
+}
+
+// hh:mm:ss with optional fractional seconds
+fn is_time(value: &str) -> bool {
+    let (time, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    let bytes = time.as_bytes();
+
+    if bytes.len() != 8 || bytes[2] != b':' || bytes[5] != b':' {
+        return false;
+    }
+
+    let (Some(hours), Some(minutes), Some(seconds), Some(_)) = (
+        get_number(&time[..2]),
+        get_number(&time[3..5]),
+        get_number(&time[6..]),
+        get_number(fraction),
+    ) else {
+        return false;
+    };
+
+    hours < 24 && minutes < 60 && seconds < 60 || hours == 24 && minutes == 0 && seconds == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simple_type(restriction: &str) -> XsdSimpleType {
+        let xsd = format!(
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:simpleType name="T">{}</xs:simpleType>
+</xs:schema>"#,
+            restriction
+        );
+
+        let schema = XsdSchema::parse(xsd.as_bytes()).unwrap();
+        schema.simple_types[0].clone()
+    }
+
+    #[test]
+    fn test_compile_schema() {
+        let xsd = r#"<?xml version="1.0"?>
+<xsd:schema xmlns:xsd="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:orders">
+  <xsd:annotation><xsd:documentation>Orders</xsd:documentation></xsd:annotation>
+  <xsd:element name="Order" type="OrderType"/>
+  <xsd:complexType name="OrderType">
+    <xsd:sequence>
+      <xsd:element name="Item" type="ItemType" maxOccurs="unbounded"/>
+      <xsd:element ref="Note" minOccurs="0"/>
+    </xsd:sequence>
+    <xsd:attribute name="id" type="xsd:int" use="required"/>
+  </xsd:complexType>
+  <xsd:complexType name="ItemType">
+    <xsd:simpleContent>
+      <xsd:extension base="xsd:string">
+        <xsd:attribute name="sku" type="Sku"/>
+      </xsd:extension>
+    </xsd:simpleContent>
+  </xsd:complexType>
+  <xsd:simpleType name="Sku">
+    <xsd:restriction base="xsd:token"><xsd:pattern value="[A-Z]-\d+"/></xsd:restriction>
+  </xsd:simpleType>
+  <xsd:element name="Note" type="xsd:string"/>
+</xsd:schema>"#;
+
+        let schema = XsdSchema::parse(xsd.as_bytes()).unwrap();
+
+        assert_eq!(Some("urn:orders"), schema.target_namespace.as_deref());
+        assert_eq!(2, schema.elements.len());
+
+        let XsdTypeRef::Complex(order_type) = schema.find_element("Order").unwrap().type_ref else {
+            panic!("Order must have a complex type");
+        };
+
+        let order_type = &schema.complex_types[order_type];
+        assert_eq!(Some("OrderType"), order_type.name.as_deref());
+        assert_eq!("id", order_type.attributes[0].name);
+        assert!(order_type.attributes[0].required);
+
+        let XsdContent::Elements(particle) = &order_type.content else {
+            panic!("OrderType must have element content");
+        };
+
+        assert!(particle.find_element("Item").is_some());
+        assert!(particle.find_element("Note").is_some());
+
+        let XsdTerm::Sequence(particles) = &particle.term else {
+            panic!("OrderType must have a sequence");
+        };
+
+        assert_eq!(
+            (1, None),
+            (particles[0].min_occurs, particles[0].max_occurs)
+        );
+        assert_eq!(
+            (0, Some(1)),
+            (particles[1].min_occurs, particles[1].max_occurs)
+        );
+    }
+
+    #[test]
+    fn test_simple_types() {
+        let builtin = |builtin| XsdSimpleType::new(None, builtin);
+
+        assert!(builtin(XsdBuiltinType::Int).validate(" 42 ").is_ok());
+        assert!(builtin(XsdBuiltinType::Int)
+            .validate("-2147483649")
+            .is_err());
+        assert!(builtin(XsdBuiltinType::Int).validate("4.2").is_err());
+        assert!(builtin(XsdBuiltinType::Byte).validate("128").is_err());
+        assert!(builtin(XsdBuiltinType::PositiveInteger)
+            .validate("0")
+            .is_err());
+        assert!(builtin(XsdBuiltinType::Decimal).validate("-10.50").is_ok());
+        assert!(builtin(XsdBuiltinType::Decimal).validate(".5").is_ok());
+        assert!(builtin(XsdBuiltinType::Decimal).validate("1e5").is_err());
+        assert!(builtin(XsdBuiltinType::Boolean).validate("1").is_ok());
+        assert!(builtin(XsdBuiltinType::Boolean).validate("yes").is_err());
+        assert!(builtin(XsdBuiltinType::Date).validate("2024-02-29").is_ok());
+        assert!(builtin(XsdBuiltinType::Date)
+            .validate("2023-02-29")
+            .is_err());
+        assert!(builtin(XsdBuiltinType::Date)
+            .validate("2024-01-01+02:00")
+            .is_ok());
+        assert!(builtin(XsdBuiltinType::Date).validate("2024-1-01").is_err());
+        assert!(builtin(XsdBuiltinType::DateTime)
+            .validate("2024-01-01T10:00:00.5Z")
+            .is_ok());
+        assert!(builtin(XsdBuiltinType::DateTime)
+            .validate("2024-01-01 10:00:00")
+            .is_err());
+        assert!(builtin(XsdBuiltinType::Time).validate("25:00:00").is_err());
+
+        assert_eq!(
+            "'abc' is not a valid value",
+            builtin(XsdBuiltinType::Int).validate("abc").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_facets() {
+        let currency = simple_type(
+            r#"<xs:restriction base="xs:string">
+    <xs:enumeration value="EUR"/><xs:enumeration value="USD"/>
+  </xs:restriction>"#,
+        );
+
+        assert!(currency.validate("EUR").is_ok());
+        assert_eq!(
+            "'GBP' is not one of the allowed values: EUR, USD",
+            currency.validate("GBP").unwrap_err()
+        );
+
+        let quantity = simple_type(
+            r#"<xs:restriction base="xs:int">
+    <xs:minInclusive value="1"/><xs:maxExclusive value="100"/>
+  </xs:restriction>"#,
+        );
+
+        assert!(quantity.validate("1").is_ok());
+        assert!(quantity.validate("99").is_ok());
+        assert_eq!("'0' is less than 1", quantity.validate("0").unwrap_err());
+        assert_eq!(
+            "'100' is greater than or equal to 100",
+            quantity.validate("100").unwrap_err()
+        );
+
+        let code = simple_type(
+            r#"<xs:restriction>
+    <xs:simpleType>
+      <xs:restriction base="xs:string"><xs:pattern value="[A-Z]+"/></xs:restriction>
+    </xs:simpleType>
+    <xs:pattern value="A.*"/><xs:pattern value="B.*"/>
+    <xs:minLength value="2"/><xs:maxLength value="3"/>
+  </xs:restriction>"#,
+        );
+
+        assert!(code.validate("AB").is_ok());
+        assert!(code.validate("BCD").is_ok());
+        assert_eq!(
+            "'CD' does not match pattern 'A.*|B.*'",
+            code.validate("CD").unwrap_err()
+        );
+        assert_eq!(
+            "'Ab' does not match pattern '[A-Z]+'",
+            code.validate("Ab").unwrap_err()
+        );
+        assert_eq!(
+            "Length of 'ABCD' is 4 which is not allowed",
+            code.validate("ABCD").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let parse = |content: &str| {
+            let xsd = format!(
+                r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">{}</xs:schema>"#,
+                content
+            );
+
+            XsdSchema::parse(xsd.as_bytes()).unwrap_err()
+        };
+
+        assert_eq!(
+            "Type 'Missing' is not defined",
+            parse(r#"<xs:element name="A" type="Missing"/>"#)
+        );
+
+        assert_eq!(
+            "Built-in type 'xs:gYear' is not supported",
+            parse(r#"<xs:element name="A" type="xs:gYear"/>"#)
+        );
+
+        assert_eq!(
+            "<xs:include> at pos 55 is not supported",
+            parse(r#"<xs:include schemaLocation="other.xsd"/>"#)
+        );
+
+        assert_eq!(
+            "Value 'x' of <xs:minInclusive> at pos 109 is not valid for its base type",
+            parse(
+                r#"<xs:simpleType name="T"><xs:restriction base="xs:int"><xs:minInclusive value="x"/></xs:restriction></xs:simpleType>"#
+            )
+        );
+
+        assert!(XsdSchema::parse(b"<schema/>").is_err());
+    }
+}
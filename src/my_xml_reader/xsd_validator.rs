@@ -0,0 +1,755 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::xml_to_json::append_text;
+use super::{AttributesIterator, MyXmlReader, XmlTagInfo, XmlTagType};
+use super::{XsdContent, XsdParticle, XsdSchema, XsdTerm, XsdTypeRef};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XsdValidationError {
+    // Path of the element. Repeated siblings get their 1-based position among
+    // the siblings of the same name: "Order/Item[2]/Price".
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for XsdValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+// Validates the document against the schema while reading it. Children are
+// matched against the content model as they are read, so only the opened
+// elements are kept. Errors of a document which is not well-formed are
+// returned as Err.
+pub fn validate_with_schema(
+    xml: &[u8],
+    schema: &XsdSchema,
+) -> Result<Vec<XsdValidationError>, String> {
+    let mut reader = MyXmlReader::from_slice(xml)?;
+    let mut validator = XsdValidator {
+        schema,
+        opened: vec![],
+        errors: vec![],
+    };
+
+    let mut content_start = 0;
+
+    while let Some(tag) = reader.read_next_tag()? {
+        if let Some(element) = validator.opened.last_mut() {
+            append_text(
+                &reader,
+                &reader.xml[content_start..tag.start_pos],
+                &mut element.text,
+            )?;
+        }
+
+        content_start = tag.end_pos + 1;
+
+        match tag.tag_type {
+            XmlTagType::Close => validator.close_element(),
+            _ => {
+                if !validator.open_element(&reader, &tag)? {
+                    if validator.opened.is_empty() {
+                        break;
+                    }
+
+                    if let XmlTagType::Open = tag.tag_type {
+                        content_start = reader.skip_node(&tag)?.end;
+                    }
+                } else if let XmlTagType::OpenClose = tag.tag_type {
+                    validator.close_element();
+                }
+            }
+        }
+    }
+
+    Ok(validator.errors)
+}
+
+struct OpenedElement<'s, 't> {
+    name: &'t str,
+    path: String,
+    type_ref: XsdTypeRef,
+    // Amounts of the children of every local name, for the paths of the next
+    // ones
+    child_counts: HashMap<&'t str, usize>,
+    // None if the content is not a model of elements or a child did not fit
+    content: Option<ContentMatcher<'s>>,
+    // The first child which did not fit, reported when the element is closed
+    content_error: Option<XsdValidationError>,
+    text: String,
+}
+
+struct XsdValidator<'s, 't> {
+    schema: &'s XsdSchema,
+    opened: Vec<OpenedElement<'s, 't>>,
+    errors: Vec<XsdValidationError>,
+}
+
+impl<'s, 't> XsdValidator<'s, 't> {
+    // Returns false if the element is not declared, so its content is not
+    // validated
+    fn open_element(
+        &mut self,
+        reader: &MyXmlReader<'t>,
+        tag: &XmlTagInfo<'t>,
+    ) -> Result<bool, String> {
+        let name = get_local_name(tag.name);
+
+        let (path, type_ref) = match self.opened.last_mut() {
+            None => {
+                let Some(element) = self.schema.find_element(name) else {
+                    self.add_error(
+                        name.to_string(),
+                        format!("Element <{}> is not declared", name),
+                    );
+                    return Ok(false);
+                };
+
+                (name.to_string(), element.type_ref)
+            }
+            Some(parent) => {
+                let position = parent.child_counts.entry(name).or_default();
+                *position += 1;
+
+                let path = match *position {
+                    1 => format!("{}/{}", parent.path, name),
+                    _ => format!("{}/{}[{}]", parent.path, name, position),
+                };
+
+                let type_ref = match parent.type_ref {
+                    XsdTypeRef::Any => Some(XsdTypeRef::Any),
+                    XsdTypeRef::Simple(_) => None,
+                    XsdTypeRef::Complex(index) => match &self.schema.complex_types[index].content {
+                        XsdContent::Elements(particle) => {
+                            particle.find_element(name).map(|element| element.type_ref)
+                        }
+                        _ => None,
+                    },
+                };
+
+                let Some(type_ref) = type_ref else {
+                    let message =
+                        format!("Element <{}> is not expected in <{}>", name, parent.name);
+                    self.add_error(path, message);
+                    return Ok(false);
+                };
+
+                if let Some(content) = &mut parent.content {
+                    if let Err(expected) = content.match_child(name) {
+                        parent.content = None;
+                        parent.content_error = Some(XsdValidationError {
+                            path: path.clone(),
+                            message: format!("Element <{}> is not expected here{}", name, expected),
+                        });
+                    }
+                }
+
+                (path, type_ref)
+            }
+        };
+
+        self.validate_attributes(reader, tag, &path, type_ref)?;
+
+        let content = match type_ref {
+            XsdTypeRef::Complex(index) => match &self.schema.complex_types[index].content {
+                XsdContent::Elements(particle) => Some(ContentMatcher::new(particle)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        self.opened.push(OpenedElement {
+            name,
+            path,
+            type_ref,
+            child_counts: HashMap::new(),
+            content,
+            content_error: None,
+            text: String::new(),
+        });
+
+        Ok(true)
+    }
+
+    fn validate_attributes(
+        &mut self,
+        reader: &MyXmlReader<'t>,
+        tag: &XmlTagInfo<'t>,
+        path: &str,
+        type_ref: XsdTypeRef,
+    ) -> Result<(), String> {
+        let declared = match type_ref {
+            XsdTypeRef::Any => return Ok(()),
+            XsdTypeRef::Simple(_) => &[][..],
+            XsdTypeRef::Complex(index) => &self.schema.complex_types[index].attributes[..],
+        };
+
+        let mut found = vec![];
+        let mut attributes = AttributesIterator::new(tag.raw);
+
+        while let Some((name, value)) = attributes.get_next()? {
+            if name == "xmlns"
+                || name.starts_with("xmlns:")
+                || name.starts_with("xml:")
+                || name.starts_with("xsi:")
+            {
+                continue;
+            }
+
+            let local_name = get_local_name(name);
+
+            let Some(attribute) = declared.iter().find(|a| a.name == local_name) else {
+                self.add_error(
+                    path.to_string(),
+                    format!("Attribute '{}' is not allowed", name),
+                );
+                continue;
+            };
+
+            found.push(local_name);
+
+            let value = reader.expand_entities(value)?;
+
+            if let Err(err) = self.schema.simple_types[attribute.simple_type].validate(&value) {
+                self.add_error(path.to_string(), format!("Attribute '{}': {}", name, err));
+            } else if let Some(fixed) = attribute.fixed.as_ref().filter(|fixed| **fixed != value) {
+                let message = format!("Attribute '{}' must be '{}'", name, fixed);
+                self.add_error(path.to_string(), message);
+            }
+        }
+
+        for attribute in declared {
+            if attribute.required && !found.contains(&attribute.name.as_str()) {
+                let message = format!("Required attribute '{}' is missing", attribute.name);
+                self.add_error(path.to_string(), message);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn close_element(&mut self) {
+        let Some(element) = self.opened.pop() else {
+            return;
+        };
+
+        let complex_type = match element.type_ref {
+            XsdTypeRef::Any => return,
+            XsdTypeRef::Simple(index) => {
+                self.validate_value(&element, index);
+                return;
+            }
+            XsdTypeRef::Complex(index) => &self.schema.complex_types[index],
+        };
+
+        match &complex_type.content {
+            XsdContent::Simple(index) => self.validate_value(&element, *index),
+            XsdContent::Empty if !is_whitespace_only(&element.text) => {
+                self.add_error(element.path, "Element must be empty".to_string());
+            }
+            XsdContent::Empty => {}
+            XsdContent::Elements(_) => {
+                if !complex_type.mixed && !is_whitespace_only(&element.text) {
+                    self.add_error(
+                        element.path.clone(),
+                        "Text is not allowed between child elements".to_string(),
+                    );
+                }
+
+                self.validate_children(element);
+            }
+        }
+    }
+
+    fn validate_value(&mut self, element: &OpenedElement, simple_type: usize) {
+        if let Err(err) = self.schema.simple_types[simple_type].validate(&element.text) {
+            self.add_error(element.path.clone(), err);
+        }
+    }
+
+    fn validate_children(&mut self, element: OpenedElement) {
+        if let Some(error) = element.content_error {
+            self.errors.push(error);
+            return;
+        }
+
+        let Some(content) = &element.content else {
+            return;
+        };
+
+        if let Err(expected) = content.match_end() {
+            self.add_error(
+                element.path,
+                format!("Content of <{}> is incomplete{}", element.name, expected),
+            );
+        }
+    }
+
+    fn add_error(&mut self, path: String, message: String) {
+        self.errors.push(XsdValidationError { path, message });
+    }
+}
+
+// Matches children against a content model one by one as they are read, so
+// they are not kept. It is shared with DTD validation.
+pub(super) struct ContentMatcher<'s> {
+    // Every way the children read so far fit the model: stacks of what is left
+    // to match, the innermost last
+    states: Vec<Vec<ContentFrame<'s>>>,
+}
+
+impl<'s> ContentMatcher<'s> {
+    pub fn new(particle: &'s XsdParticle) -> Self {
+        Self {
+            states: vec![vec![ContentFrame::Particle { particle, count: 0 }]],
+        }
+    }
+
+    // Returns Err with the expected elements, ". Expected <A> or <B>", if the
+    // child does not fit. The matcher is not usable after that.
+    pub fn match_child(&mut self, name: &str) -> Result<(), String> {
+        let mut step = ContentStep::new(Some(name));
+
+        for state in std::mem::take(&mut self.states) {
+            step.advance(state);
+        }
+
+        if step.matched.is_empty() {
+            return Err(step.get_expected());
+        }
+
+        self.states = step.matched;
+        Ok(())
+    }
+
+    // Returns Err with the expected elements if the content is incomplete
+    pub fn match_end(&self) -> Result<(), String> {
+        let mut step = ContentStep::new(None);
+
+        for state in &self.states {
+            step.advance(state.clone());
+        }
+
+        match step.is_end_reached {
+            true => Ok(()),
+            false => Err(step.get_expected()),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum ContentFrame<'s> {
+    // The particle occurred count times. Counts of unbounded particles stop at
+    // min_occurs, as the occurrences after it are all alike.
+    Particle {
+        particle: &'s XsdParticle,
+        count: usize,
+    },
+    // Particles of a sequence from the index on
+    Sequence {
+        particles: &'s [XsdParticle],
+        index: usize,
+    },
+    // Every particle of xs:all is an element which occurs at most once
+    All {
+        particles: &'s [XsdParticle],
+        used: Vec<bool>,
+    },
+}
+
+// Frames of the same place of the model are equal
+impl PartialEq for ContentFrame<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Particle { particle, count },
+                Self::Particle {
+                    particle: other_particle,
+                    count: other_count,
+                },
+            ) => std::ptr::eq(*particle, *other_particle) && count == other_count,
+            (
+                Self::Sequence { particles, index },
+                Self::Sequence {
+                    particles: other_particles,
+                    index: other_index,
+                },
+            ) => std::ptr::eq(*particles, *other_particles) && index == other_index,
+            (
+                Self::All { particles, used },
+                Self::All {
+                    particles: other_particles,
+                    used: other_used,
+                },
+            ) => std::ptr::eq(*particles, *other_particles) && used == other_used,
+            _ => false,
+        }
+    }
+}
+
+// Moves the states over one child, or to the end if there is no name. Parts of
+// the model which may be skipped are skipped, and the elements met on the way
+// are what was expected.
+struct ContentStep<'s, 'n> {
+    name: Option<&'n str>,
+    // States which were advanced, so a part of the model which matches nothing
+    // is not repeated forever
+    visited: Vec<Vec<ContentFrame<'s>>>,
+    matched: Vec<Vec<ContentFrame<'s>>>,
+    is_end_reached: bool,
+    expected: Vec<&'s str>,
+}
+
+impl<'s, 'n> ContentStep<'s, 'n> {
+    fn new(name: Option<&'n str>) -> Self {
+        Self {
+            name,
+            visited: vec![],
+            matched: vec![],
+            is_end_reached: false,
+            expected: vec![],
+        }
+    }
+
+    fn advance(&mut self, mut state: Vec<ContentFrame<'s>>) {
+        if self.visited.contains(&state) {
+            return;
+        }
+
+        self.visited.push(state.clone());
+
+        let Some(frame) = state.pop() else {
+            self.is_end_reached = true;
+            return;
+        };
+
+        match frame {
+            ContentFrame::Particle { particle, count } => {
+                if particle.max_occurs.is_none_or(|max| count < max) {
+                    let next_count = match particle.max_occurs {
+                        Some(_) => count + 1,
+                        None => (count + 1).min(particle.min_occurs),
+                    };
+
+                    let mut next = state.clone();
+                    next.push(ContentFrame::Particle {
+                        particle,
+                        count: next_count,
+                    });
+
+                    self.advance_term(&particle.term, next);
+                }
+
+                if count >= particle.min_occurs {
+                    self.advance(state);
+                }
+            }
+            ContentFrame::Sequence { particles, index } => {
+                if let Some(particle) = particles.get(index) {
+                    state.push(ContentFrame::Sequence {
+                        particles,
+                        index: index + 1,
+                    });
+                    state.push(ContentFrame::Particle { particle, count: 0 });
+                }
+
+                self.advance(state);
+            }
+            ContentFrame::All { particles, used } => {
+                let mut is_complete = true;
+
+                for (index, particle) in particles.iter().enumerate() {
+                    let (XsdTerm::Element(element), false) = (&particle.term, used[index]) else {
+                        continue;
+                    };
+
+                    is_complete &= particle.min_occurs == 0;
+
+                    if self.name == Some(element.name.as_str()) {
+                        let mut used = used.clone();
+                        used[index] = true;
+
+                        let mut next = state.clone();
+                        next.push(ContentFrame::All { particles, used });
+                        self.add_matched(next);
+                    } else {
+                        self.expect(&element.name);
+                    }
+                }
+
+                if is_complete {
+                    self.advance(state);
+                }
+            }
+        }
+    }
+
+    fn advance_term(&mut self, term: &'s XsdTerm, mut state: Vec<ContentFrame<'s>>) {
+        match term {
+            XsdTerm::Element(element) => {
+                if self.name == Some(element.name.as_str()) {
+                    self.add_matched(state);
+                } else {
+                    self.expect(&element.name);
+                }
+            }
+            XsdTerm::Sequence(particles) => {
+                state.push(ContentFrame::Sequence {
+                    particles,
+                    index: 0,
+                });
+                self.advance(state);
+            }
+            XsdTerm::Choice(particles) => {
+                for particle in particles {
+                    let mut next = state.clone();
+                    next.push(ContentFrame::Particle { particle, count: 0 });
+                    self.advance(next);
+                }
+            }
+            XsdTerm::All(particles) => {
+                state.push(ContentFrame::All {
+                    particles,
+                    used: vec![false; particles.len()],
+                });
+                self.advance(state);
+            }
+        }
+    }
+
+    fn add_matched(&mut self, state: Vec<ContentFrame<'s>>) {
+        if !self.matched.contains(&state) {
+            self.matched.push(state);
+        }
+    }
+
+    fn expect(&mut self, name: &'s str) {
+        if !self.expected.contains(&name) {
+            self.expected.push(name);
+        }
+    }
+
+    // ". Expected <A> or <B>", or empty if nothing else was expected
+    fn get_expected(&self) -> String {
+        if self.expected.is_empty() {
+            return String::new();
+        }
+
+        let expected: Vec<String> = self
+            .expected
+            .iter()
+            .map(|name| format!("<{}>", name))
+            .collect();
+
+        format!(". Expected {}", expected.join(" or "))
+    }
+}
+
+fn get_local_name(name: &str) -> &str {
+    name.split_once(':')
+        .map_or(name, |(_, local_name)| local_name)
+}
+
+fn is_whitespace_only(text: &str) -> bool {
+    text.chars().all(|c| matches!(c, ' ' | '\t' | '\r' | '\n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER_XSD: &str = r#"<?xml version="1.0"?>
+<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="Order">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="Date" type="xs:date"/>
+        <xs:choice>
+          <xs:element name="Customer" type="xs:string"/>
+          <xs:element name="CustomerId" type="xs:int"/>
+        </xs:choice>
+        <xs:element name="Item" type="Item" minOccurs="1" maxOccurs="3"/>
+        <xs:element name="Comment" minOccurs="0">
+          <xs:complexType mixed="true">
+            <xs:sequence>
+              <xs:element name="b" type="xs:string" minOccurs="0" maxOccurs="unbounded"/>
+            </xs:sequence>
+          </xs:complexType>
+        </xs:element>
+        <xs:element name="Extra" minOccurs="0"/>
+      </xs:sequence>
+      <xs:attribute name="id" type="xs:int" use="required"/>
+      <xs:attribute name="version" type="xs:string" fixed="2"/>
+    </xs:complexType>
+  </xs:element>
+  <xs:complexType name="Item">
+    <xs:all>
+      <xs:element name="Sku" type="Sku"/>
+      <xs:element name="Price" type="Price"/>
+      <xs:element name="Gift" type="xs:boolean" minOccurs="0"/>
+    </xs:all>
+  </xs:complexType>
+  <xs:simpleType name="Sku">
+    <xs:restriction base="xs:token">
+      <xs:pattern value="[A-Z]{2}-\d{3}"/>
+    </xs:restriction>
+  </xs:simpleType>
+  <xs:complexType name="Price">
+    <xs:simpleContent>
+      <xs:extension base="xs:decimal">
+        <xs:attribute name="currency" use="required">
+          <xs:simpleType>
+            <xs:restriction base="xs:string">
+              <xs:enumeration value="EUR"/>
+              <xs:enumeration value="USD"/>
+            </xs:restriction>
+          </xs:simpleType>
+        </xs:attribute>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+</xs:schema>"#;
+
+    fn validate(xml: &str) -> Vec<String> {
+        let schema = XsdSchema::parse(ORDER_XSD.as_bytes()).unwrap();
+
+        validate_with_schema(xml.as_bytes(), &schema)
+            .unwrap()
+            .iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let xml = r#"<?xml version="1.0"?>
+<o:Order xmlns:o="urn:orders" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" id="7" version="2">
+  <!-- ordered online -->
+  <o:Date>2024-05-01</o:Date>
+  <o:CustomerId> 42 </o:CustomerId>
+  <o:Item><o:Price currency="EUR">10.50</o:Price><o:Sku>AB-123</o:Sku></o:Item>
+  <o:Item><o:Sku>CD-456</o:Sku><o:Price currency="USD">3</o:Price><o:Gift>true</o:Gift></o:Item>
+  <o:Comment>Deliver <o:b>before</o:b> noon</o:Comment>
+  <o:Extra any="thing"><Nested/>text</o:Extra>
+</o:Order>"#;
+
+        assert_eq!(Vec::<String>::new(), validate(xml));
+    }
+
+    #[test]
+    fn test_invalid_values_and_attributes() {
+        let xml = r#"<Order version="3" status="new">
+  <Date>2024-13-01</Date>
+  <Customer>ACME</Customer>
+  <Item><Sku>ab-1</Sku><Price currency="GBP">ten</Price></Item>
+  <Item><Sku>AB-123</Sku><Price>1</Price></Item>
+</Order>"#;
+
+        assert_eq!(
+            vec![
+                "Order: Attribute 'version' must be '2'",
+                "Order: Attribute 'status' is not allowed",
+                "Order: Required attribute 'id' is missing",
+                "Order/Date: '2024-13-01' is not a valid xs:date",
+                "Order/Item/Sku: 'ab-1' does not match pattern '[A-Z]{2}-\\d{3}'",
+                "Order/Item/Price: Attribute 'currency': 'GBP' is not one of the allowed values: EUR, USD",
+                "Order/Item/Price: 'ten' is not a valid xs:decimal",
+                "Order/Item[2]/Price: Required attribute 'currency' is missing",
+            ],
+            validate(xml)
+        );
+    }
+
+    #[test]
+    fn test_invalid_content() {
+        assert_eq!(
+            vec![
+                "Order/Item[4]: Element <Item> is not expected here. Expected <Comment> or <Extra>"
+            ],
+            validate(
+                r#"<Order id="1"><Date>2024-01-01</Date><Customer>A</Customer>
+<Item><Sku>AB-123</Sku><Price currency="EUR">1</Price></Item>
+<Item><Sku>AB-123</Sku><Price currency="EUR">1</Price></Item>
+<Item><Sku>AB-123</Sku><Price currency="EUR">1</Price></Item>
+<Item><Sku>AB-123</Sku><Price currency="EUR">1</Price></Item>
+</Order>"#
+            )
+        );
+
+        assert_eq!(
+            vec!["Order: Content of <Order> is incomplete. Expected <Item>"],
+            validate(r#"<Order id="1"><Date>2024-01-01</Date><Customer>A</Customer></Order>"#)
+        );
+
+        assert_eq!(
+            vec!["Order/Item: Element <Item> is not expected here. Expected <Customer> or <CustomerId>"],
+            validate(
+                r#"<Order id="1"><Date>2024-01-01</Date><Item><Sku>AB-123</Sku><Price currency="EUR">1</Price></Item></Order>"#
+            )
+        );
+
+        assert_eq!(
+            vec![
+                "Order/Item/Unknown: Element <Unknown> is not expected in <Item>",
+                "Order/Item: Content of <Item> is incomplete. Expected <Price> or <Gift>",
+                "Order/Item[2]/Sku/b: Element <b> is not expected in <Sku>",
+                "Order: Text is not allowed between child elements",
+            ],
+            validate(
+                r#"<Order id="1"><Date>2024-01-01</Date><Customer>A</Customer>
+<Item><Sku>AB-123</Sku><Unknown><Price/></Unknown></Item> stray text
+<Item><Price currency="EUR">1</Price><Sku>AB-123<b/></Sku></Item>
+</Order>"#
+            )
+        );
+
+        assert_eq!(
+            vec!["Invoice: Element <Invoice> is not declared"],
+            validate("<Invoice/>")
+        );
+    }
+
+    #[test]
+    fn test_many_children() {
+        let xsd = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="List">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="Item" type="xs:int" maxOccurs="unbounded"/>
+        <xs:sequence minOccurs="0" maxOccurs="unbounded">
+          <xs:element name="Note" type="xs:string" minOccurs="0"/>
+        </xs:sequence>
+        <xs:element name="End"/>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#;
+
+        let schema = XsdSchema::parse(xsd.as_bytes()).unwrap();
+
+        let mut xml = String::from("<List>");
+
+        for i in 0..10000 {
+            xml.push_str(&format!("<Item>{}</Item>", i));
+        }
+
+        xml.push_str("<Note/><Note/><End/><Item>x</Item></List>");
+
+        let errors: Vec<String> = validate_with_schema(xml.as_bytes(), &schema)
+            .unwrap()
+            .iter()
+            .map(|error| error.to_string())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "List/Item[10001]: 'x' is not a valid xs:int",
+                "List/Item[10001]: Element <Item> is not expected here",
+            ],
+            errors
+        );
+    }
+}